@@ -11,9 +11,10 @@
 
 use std::fmt::{Display, Formatter};
 use std::io;
+use std::marker::PhantomData;
 use std::result;
 
-use libc::__errno_location;
+use libc::{__errno_location, c_void, MAP_FAILED};
 
 /// Wrapper over [`errno`](http://man7.org/linux/man-pages/man3/errno.3.html).
 ///
@@ -116,6 +117,130 @@ pub fn errno_result<T>() -> Result<T> {
     Err(Error::last())
 }
 
+/// Returns `p` as a [`Result`], which is the last `errno` if `p` is null.
+///
+/// This is meant for wrapping the calls returning a null pointer on failure
+/// (e.g. `malloc` or `opendir`).
+///
+/// [`Result`]: type.Result.html
+///
+/// # Arguments
+///
+/// * `p`: the pointer returned by the call.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::errno::{from_ptr_result, Error};
+///
+/// let dir = unsafe { libc::opendir(b"/nonexistent\0".as_ptr() as *const libc::c_char) };
+/// assert_eq!(from_ptr_result(dir).unwrap_err(), Error::new(libc::ENOENT));
+/// ```
+pub fn from_ptr_result<T>(p: *mut T) -> Result<*mut T> {
+    if p.is_null() {
+        errno_result()
+    } else {
+        Ok(p)
+    }
+}
+
+/// Returns the address returned by `mmap` as a [`Result`], which is the last `errno`
+/// if the address is `MAP_FAILED`.
+///
+/// Unlike most pointer returning calls, `mmap` reports failures with `MAP_FAILED`
+/// (i.e. `-1`) instead of a null pointer, which is a valid mapping address.
+///
+/// [`Result`]: type.Result.html
+///
+/// # Arguments
+///
+/// * `p`: the address returned by `mmap`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use std::ptr::null_mut;
+/// use vmm_sys_util::errno::{from_mmap_result, Error};
+///
+/// // Mappings can't be empty.
+/// let addr = unsafe {
+///     libc::mmap(
+///         null_mut(),
+///         0,
+///         libc::PROT_READ,
+///         libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+///         -1,
+///         0,
+///     )
+/// };
+/// assert_eq!(from_mmap_result(addr).unwrap_err(), Error::new(libc::EINVAL));
+/// ```
+pub fn from_mmap_result(p: *mut c_void) -> Result<*mut c_void> {
+    if p == MAP_FAILED {
+        errno_result()
+    } else {
+        Ok(p)
+    }
+}
+
+/// Guard restoring `errno` to its value at the time the guard was created, when dropped.
+///
+/// Returned by [`save_errno`](fn.save_errno.html). Since `errno` is per-thread, the guard
+/// can't be sent to another thread:
+///
+/// ```compile_fail
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::errno::save_errno;
+///
+/// let guard = save_errno();
+/// std::thread::spawn(move || drop(guard));
+/// ```
+#[derive(Debug)]
+pub struct ErrnoGuard(i32, PhantomData<*const ()>);
+
+impl ErrnoGuard {
+    /// Returns the saved `errno` wrapped in an `Error`.
+    pub fn saved(&self) -> Error {
+        Error(self.0)
+    }
+}
+
+impl Drop for ErrnoGuard {
+    fn drop(&mut self) {
+        // Safe because `__errno_location` returns a valid pointer to the errno of the thread.
+        unsafe { *__errno_location() = self.0 };
+    }
+}
+
+/// Saves the current `errno`, which is restored when dropping the returned guard.
+///
+/// This protects the `errno` of a failed call from being overwritten by cleanup code
+/// (e.g. a `close` in a `Drop` implementation) running before the caller checks it.
+/// The guard is dropped on the thread that created it, since `errno` is per-thread.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::errno::{save_errno, Error};
+///
+/// unsafe { libc::close(-1) };
+/// {
+///     let _guard = save_errno();
+///     // Cleanup code overwriting errno.
+///     unsafe { libc::rmdir(b"/\0".as_ptr() as *const libc::c_char) };
+/// }
+/// assert_eq!(Error::last(), Error::new(libc::EBADF));
+/// ```
+pub fn save_errno() -> ErrnoGuard {
+    ErrnoGuard(Error::last().errno(), PhantomData)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,6 +249,63 @@ mod tests {
     use std::io::{self, Write};
     use std::os::unix::io::FromRawFd;
 
+    #[test]
+    fn test_from_ptr_result() {
+        let mut value = 0u32;
+        let p: *mut u32 = &mut value;
+        assert_eq!(from_ptr_result(p).unwrap(), p);
+
+        unsafe { *__errno_location() = libc::ENOMEM };
+        assert_eq!(
+            from_ptr_result(std::ptr::null_mut::<u32>()).unwrap_err(),
+            Error::new(libc::ENOMEM)
+        );
+    }
+
+    #[test]
+    fn test_from_mmap_result() {
+        let len = 4096;
+        let mmap = |len| unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+
+        let addr = from_mmap_result(mmap(len)).unwrap();
+        assert_ne!(addr, MAP_FAILED);
+        assert_eq!(unsafe { libc::munmap(addr, len) }, 0);
+
+        assert_eq!(
+            from_mmap_result(mmap(0)).unwrap_err(),
+            Error::new(libc::EINVAL)
+        );
+        // A null address is a valid mapping address.
+        assert_eq!(
+            from_mmap_result(std::ptr::null_mut()).unwrap(),
+            std::ptr::null_mut()
+        );
+    }
+
+    #[test]
+    fn test_save_errno() {
+        unsafe { libc::close(-1) };
+        assert_eq!(Error::last(), Error::new(libc::EBADF));
+        {
+            let guard = save_errno();
+            assert_eq!(guard.saved(), Error::new(libc::EBADF));
+
+            let ret = unsafe { libc::open(b"/nonexistent\0".as_ptr() as *const libc::c_char, 0) };
+            assert_eq!(ret, -1);
+            assert_eq!(Error::last(), Error::new(libc::ENOENT));
+        }
+        assert_eq!(Error::last(), Error::new(libc::EBADF));
+    }
+
     #[test]
     pub fn test_invalid_fd() {
         let mut file = unsafe { File::from_raw_fd(-1) };
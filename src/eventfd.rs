@@ -8,20 +8,57 @@
 //! [`eventfd`](http://man7.org/linux/man-pages/man2/eventfd.2.html).
 
 use std::fs::File;
-use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::future::Future;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, RawFd};
+use std::pin::Pin;
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 use std::{io, mem, result};
 
-use libc::{c_void, dup, eventfd, read, write};
+use libc::{c_void, dup, eventfd, poll, pollfd, read, write, EBADF, POLLIN, POLLNVAL};
 
 // Reexport commonly used flags from libc.
 pub use libc::{EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
 
+use crate::poll::poll_fds;
+
 /// A safe wrapper around Linux
 /// [`eventfd`](http://man7.org/linux/man-pages/man2/eventfd.2.html).
 pub struct EventFd {
     eventfd: File,
 }
 
+/// Counters of the operations on an [`EventFd`](struct.EventFd.html), e.g. for diagnosing
+/// lost wake ups.
+///
+/// The counters are updated by an [`InstrumentedEventFd`](struct.InstrumentedEventFd.html)
+/// and its clones. They count the successful reads and writes made through it.
+#[derive(Debug, Default)]
+pub struct EventFdMetrics {
+    reads: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl EventFdMetrics {
+    /// Create new counters, starting at zero.
+    pub fn new() -> Self {
+        EventFdMetrics::default()
+    }
+
+    /// Get the number of successful reads.
+    pub fn reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of successful writes.
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+}
+
 impl EventFd {
     /// Create a new EventFd with an initial value.
     ///
@@ -38,9 +75,34 @@ impl EventFd {
     /// EventFd::new(EFD_NONBLOCK).unwrap();
     /// ```
     pub fn new(flag: i32) -> result::Result<EventFd, io::Error> {
+        EventFd::with_initval(0, flag)
+    }
+
+    /// Create a new EventFd whose counter starts at `initval`.
+    ///
+    /// This allows e.g. a counting semaphore (`EFD_SEMAPHORE`) to start with
+    /// `initval` permits available. Note that the kernel only accepts a `u32`
+    /// initial value, even though the counter itself is a `u64`.
+    ///
+    /// # Arguments
+    ///
+    /// * `initval`: the initial value of the counter.
+    /// * `flag`: the flags used for creating the `EventFd`, as for
+    ///   [`new`](struct.EventFd.html#method.new).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    ///
+    /// let evt = EventFd::with_initval(3, EFD_NONBLOCK).unwrap();
+    /// assert_eq!(evt.read().unwrap(), 3);
+    /// ```
+    pub fn with_initval(initval: u32, flag: i32) -> result::Result<EventFd, io::Error> {
         // This is safe because eventfd merely allocated an eventfd for
         // our process and we handle the error case.
-        let ret = unsafe { eventfd(0, flag) };
+        let ret = unsafe { eventfd(initval, flag) };
         if ret < 0 {
             Err(io::Error::last_os_error())
         } else {
@@ -89,11 +151,45 @@ impl EventFd {
         }
     }
 
+    /// Try to add a value to the eventfd's counter.
+    ///
+    /// The counter can hold at most `0xffff_ffff_ffff_fffe`. Returns `Ok(false)`
+    /// without changing the counter if adding `v` would exceed that, so that the
+    /// caller can back off until the counter is read. This requires the `EventFd`
+    /// to be nonblocking (`EFD_NONBLOCK`), otherwise the call blocks as
+    /// [`write`](struct.EventFd.html#method.write) does.
+    ///
+    /// # Arguments
+    ///
+    /// * `v`: the value to be added to the eventfd's counter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    ///
+    /// let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+    /// assert!(evt.try_write(0xffff_ffff_ffff_fffe).unwrap());
+    /// assert!(!evt.try_write(1).unwrap());
+    /// ```
+    pub fn try_write(&self, v: u64) -> result::Result<bool, io::Error> {
+        match self.write(v) {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Read a value from the eventfd.
     ///
     /// If the counter is zero, this would either block
     /// until the counter becomes nonzero, or fail with the
     /// error EAGAIN if the file descriptor has been made nonblocking.
+    /// A blocked read fails with EINTR when it is interrupted by a signal
+    /// handler, unless the handler was registered with `SA_RESTART`; see
+    /// [`read_uninterruptible`](struct.EventFd.html#method.read_uninterruptible)
+    /// for retrying instead.
     ///
     /// # Examples
     ///
@@ -123,6 +219,139 @@ impl EventFd {
         }
     }
 
+    /// Read a value from the eventfd, retrying when interrupted by a signal.
+    ///
+    /// This is the same as [`read`](struct.EventFd.html#method.read), except that
+    /// it doesn't fail with EINTR: when a signal handler interrupts the read, the
+    /// read is started again. Use `read` for observing the interruptions instead,
+    /// e.g. when a signal is used for asking the reading thread to stop.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::EventFd;
+    ///
+    /// let evt = EventFd::new(0).unwrap();
+    /// evt.write(55).unwrap();
+    /// assert_eq!(evt.read_uninterruptible().unwrap(), 55);
+    /// ```
+    pub fn read_uninterruptible(&self) -> result::Result<u64, io::Error> {
+        loop {
+            match self.read() {
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                res => return res,
+            }
+        }
+    }
+
+    /// Read a value from the EventFd and report whether this drained its counter.
+    ///
+    /// In the default mode, reading always resets the counter to zero, while in
+    /// semaphore mode (`EFD_SEMAPHORE`) it only decrements it by one, so the
+    /// counter is drained only by reading it when it was one. On success, returns
+    /// a tuple `(value read, drained)`. This blocks until the counter is nonzero
+    /// if the EventFd was not created with `EFD_NONBLOCK`.
+    ///
+    /// The counter may be incremented again by another handle right after it was
+    /// drained, in which case `drained` is false.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_SEMAPHORE};
+    ///
+    /// let evt = EventFd::new(EFD_SEMAPHORE).unwrap();
+    /// evt.write(2).unwrap();
+    /// assert_eq!(evt.wait_and_report().unwrap(), (1, false));
+    /// assert_eq!(evt.wait_and_report().unwrap(), (1, true));
+    /// ```
+    pub fn wait_and_report(&self) -> result::Result<(u64, bool), io::Error> {
+        let value = self.read()?;
+        Ok((value, !self.is_readable()?))
+    }
+
+    /// Get the current value of the EventFd's counter without consuming it.
+    ///
+    /// There is no system call for peeking at the counter, so this reads it
+    /// and adds the value read back. This is inherently racy: another handle may
+    /// read the counter, or be woken up, in the meantime, and a read by another
+    /// handle between checking the counter and reading it makes this block if
+    /// the EventFd was not created with `EFD_NONBLOCK`. Thus it is only meant for
+    /// diagnostics and must not be relied on for correctness.
+    ///
+    /// In semaphore mode (`EFD_SEMAPHORE`) reading only takes one from the
+    /// counter, so this returns 1 whenever the counter is nonzero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    ///
+    /// let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+    /// evt.write(5).unwrap();
+    /// assert_eq!(evt.peek().unwrap(), 5);
+    /// assert_eq!(evt.read().unwrap(), 5);
+    /// ```
+    pub fn peek(&self) -> result::Result<u64, io::Error> {
+        if !self.is_readable()? {
+            return Ok(0);
+        }
+        let value = self.read()?;
+        self.write(value)?;
+        Ok(value)
+    }
+
+    /// Reset the EventFd's counter to zero, returning the discarded value.
+    ///
+    /// Unlike [`read`](struct.EventFd.html#method.read), this doesn't block when the
+    /// counter is already zero, but returns 0, so it can be used for discarding the
+    /// pending events (e.g. when pausing) whether or not the EventFd was created with
+    /// `EFD_NONBLOCK`. This is a single read, so in semaphore mode (`EFD_SEMAPHORE`) it only
+    /// takes one from the counter, as [`read`](struct.EventFd.html#method.read) does. As for
+    /// [`peek`](struct.EventFd.html#method.peek), the counter is checked before reading it,
+    /// so a read by another handle in between makes this block if the EventFd was not
+    /// created with `EFD_NONBLOCK`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::EventFd;
+    ///
+    /// let evt = EventFd::new(0).unwrap();
+    /// evt.write(5).unwrap();
+    /// assert_eq!(evt.clear().unwrap(), 5);
+    /// assert_eq!(evt.clear().unwrap(), 0);
+    /// ```
+    pub fn clear(&self) -> result::Result<u64, io::Error> {
+        if !self.is_readable()? {
+            return Ok(0);
+        }
+        match self.read() {
+            // Another nonblocking handle drained the counter in the meantime.
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            res => res,
+        }
+    }
+
+    // Check whether the counter is nonzero, which is exactly when the EventFd is readable.
+    fn is_readable(&self) -> result::Result<bool, io::Error> {
+        let mut pfd = pollfd {
+            fd: self.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+        // This is safe because we give a single valid pollfd and check the return value.
+        let ret = unsafe { poll(&mut pfd, 1, 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(pfd.revents & POLLIN != 0)
+    }
+
     /// Clone this EventFd.
     ///
     /// This internally creates a new file descriptor and it will share the same
@@ -152,6 +381,75 @@ impl EventFd {
             })
         }
     }
+
+    /// Create a pair of EventFd handles sharing the same counter.
+    ///
+    /// Both handles refer to the same open file description (the second one is
+    /// obtained through [`dup`](http://man7.org/linux/man-pages/man2/dup.2.html)),
+    /// so a value written on one of them can be read from the other. Note that
+    /// this is not a pipe: there is a single counter shared by both ends, so a
+    /// handle can also read back what it has written itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag`: The flags used for creating the underlying `EventFd`.
+    ///   Refer to Linux [`eventfd`](http://man7.org/linux/man-pages/man2/eventfd.2.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    ///
+    /// let (notifier, waiter) = EventFd::pair(EFD_NONBLOCK).unwrap();
+    /// notifier.write(1).unwrap();
+    /// assert_eq!(waiter.read().unwrap(), 1);
+    /// ```
+    pub fn pair(flag: i32) -> result::Result<(EventFd, EventFd), io::Error> {
+        let evt = EventFd::new(flag)?;
+        let evt_clone = evt.try_clone()?;
+        Ok((evt, evt_clone))
+    }
+
+    /// Create two distinct EventFds, for signaling in each direction between two threads.
+    ///
+    /// Unlike with [`pair`](#method.pair), the EventFds don't share a counter: each one is a
+    /// separate channel, so e.g. a request signaled on the first one and its response
+    /// signaled on the second one can't consume each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag`: The flags used for creating both `EventFd`s.
+    ///   Refer to Linux [`eventfd`](http://man7.org/linux/man-pages/man2/eventfd.2.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    ///
+    /// let (request, response) = EventFd::bidirectional(EFD_NONBLOCK).unwrap();
+    /// request.write(1).unwrap();
+    /// assert!(response.read().is_err());
+    /// assert_eq!(request.read().unwrap(), 1);
+    /// ```
+    pub fn bidirectional(flag: i32) -> result::Result<(EventFd, EventFd), io::Error> {
+        Ok((EventFd::new(flag)?, EventFd::new(flag)?))
+    }
+
+    /// Get a future that resolves when the EventFd is readable.
+    ///
+    /// The future doesn't depend on any particular async runtime: while the EventFd is not
+    /// readable, it registers its waker with `reactor`, which is supplied by the caller.
+    /// Resolving the future doesn't read the counter, so a [`read`](#method.read) is needed
+    /// afterwards to consume it.
+    ///
+    /// # Arguments
+    ///
+    /// * `reactor`: the reactor waking the task that polls the future.
+    pub fn readable<'a, R: Reactor + ?Sized>(&'a self, reactor: &'a R) -> Readable<'a, R> {
+        Readable { evt: self, reactor }
+    }
 }
 
 impl AsRawFd for EventFd {
@@ -160,6 +458,12 @@ impl AsRawFd for EventFd {
     }
 }
 
+impl AsFd for EventFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.eventfd.as_fd()
+    }
+}
+
 impl FromRawFd for EventFd {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
         EventFd {
@@ -168,9 +472,410 @@ impl FromRawFd for EventFd {
     }
 }
 
+fn check_chunk_len(len: usize) -> result::Result<(), io::Error> {
+    if len != mem::size_of::<u64>() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "eventfd buffers must be exactly 8 bytes long",
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the counter of the eventfd as an 8 byte integer in native endianness.
+///
+/// This is meant for generic code working with any `Read` implementer;
+/// [`EventFd::read`](struct.EventFd.html#method.read) should be preferred otherwise.
+/// Reading into a buffer which is not exactly 8 bytes long fails with
+/// `io::ErrorKind::InvalidInput`.
+impl io::Read for EventFd {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        check_chunk_len(buf.len())?;
+        let v = EventFd::read(self)?;
+        buf.copy_from_slice(&v.to_ne_bytes());
+        Ok(buf.len())
+    }
+}
+
+/// Adds an 8 byte integer in native endianness to the counter of the eventfd.
+///
+/// This is meant for generic code working with any `Write` implementer;
+/// [`EventFd::write`](struct.EventFd.html#method.write) should be preferred otherwise.
+/// Writing a buffer which is not exactly 8 bytes long fails with
+/// `io::ErrorKind::InvalidInput`.
+impl io::Write for EventFd {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        check_chunk_len(buf.len())?;
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(buf);
+        EventFd::write(self, u64::from_ne_bytes(bytes))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// An [`EventFd`](struct.EventFd.html) counting its reads and writes in
+/// [`EventFdMetrics`](struct.EventFdMetrics.html).
+///
+/// The counters are shared by the clones of the wrapper, and can be shared by several
+/// wrappers. Only the operations made through the wrapper are counted, so a plain `EventFd`
+/// has no overhead.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use std::sync::Arc;
+/// use vmm_sys_util::eventfd::{EventFdMetrics, InstrumentedEventFd, EFD_NONBLOCK};
+///
+/// let metrics = Arc::new(EventFdMetrics::new());
+/// let evt = InstrumentedEventFd::new(EFD_NONBLOCK, metrics.clone()).unwrap();
+/// evt.write(1).unwrap();
+/// assert_eq!(metrics.writes(), 1);
+/// ```
+pub struct InstrumentedEventFd {
+    evt: EventFd,
+    metrics: Arc<EventFdMetrics>,
+}
+
+impl InstrumentedEventFd {
+    /// Create a new EventFd counting its reads and writes in `metrics`.
+    ///
+    /// # Arguments
+    ///
+    /// * `flag`: the flags used for creating the `EventFd`, as for
+    ///   [`EventFd::new`](struct.EventFd.html#method.new).
+    /// * `metrics`: the counters to be updated.
+    pub fn new(
+        flag: i32,
+        metrics: Arc<EventFdMetrics>,
+    ) -> result::Result<InstrumentedEventFd, io::Error> {
+        Ok(InstrumentedEventFd {
+            evt: EventFd::new(flag)?,
+            metrics,
+        })
+    }
+
+    /// Add a value to the eventfd's counter, as [`EventFd::write`], counting the write on
+    /// success.
+    ///
+    /// [`EventFd::write`]: struct.EventFd.html#method.write
+    ///
+    /// # Arguments
+    ///
+    /// * `v`: the value to be added to the eventfd's counter.
+    pub fn write(&self, v: u64) -> result::Result<(), io::Error> {
+        self.evt.write(v)?;
+        self.metrics.writes.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Read a value from the eventfd, as [`EventFd::read`], counting the read on success.
+    ///
+    /// [`EventFd::read`]: struct.EventFd.html#method.read
+    pub fn read(&self) -> result::Result<u64, io::Error> {
+        let v = self.evt.read()?;
+        self.metrics.reads.fetch_add(1, Ordering::Relaxed);
+        Ok(v)
+    }
+
+    /// Clone this wrapper, sharing its counters.
+    pub fn try_clone(&self) -> result::Result<InstrumentedEventFd, io::Error> {
+        Ok(InstrumentedEventFd {
+            evt: self.evt.try_clone()?,
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// Returns the wrapped EventFd, whose operations are not counted.
+    pub fn get_ref(&self) -> &EventFd {
+        &self.evt
+    }
+
+    /// Returns the counters updated by this wrapper.
+    pub fn metrics(&self) -> &Arc<EventFdMetrics> {
+        &self.metrics
+    }
+}
+
+impl AsRawFd for InstrumentedEventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.evt.as_raw_fd()
+    }
+}
+
+impl AsFd for InstrumentedEventFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.evt.as_fd()
+    }
+}
+
+/// A source of readiness events for futures, e.g. the event loop of an async runtime.
+///
+/// This is the only part of an async runtime needed by
+/// [`EventFd::readable`](struct.EventFd.html#method.readable), so that it can be used with any
+/// executor.
+pub trait Reactor {
+    /// Wake `waker` once `fd` is readable.
+    ///
+    /// The registration is expected to be one-shot: after being woken, the task polls the
+    /// future again, which registers again if `fd` is still not readable.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`: the file descriptor to watch.
+    /// * `waker`: the waker of the task to be woken.
+    fn register_readable(&self, fd: RawFd, waker: Waker) -> result::Result<(), io::Error>;
+}
+
+/// Future returned by [`EventFd::readable`](struct.EventFd.html#method.readable).
+pub struct Readable<'a, R: Reactor + ?Sized> {
+    evt: &'a EventFd,
+    reactor: &'a R,
+}
+
+impl<'a, R: Reactor + ?Sized> Future for Readable<'a, R> {
+    type Output = result::Result<(), io::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.evt.is_readable() {
+            Ok(true) => return Poll::Ready(Ok(())),
+            Ok(false) => (),
+            Err(e) => return Poll::Ready(Err(e)),
+        }
+        if let Err(e) = self
+            .reactor
+            .register_readable(self.evt.as_raw_fd(), cx.waker().clone())
+        {
+            return Poll::Ready(Err(e));
+        }
+        // Check again, in case the EventFd was written before the registration and the
+        // reactor only reports new events.
+        match self.evt.is_readable() {
+            Ok(true) => Poll::Ready(Ok(())),
+            Ok(false) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// A one-shot notification primitive backed by an [`EventFd`](struct.EventFd.html).
+///
+/// Any number of calls to [`notify`](struct.Notify.html#method.notify) made before the
+/// waiter observes them result in a single wake up, because waiting drains the whole
+/// eventfd counter.
+pub struct Notify {
+    evt: EventFd,
+}
+
+impl Notify {
+    /// Create a new, not yet notified, `Notify`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::Notify;
+    ///
+    /// let notify = Notify::new().unwrap();
+    /// notify.notify().unwrap();
+    /// notify.wait().unwrap();
+    /// ```
+    pub fn new() -> result::Result<Notify, io::Error> {
+        Ok(Notify {
+            evt: EventFd::new(EFD_NONBLOCK)?,
+        })
+    }
+
+    /// Signal the waiter.
+    ///
+    /// Notifying multiple times before a wait wakes the waiter only once.
+    pub fn notify(&self) -> result::Result<(), io::Error> {
+        match self.evt.write(1) {
+            // The counter is saturated, so the waiter will be woken anyway.
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            r => r,
+        }
+    }
+
+    /// Block until the `Notify` is notified, consuming the notification.
+    pub fn wait(&self) -> result::Result<(), io::Error> {
+        while !self.wait_inner(None)? {}
+        Ok(())
+    }
+
+    /// Block until the `Notify` is notified or `timeout` elapses.
+    ///
+    /// Returns `true` if a notification was consumed and `false` on timeout. A wait
+    /// interrupted by a signal is resumed without extending the timeout.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout`: the maximum time to wait for a notification. It is rounded
+    ///   down to milliseconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::eventfd::Notify;
+    ///
+    /// let notify = Notify::new().unwrap();
+    /// assert!(!notify.wait_timeout(Duration::from_millis(1)).unwrap());
+    /// ```
+    pub fn wait_timeout(&self, timeout: Duration) -> result::Result<bool, io::Error> {
+        self.wait_inner(Some(timeout))
+    }
+
+    // Wait for the eventfd to become readable, then drain its counter. Returns whether a
+    // notification was consumed.
+    fn wait_inner(&self, timeout: Option<Duration>) -> result::Result<bool, io::Error> {
+        let mut pfd = pollfd {
+            fd: self.evt.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        };
+        let ready = poll_fds(slice::from_mut(&mut pfd), timeout)
+            .map_err(|e| io::Error::from_raw_os_error(e.errno()))?;
+        if ready == 0 {
+            return Ok(false);
+        }
+        match self.evt.read() {
+            Ok(_) => Ok(true),
+            // Another waiter consumed the notification first.
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl AsRawFd for Notify {
+    fn as_raw_fd(&self) -> RawFd {
+        self.evt.as_raw_fd()
+    }
+}
+
+impl AsFd for Notify {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.evt.as_fd()
+    }
+}
+
+/// Wait until at least one of `evts` is readable, and return the indices of the readable ones.
+///
+/// This is a single [`poll`](http://man7.org/linux/man-pages/man2/poll.2.html) call, meant
+/// for one-off waits on a few eventfds where creating a
+/// [`PollContext`](../poll/struct.PollContext.html) would be overkill. The counters are not
+/// read, so the eventfds stay readable until the caller reads them. The indices are in
+/// ascending order. An empty list is returned when `timeout` elapses. A wait interrupted by
+/// a signal is resumed without extending the timeout.
+///
+/// # Arguments
+///
+/// * `evts`: the eventfds to wait for.
+/// * `timeout`: the maximum time to wait, rounded down to milliseconds. If `None`, wait
+///   for as long as it takes.
+///
+/// # Errors
+///
+/// Fails with `EBADF` if one of `evts` is not an open file descriptor.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::eventfd::{wait_any, EventFd};
+///
+/// let evt1 = EventFd::new(0).unwrap();
+/// let evt2 = EventFd::new(0).unwrap();
+/// evt2.write(1).unwrap();
+/// assert_eq!(wait_any(&[&evt1, &evt2], None).unwrap(), [1]);
+/// ```
+pub fn wait_any(
+    evts: &[&EventFd],
+    timeout: Option<Duration>,
+) -> result::Result<Vec<usize>, io::Error> {
+    let mut pfds: Vec<pollfd> = evts
+        .iter()
+        .map(|evt| pollfd {
+            fd: evt.as_raw_fd(),
+            events: POLLIN,
+            revents: 0,
+        })
+        .collect();
+    poll_fds(&mut pfds, timeout).map_err(|e| io::Error::from_raw_os_error(e.errno()))?;
+    if pfds.iter().any(|pfd| pfd.revents & POLLNVAL != 0) {
+        return Err(io::Error::from_raw_os_error(EBADF));
+    }
+    Ok(pfds
+        .iter()
+        .enumerate()
+        .filter(|(_, pfd)| pfd.revents != 0)
+        .map(|(i, _)| i)
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::signal::{register_signal_handler, Killable, SignalFlag, SIGRTMIN};
+    use libc::{c_int, siginfo_t};
+    use std::cell::RefCell;
+    use std::sync::atomic::AtomicBool;
+    use std::task::Wake;
+    use std::thread;
+    use std::time::Instant;
+
+    // A reactor waiting for a single registered file descriptor at a time.
+    #[derive(Default)]
+    struct TestReactor {
+        registered: RefCell<Option<(RawFd, Waker)>>,
+    }
+
+    impl Reactor for TestReactor {
+        fn register_readable(&self, fd: RawFd, waker: Waker) -> result::Result<(), io::Error> {
+            *self.registered.borrow_mut() = Some((fd, waker));
+            Ok(())
+        }
+    }
+
+    impl TestReactor {
+        fn turn(&self) {
+            let (fd, waker) = self.registered.borrow_mut().take().unwrap();
+            assert!(crate::poll::wait_readable(&fd, None).unwrap());
+            waker.wake();
+        }
+    }
+
+    #[derive(Default)]
+    struct FlagWaker(AtomicBool);
+
+    impl Wake for FlagWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    // Poll `fut` to completion, returning its output and the number of times it was pending.
+    fn block_on<F: Future>(fut: F, reactor: &TestReactor) -> (F::Output, usize) {
+        let mut fut = Box::pin(fut);
+        let flag = Arc::new(FlagWaker::default());
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut pending = 0;
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return (output, pending);
+            }
+            pending += 1;
+            reactor.turn();
+            assert!(flag.0.swap(false, Ordering::SeqCst));
+        }
+    }
 
     #[test]
     fn test_new() {
@@ -178,6 +883,40 @@ mod tests {
         EventFd::new(0).unwrap();
     }
 
+    #[test]
+    fn test_readable() {
+        let reactor = TestReactor::default();
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+
+        evt.write(1).unwrap();
+        let (res, pending) = block_on(evt.readable(&reactor), &reactor);
+        res.unwrap();
+        assert_eq!(pending, 0);
+        assert_eq!(evt.read().unwrap(), 1);
+
+        let writer_evt = evt.try_clone().unwrap();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            writer_evt.write(2).unwrap();
+        });
+        let (res, pending) = block_on(evt.readable(&reactor), &reactor);
+        res.unwrap();
+        assert_eq!(pending, 1);
+        assert_eq!(evt.read().unwrap(), 2);
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn test_with_initval() {
+        let evt = EventFd::with_initval(2, EFD_NONBLOCK | EFD_SEMAPHORE).unwrap();
+        assert_eq!(evt.read().unwrap(), 1);
+        assert_eq!(evt.read().unwrap(), 1);
+        assert_eq!(evt.read().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        let evt = EventFd::with_initval(u32::MAX, EFD_NONBLOCK).unwrap();
+        assert_eq!(evt.read().unwrap(), u64::from(u32::MAX));
+    }
+
     #[test]
     fn test_read_write() {
         let evt = EventFd::new(EFD_NONBLOCK).unwrap();
@@ -185,6 +924,28 @@ mod tests {
         assert_eq!(evt.read().unwrap(), 55);
     }
 
+    #[test]
+    fn test_io_read_write() {
+        use std::io::{Read, Write};
+
+        let mut evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        evt.write_all(&7u64.to_ne_bytes()).unwrap();
+        assert_eq!(
+            Write::write(&mut evt, &[0u8; 4]).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(evt.peek().unwrap(), 7);
+
+        let mut small = [0u8; 4];
+        assert_eq!(
+            Read::read(&mut evt, &mut small).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let mut buf = [0u8; 8];
+        evt.read_exact(&mut buf).unwrap();
+        assert_eq!(u64::from_ne_bytes(buf), 7);
+    }
+
     #[test]
     fn test_write_overflow() {
         let evt = EventFd::new(EFD_NONBLOCK).unwrap();
@@ -195,6 +956,53 @@ mod tests {
             _ => panic!("Unexpected"),
         }
     }
+
+    #[test]
+    fn test_try_write() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        assert!(evt.try_write(u64::MAX - 2).unwrap());
+        assert!(evt.try_write(1).unwrap());
+        // The counter is at its maximum value.
+        assert!(!evt.try_write(1).unwrap());
+        assert!(evt.try_write(0).unwrap());
+        assert_eq!(evt.read().unwrap(), u64::MAX - 1);
+
+        assert!(evt.try_write(5).unwrap());
+        assert_eq!(evt.read().unwrap(), 5);
+        // u64::MAX is not a valid value.
+        assert!(evt.try_write(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_read_uninterruptible() {
+        static INTERRUPTED: SignalFlag = SignalFlag::new();
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            INTERRUPTED.set();
+        }
+
+        // The handler isn't registered with `SA_RESTART`, so it makes the blocked read fail
+        // with EINTR.
+        let signal = SIGRTMIN() + 10;
+        register_signal_handler(signal, handle_signal).unwrap();
+
+        let evt = Arc::new(EventFd::new(0).unwrap());
+        let reader_evt = evt.clone();
+        let reader = thread::spawn(move || reader_evt.read_uninterruptible());
+
+        // Keep interrupting the reader until the handler runs.
+        while !INTERRUPTED.is_set() {
+            reader.kill(signal).unwrap();
+            thread::sleep(Duration::from_millis(10));
+        }
+        // Signal again once the reader is certainly blocked.
+        thread::sleep(Duration::from_millis(50));
+        reader.kill(signal).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        evt.write(42).unwrap();
+        assert_eq!(reader.join().unwrap().unwrap(), 42);
+    }
+
     #[test]
     fn test_read_nothing() {
         let evt = EventFd::new(EFD_NONBLOCK).unwrap();
@@ -204,6 +1012,102 @@ mod tests {
             _ => panic!("Unexpected"),
         }
     }
+
+    #[test]
+    fn test_wait_and_report() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        evt.write(3).unwrap();
+        assert_eq!(evt.wait_and_report().unwrap(), (3, true));
+        assert_eq!(
+            evt.wait_and_report().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn test_wait_and_report_semaphore() {
+        let evt = EventFd::new(EFD_NONBLOCK | EFD_SEMAPHORE).unwrap();
+        evt.write(3).unwrap();
+        assert_eq!(evt.wait_and_report().unwrap(), (1, false));
+        assert_eq!(evt.wait_and_report().unwrap(), (1, false));
+        assert_eq!(evt.wait_and_report().unwrap(), (1, true));
+        assert!(evt.read().is_err());
+
+        // A blocking wait is woken up by a write from another thread.
+        let evt = Arc::new(EventFd::new(EFD_SEMAPHORE).unwrap());
+        let writer = evt.clone();
+        let handle = thread::spawn(move || writer.write(1).unwrap());
+        assert_eq!(evt.wait_and_report().unwrap(), (1, true));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_peek() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        assert_eq!(evt.peek().unwrap(), 0);
+        evt.write(42).unwrap();
+        assert_eq!(evt.peek().unwrap(), 42);
+        assert_eq!(evt.peek().unwrap(), 42);
+        assert_eq!(evt.read().unwrap(), 42);
+        assert_eq!(evt.peek().unwrap(), 0);
+
+        // Peeking a blocking EventFd with a zero counter doesn't block.
+        let evt = EventFd::new(0).unwrap();
+        assert_eq!(evt.peek().unwrap(), 0);
+
+        let evt = EventFd::with_initval(3, EFD_NONBLOCK | EFD_SEMAPHORE).unwrap();
+        assert_eq!(evt.peek().unwrap(), 1);
+        for _ in 0..3 {
+            assert_eq!(evt.read().unwrap(), 1);
+        }
+        assert!(evt.read().is_err());
+    }
+
+    #[test]
+    fn test_clear() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        assert_eq!(evt.clear().unwrap(), 0);
+        evt.write(42).unwrap();
+        assert_eq!(evt.clear().unwrap(), 42);
+        assert_eq!(evt.read().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        // Clearing a blocking EventFd with a zero counter doesn't block.
+        let evt = EventFd::new(0).unwrap();
+        assert_eq!(evt.clear().unwrap(), 0);
+
+        // A semaphore is only decremented by one.
+        let evt = EventFd::with_initval(2, EFD_NONBLOCK | EFD_SEMAPHORE).unwrap();
+        assert_eq!(evt.clear().unwrap(), 1);
+        assert_eq!(evt.clear().unwrap(), 1);
+        assert_eq!(evt.clear().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_instrumented_eventfd() {
+        let metrics = Arc::new(EventFdMetrics::new());
+        let evt = InstrumentedEventFd::new(EFD_NONBLOCK, metrics.clone()).unwrap();
+        for _ in 0..3 {
+            evt.write(1).unwrap();
+        }
+        assert_eq!(evt.read().unwrap(), 3);
+        // Failed operations aren't counted.
+        assert!(evt.read().is_err());
+        assert_eq!((metrics.reads(), metrics.writes()), (1, 3));
+
+        // The clones share the counters.
+        let clone = evt.try_clone().unwrap();
+        clone.write(1).unwrap();
+        clone.read().unwrap();
+        assert_eq!((metrics.reads(), metrics.writes()), (2, 4));
+
+        assert!(Arc::ptr_eq(clone.metrics(), &metrics));
+
+        // The operations on the wrapped EventFd aren't counted.
+        evt.get_ref().write(1).unwrap();
+        evt.get_ref().read().unwrap();
+        assert_eq!((metrics.reads(), metrics.writes()), (2, 4));
+    }
+
     #[test]
     fn test_clone() {
         let evt = EventFd::new(EFD_NONBLOCK).unwrap();
@@ -211,4 +1115,169 @@ mod tests {
         evt.write(923).unwrap();
         assert_eq!(evt_clone.read().unwrap(), 923);
     }
+
+    #[test]
+    fn test_pair() {
+        let (evt1, evt2) = EventFd::pair(EFD_NONBLOCK).unwrap();
+        assert_ne!(evt1.as_raw_fd(), evt2.as_raw_fd());
+
+        evt1.write(42).unwrap();
+        assert_eq!(evt2.read().unwrap(), 42);
+
+        // Both ends share the same counter, so it is now drained for both.
+        match evt1.read() {
+            Err(ref inner) if inner.kind() == io::ErrorKind::WouldBlock => (),
+            _ => panic!("Unexpected"),
+        }
+    }
+
+    #[test]
+    fn test_bidirectional() {
+        let (request, response) = EventFd::bidirectional(0).unwrap();
+        let request_clone = request.try_clone().unwrap();
+        let response_clone = response.try_clone().unwrap();
+
+        // The other thread answers each request with the request value plus one.
+        let responder = thread::spawn(move || {
+            for _ in 0..3 {
+                let v = request_clone.read().unwrap();
+                response_clone.write(v + 1).unwrap();
+            }
+        });
+        for v in &[1, 10, 100] {
+            request.write(*v).unwrap();
+            assert_eq!(response.read().unwrap(), v + 1);
+        }
+        responder.join().unwrap();
+
+        // Each direction has its own counter.
+        let (evt1, evt2) = EventFd::bidirectional(EFD_NONBLOCK).unwrap();
+        evt1.write(1).unwrap();
+        assert_eq!(evt2.read().unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        evt2.write(2).unwrap();
+        assert_eq!(evt1.read().unwrap(), 1);
+        assert_eq!(evt2.read().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_as_fd() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        assert_eq!(evt.as_fd().as_raw_fd(), evt.as_raw_fd());
+
+        let notify = Notify::new().unwrap();
+        assert_eq!(notify.as_fd().as_raw_fd(), notify.as_raw_fd());
+    }
+
+    #[test]
+    fn test_wait_any() {
+        let evts: Vec<EventFd> = (0..3).map(|_| EventFd::new(0).unwrap()).collect();
+        let refs: Vec<&EventFd> = evts.iter().collect();
+        assert!(wait_any(&refs, Some(Duration::from_millis(10)))
+            .unwrap()
+            .is_empty());
+
+        evts[2].write(1).unwrap();
+        evts[0].write(1).unwrap();
+        assert_eq!(wait_any(&refs, None).unwrap(), [0, 2]);
+        // The counters are left for the caller to read.
+        assert_eq!(wait_any(&refs, None).unwrap(), [0, 2]);
+        assert_eq!(evts[0].read().unwrap(), 1);
+        assert_eq!(wait_any(&refs, Some(Duration::from_secs(1))).unwrap(), [2]);
+
+        // A write from another thread ends the wait.
+        let evt = Arc::new(EventFd::new(0).unwrap());
+        let writer_evt = evt.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            writer_evt.write(1).unwrap();
+        });
+        assert_eq!(wait_any(&[&evts[1], &evt], None).unwrap(), [1]);
+        writer.join().unwrap();
+
+        // A closed eventfd fails the wait, even though the others are readable. It is
+        // duplicated to a high number that won't be reused by the other tests.
+        let fd = unsafe { libc::fcntl(evts[0].as_raw_fd(), libc::F_DUPFD_CLOEXEC, 520) };
+        assert!(fd >= 520);
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+        let closed = mem::ManuallyDrop::new(unsafe { EventFd::from_raw_fd(fd) });
+        let err = wait_any(&[&evts[2], &closed], None).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(EBADF));
+    }
+
+    #[test]
+    fn test_wait_any_interrupted() {
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+
+        let signal = SIGRTMIN() + 18;
+        register_signal_handler(signal, handle_signal).unwrap();
+
+        // Signals don't end a wait without timeout.
+        let evt = Arc::new(EventFd::new(0).unwrap());
+        let waiter_evt = evt.clone();
+        let waiter = thread::spawn(move || wait_any(&[&waiter_evt], None).unwrap());
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(20));
+            waiter.kill(signal).unwrap();
+        }
+        evt.write(1).unwrap();
+        assert_eq!(waiter.join().unwrap(), [0]);
+    }
+
+    #[test]
+    fn test_notify_before_wait() {
+        let notify = Notify::new().unwrap();
+        notify.notify().unwrap();
+        notify.wait().unwrap();
+        assert!(!notify.wait_timeout(Duration::from_millis(1)).unwrap());
+    }
+
+    #[test]
+    fn test_wait_then_notify() {
+        let notify = Arc::new(Notify::new().unwrap());
+        let notify_clone = notify.clone();
+        let waiter = thread::spawn(move || notify_clone.wait().unwrap());
+
+        thread::sleep(Duration::from_millis(10));
+        notify.notify().unwrap();
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn test_double_notify() {
+        let notify = Notify::new().unwrap();
+        notify.notify().unwrap();
+        notify.notify().unwrap();
+        assert!(notify.wait_timeout(Duration::from_millis(1)).unwrap());
+        // Both notifications were consumed by a single wait.
+        assert!(!notify.wait_timeout(Duration::from_millis(1)).unwrap());
+    }
+
+    #[test]
+    fn test_notify_wait_timeout_interrupted() {
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+
+        let signal = SIGRTMIN() + 17;
+        register_signal_handler(signal, handle_signal).unwrap();
+
+        // Interrupting the wait several times neither ends it early nor pushes back its end.
+        let timeout = Duration::from_millis(200);
+        let waiter = thread::spawn(move || {
+            let notify = Notify::new().unwrap();
+            let start = Instant::now();
+            let notified = notify.wait_timeout(timeout).unwrap();
+            (notified, start.elapsed())
+        });
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(40));
+            waiter.kill(signal).unwrap();
+        }
+        let (notified, elapsed) = waiter.join().unwrap();
+        assert!(!notified);
+        assert!(elapsed >= timeout, "elapsed = {:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(80),
+            "elapsed = {:?}",
+            elapsed
+        );
+    }
 }
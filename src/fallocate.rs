@@ -7,14 +7,20 @@
 //! Enum and function for dealing with an allocated disk space
 //! by [`fallocate`](http://man7.org/linux/man-pages/man2/fallocate.2.html).
 
+use std::cmp::max;
+use std::mem::size_of;
+use std::os::raw::c_ulong;
 use std::os::unix::io::AsRawFd;
 
 use crate::errno::{errno_result, Error, Result};
+use crate::ioctl::{ioctl_with_mut_ref, _IOC_READ, _IOC_WRITE};
 
 /// Operation to be performed on a given range when calling [`fallocate`]
 ///
 /// [`fallocate`]: fn.fallocate.html
 pub enum FallocateMode {
+    /// Allocating file space.
+    Allocate,
     /// Deallocating file space.
     PunchHole,
     /// Zeroing file space.
@@ -50,6 +56,7 @@ pub enum FallocateMode {
 ///     .read(true)
 ///     .write(true)
 ///     .create(true)
+///     .truncate(true)
 ///     .open(&path)
 ///     .unwrap();
 /// fallocate(&f, FallocateMode::PunchHole, true, 0, 1).unwrap();
@@ -74,6 +81,7 @@ pub fn fallocate(
     };
 
     let mut mode = match mode {
+        FallocateMode::Allocate => 0,
         FallocateMode::PunchHole => libc::FALLOC_FL_PUNCH_HOLE,
         FallocateMode::ZeroRange => libc::FALLOC_FL_ZERO_RANGE,
     };
@@ -91,3 +99,213 @@ pub fn fallocate(
         Ok(())
     }
 }
+
+/// Preallocate disk space for a range of a file.
+///
+/// Reserves the blocks backing the range starting at `offset` of `len` bytes, growing the file
+/// if `offset` + `len` is greater than the file size. Reading from the preallocated range
+/// returns zeroes.
+///
+/// # Arguments
+///
+/// * `file`: the file for which to preallocate space.
+/// * `offset`: the position where the range to preallocate starts.
+/// * `len`: the bytes of the range to preallocate.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::fs::OpenOptions;
+/// # use std::path::PathBuf;
+/// use vmm_sys_util::fallocate::preallocate;
+/// use vmm_sys_util::tempdir::TempDir;
+///
+/// let tempdir = TempDir::new_with_prefix("/tmp/preallocate_test").unwrap();
+/// let mut path = PathBuf::from(tempdir.as_path());
+/// path.push("file");
+/// let f = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .truncate(true)
+///     .open(&path)
+///     .unwrap();
+/// preallocate(&f, 0, 4096).unwrap();
+/// assert_eq!(f.metadata().unwrap().len(), 4096);
+/// ```
+pub fn preallocate(file: &dyn AsRawFd, offset: u64, len: u64) -> Result<()> {
+    fallocate(file, FallocateMode::Allocate, false, offset, len)
+}
+
+// Definitions from linux/fiemap.h.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+}
+
+const FIEMAP_FLAG_SYNC: u32 = 0x0000_0001;
+const FIEMAP_EXTENT_LAST: u32 = 0x0000_0001;
+const FIEMAP_MAX_EXTENTS: usize = 32;
+
+// Definition from linux/fs.h.
+const FS_IOC_FIEMAP: c_ulong = ioctl_expr!(
+    _IOC_READ | _IOC_WRITE,
+    'f' as u32,
+    11,
+    size_of::<Fiemap>() as u32
+);
+
+// A `struct fiemap` followed by the extents filled in by the kernel.
+#[repr(C)]
+#[derive(Default)]
+struct FiemapBuffer {
+    header: Fiemap,
+    extents: [FiemapExtent; FIEMAP_MAX_EXTENTS],
+}
+
+/// Check whether a range of a file is entirely backed by allocated blocks.
+///
+/// The check is done with the
+/// [`FS_IOC_FIEMAP`](https://www.kernel.org/doc/Documentation/filesystems/fiemap.txt) ioctl
+/// rather than with `lseek(SEEK_DATA)`, because common filesystems (e.g. ext4, xfs) report
+/// preallocated but not yet written extents as holes to `SEEK_DATA`. Such extents count as
+/// allocated here.
+///
+/// Returns `Ok(true)` if every byte of the range starting at `offset` of `len` bytes is
+/// allocated, `Ok(false)` if the range contains a hole or extends past the end of the file.
+/// Filesystems that don't support `FS_IOC_FIEMAP` make this return an error.
+///
+/// # Arguments
+///
+/// * `file`: the file to be checked.
+/// * `offset`: the position where the range to check starts.
+/// * `len`: the bytes of the range to check.
+pub fn is_range_allocated(file: &dyn AsRawFd, offset: u64, len: u64) -> Result<bool> {
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| Error::new(libc::EINVAL))?;
+
+    let mut pos = offset;
+    while pos < end {
+        let mut fiemap = FiemapBuffer::default();
+        fiemap.header.fm_start = pos;
+        fiemap.header.fm_length = end - pos;
+        fiemap.header.fm_flags = FIEMAP_FLAG_SYNC;
+        fiemap.header.fm_extent_count = FIEMAP_MAX_EXTENTS as u32;
+
+        // Safe because the buffer has room for `fm_extent_count` extents, which is all the
+        // kernel writes, and we check the return value.
+        let ret = unsafe { ioctl_with_mut_ref(&file.as_raw_fd(), FS_IOC_FIEMAP, &mut fiemap) };
+        if ret < 0 {
+            return errno_result();
+        }
+
+        let mapped_extents = fiemap.header.fm_mapped_extents as usize;
+        if mapped_extents == 0 {
+            return Ok(false);
+        }
+
+        for extent in &fiemap.extents[..mapped_extents] {
+            if extent.fe_logical > pos {
+                // There's a hole between the bytes checked so far and this extent.
+                return Ok(false);
+            }
+            pos = max(pos, extent.fe_logical + extent.fe_length);
+            if pos >= end {
+                return Ok(true);
+            }
+            if extent.fe_flags & FIEMAP_EXTENT_LAST != 0 {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{File, OpenOptions};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::tempdir::TempDir;
+
+    fn create_file(tempdir: &TempDir) -> File {
+        let mut path = PathBuf::from(tempdir.as_path());
+        path.push("file");
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap()
+    }
+
+    // Filesystems such as tmpfs don't support FS_IOC_FIEMAP, so the checks relying on it are
+    // skipped when /tmp is on one of them.
+    fn fiemap_supported(f: &File) -> bool {
+        match is_range_allocated(f, 0, 1) {
+            Err(e) => e.errno() != libc::EOPNOTSUPP,
+            Ok(_) => true,
+        }
+    }
+
+    #[test]
+    fn test_preallocate() {
+        let tempdir = TempDir::new_with_prefix("/tmp/preallocate_test").unwrap();
+        let f = create_file(&tempdir);
+
+        preallocate(&f, 0, 0x10_0000).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 0x10_0000);
+        if !fiemap_supported(&f) {
+            return;
+        }
+        assert!(is_range_allocated(&f, 0, 0x10_0000).unwrap());
+        assert!(is_range_allocated(&f, 0x1000, 0x1000).unwrap());
+        // The range extends past the end of the file.
+        assert!(!is_range_allocated(&f, 0, 0x20_0000).unwrap());
+
+        assert!(fallocate(&f, FallocateMode::PunchHole, true, 0x8_0000, 0x1000).is_ok());
+        assert!(!is_range_allocated(&f, 0, 0x10_0000).unwrap());
+        assert!(is_range_allocated(&f, 0, 0x8_0000).unwrap());
+    }
+
+    #[test]
+    fn test_is_range_allocated_sparse() {
+        let tempdir = TempDir::new_with_prefix("/tmp/preallocate_test").unwrap();
+        let mut f = create_file(&tempdir);
+        if !fiemap_supported(&f) {
+            return;
+        }
+
+        f.set_len(0x10_0000).unwrap();
+        assert!(!is_range_allocated(&f, 0, 0x10_0000).unwrap());
+
+        f.write_all(&[0x55u8; 0x1000]).unwrap();
+        assert!(is_range_allocated(&f, 0, 0x1000).unwrap());
+        assert!(!is_range_allocated(&f, 0, 0x2000).unwrap());
+
+        assert!(is_range_allocated(&f, u64::MAX, 1).is_err());
+    }
+}
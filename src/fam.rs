@@ -23,6 +23,8 @@ use std::mem::{self, size_of};
 pub enum Error {
     /// The max size has been exceeded
     SizeLimitExceeded,
+    /// The number of bytes doesn't match the FAM length stored in them
+    InvalidByteSize,
 }
 
 /// Trait for accessing properties of C defined FAM structures.
@@ -32,7 +34,11 @@ pub enum Error {
 /// * the implementor should contain a flexible array member of elements of type `Entry`
 /// * `Entry` should be a POD
 ///
-/// Violating these may cause problems.
+/// Violating these may cause problems. The lack of drop glue, which is part of being a POD,
+/// is checked though: `Entry` must be `Copy`, and the constructors of
+/// [`FamStructWrapper`](struct.FamStructWrapper.html) panic if the implementer needs to be
+/// dropped, because the wrapper stores the entries in a buffer of zero-initialized or copied
+/// implementers.
 ///
 /// # Example
 ///
@@ -169,6 +175,18 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
         array_size_in_bytes / size_of::<T::Entry>()
     }
 
+    /// Check that `T` can be dropped without running any code.
+    ///
+    /// The `mem_allocator` holds the entries as zero-initialized `T` elements, which are
+    /// dropped along with it, so a `Drop` implementation of `T` would run on memory that
+    /// doesn't hold a `T`.
+    fn assert_no_drop_glue() {
+        assert!(
+            !mem::needs_drop::<T>(),
+            "FamStruct implementers must not need to be dropped"
+        );
+    }
+
     /// Create a new FamStructWrapper with `num_elements` elements.
     ///
     /// The elements will be zero-initialized. The type of the elements will be
@@ -177,7 +195,12 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
     /// # Arguments
     ///
     /// * `num_elements` - The number of elements in the FamStructWrapper.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` needs to be dropped, i.e. if it isn't a POD.
     pub fn new(num_elements: usize) -> FamStructWrapper<T> {
+        Self::assert_no_drop_glue();
         let required_mem_allocator_capacity =
             FamStructWrapper::<T>::mem_allocator_len(num_elements);
 
@@ -219,7 +242,12 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
     /// # Arguments
     ///
     /// * `content` - The raw content represented as `Vec[T]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` needs to be dropped, i.e. if it isn't a POD.
     pub unsafe fn from_raw(content: Vec<T>) -> Self {
+        Self::assert_no_drop_glue();
         FamStructWrapper {
             mem_allocator: content,
         }
@@ -230,6 +258,66 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
         self.mem_allocator
     }
 
+    /// Create a new FamStructWrapper from the bytes of a FAM struct.
+    ///
+    /// The bytes are the header (`T`) followed by the entries, as returned by
+    /// [`into_bytes`](struct.FamStructWrapper.html#method.into_bytes), e.g. on
+    /// the other end of a socket. They don't need to be aligned.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes of the header and of the entries.
+    ///
+    /// # Errors
+    ///
+    /// When `bytes` is shorter than the header, or when its length doesn't match the FAM
+    /// length stored in the header, it returns Error::InvalidByteSize. When the FAM length
+    /// is greater than the max possible len it returns Error::SizeLimitExceeded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` needs to be dropped, i.e. if it isn't a POD.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::assert_no_drop_glue();
+        if bytes.len() < size_of::<T>() {
+            return Err(Error::InvalidByteSize);
+        }
+        // This is safe since `bytes` holds at least a `T`, which is a POD.
+        let header: T = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) };
+        let len = header.len();
+        if len > T::max_len() {
+            return Err(Error::SizeLimitExceeded);
+        }
+        if bytes.len() != size_of::<T>() + len * size_of::<T::Entry>() {
+            return Err(Error::InvalidByteSize);
+        }
+
+        let mut adapter = FamStructWrapper::<T>::new(len);
+        // This is safe since `mem_allocator` holds at least `byte_size()` bytes, which is the
+        // length of `bytes`.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                adapter.mem_allocator.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+        }
+        Ok(adapter)
+    }
+
+    /// Consume the FamStructWrapper and return the bytes of the FAM struct.
+    ///
+    /// These are the [`byte_size`](struct.FamStructWrapper.html#method.byte_size) bytes of
+    /// the header followed by the entries, which can be turned back into a FamStructWrapper
+    /// with [`from_bytes`](struct.FamStructWrapper.html#method.from_bytes).
+    pub fn into_bytes(self) -> Vec<u8> {
+        // This is safe since `mem_allocator` holds at least `byte_size()` initialized bytes.
+        unsafe {
+            std::slice::from_raw_parts(self.mem_allocator.as_ptr() as *const u8, self.byte_size())
+        }
+        .to_vec()
+    }
+
     /// Get a reference to the actual [`FamStruct`](trait.FamStruct.html) instance.
     pub fn as_fam_struct_ref(&self) -> &T {
         &self.mem_allocator[0]
@@ -242,24 +330,33 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
 
     /// Get a pointer to the [`FamStruct`](trait.FamStruct.html) instance.
     ///
+    /// The entries follow the header contiguously in memory, so the pointer
+    /// can be passed to a C API expecting the whole structure. It must not be
+    /// used to access more than `len` entries though.
+    ///
     /// The caller must ensure that the fam_struct outlives the pointer this
     /// function returns, or else it will end up pointing to garbage.
     ///
     /// Modifying the container referenced by this pointer may cause its buffer
     /// to be reallocated, which would also make any pointers to it invalid.
     pub fn as_fam_struct_ptr(&self) -> *const T {
-        self.as_fam_struct_ref()
+        // Derive the pointer from the whole buffer rather than from a reference
+        // to the header, so that it is also valid for accessing the entries.
+        self.mem_allocator.as_ptr()
     }
 
     /// Get a mutable pointer to the [`FamStruct`](trait.FamStruct.html) instance.
     ///
+    /// As for [`as_fam_struct_ptr`](#method.as_fam_struct_ptr), the pointer is
+    /// valid for accessing the header and up to `len` entries.
+    ///
     /// The caller must ensure that the fam_struct outlives the pointer this
     /// function returns, or else it will end up pointing to garbage.
     ///
     /// Modifying the container referenced by this pointer may cause its buffer
     /// to be reallocated, which would also make any pointers to it invalid.
     pub fn as_mut_fam_struct_ptr(&mut self) -> *mut T {
-        self.as_mut_fam_struct()
+        self.mem_allocator.as_mut_ptr()
     }
 
     /// Get the elements slice.
@@ -272,6 +369,15 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
         self.as_mut_fam_struct().as_mut_slice()
     }
 
+    /// Get the size in bytes of the [`FamStruct`](trait.FamStruct.html) instance.
+    ///
+    /// This is the size of the header (`T`) plus the size of the entries currently in
+    /// the FAM, which is what e.g. a kernel expects as the size of such a structure.
+    /// An empty `FamStructWrapper` still holds a header, so its size is `size_of::<T>()`.
+    pub fn byte_size(&self) -> usize {
+        size_of::<T>() + self.len() * size_of::<T::Entry>()
+    }
+
     /// Get the number of elements of type `FamStruct::Entry` currently in the vec.
     fn len(&self) -> usize {
         self.as_fam_struct_ref().len()
@@ -287,14 +393,28 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
     /// Reserve additional capacity.
     ///
     /// Reserve capacity for at least `additional` more
-    /// [`FamStruct::Entry`](trait.FamStruct.html#associatedtype.Entry) elements.
+    /// [`FamStruct::Entry`](trait.FamStruct.html#associatedtype.Entry) elements,
+    /// so that pushing them doesn't reallocate the underlying buffer. The length
+    /// is not changed.
     ///
     /// If the capacity is already reserved, this method doesn't do anything.
     /// If not this will trigger a reallocation of the underlying buffer.
-    fn reserve(&mut self, additional: usize) {
-        let desired_capacity = self.len() + additional;
+    ///
+    /// # Arguments
+    ///
+    /// * `additional` - The number of elements to reserve capacity for.
+    ///
+    /// # Errors
+    ///
+    /// When the len plus `additional` is greater than the max possible len it returns
+    /// Error::SizeLimitExceeded.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), Error> {
+        let desired_capacity = match self.len().checked_add(additional) {
+            Some(capacity) if capacity <= T::max_len() => capacity,
+            _ => return Err(Error::SizeLimitExceeded),
+        };
         if desired_capacity <= self.capacity() {
-            return;
+            return Ok(());
         }
 
         let current_mem_allocator_len = self.mem_allocator.len();
@@ -302,6 +422,7 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
         let additional_mem_allocator_len = required_mem_allocator_len - current_mem_allocator_len;
 
         self.mem_allocator.reserve(additional_mem_allocator_len);
+        Ok(())
     }
 
     /// Update the length of the FamStructWrapper.
@@ -330,7 +451,7 @@ impl<T: Default + FamStruct> FamStructWrapper<T> {
                 return Err(Error::SizeLimitExceeded);
             }
             // Reserve additional capacity.
-            self.reserve(additional_elements as usize);
+            self.reserve(additional_elements as usize)?;
         }
 
         let current_mem_allocator_len = self.mem_allocator.len();
@@ -413,6 +534,7 @@ impl<T: Default + FamStruct> Clone for FamStructWrapper<T> {
 
 impl<T: Default + FamStruct> From<Vec<T>> for FamStructWrapper<T> {
     fn from(vec: Vec<T>) -> Self {
+        Self::assert_no_drop_glue();
         FamStructWrapper { mem_allocator: vec }
     }
 }
@@ -582,6 +704,64 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_empty() {
+        let adapter = MockFamStructWrapper::from_entries(&[]);
+        assert_eq!(adapter.len(), 0);
+        assert!(adapter.as_slice().is_empty());
+        assert_eq!(adapter.as_slice().iter().count(), 0);
+        // The header is still allocated.
+        assert_eq!(adapter.mem_allocator.len(), 1);
+        assert_eq!(adapter.as_fam_struct_ref().len, 0);
+        assert_eq!(adapter.byte_size(), size_of::<MockFamStruct>());
+
+        let mut adapter = MockFamStructWrapper::new(0);
+        assert!(adapter.as_mut_slice().is_empty());
+        assert!(adapter == MockFamStructWrapper::from_entries(&[]));
+        assert!(adapter.clone().as_slice().is_empty());
+        adapter.retain(|_| true);
+        assert_eq!(adapter.byte_size(), size_of::<MockFamStruct>());
+    }
+
+    #[test]
+    fn test_byte_size() {
+        let adapter = MockFamStructWrapper::from_entries(&[1, 2, 3]);
+        assert_eq!(
+            adapter.byte_size(),
+            size_of::<MockFamStruct>() + 3 * size_of::<u32>()
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let adapter = MockFamStructWrapper::from_entries(&[1, 2, 3]);
+        let bytes = adapter.clone().into_bytes();
+        assert_eq!(bytes.len(), adapter.byte_size());
+        assert_eq!(bytes[0..4], 3u32.to_ne_bytes());
+        assert_eq!(bytes[16..20], 3u32.to_ne_bytes());
+
+        // The bytes don't need to be aligned.
+        let mut unaligned = vec![0u8];
+        unaligned.extend_from_slice(&bytes);
+        let round_trip = MockFamStructWrapper::from_bytes(&unaligned[1..]).unwrap();
+        assert!(round_trip == adapter);
+        assert_eq!(round_trip.as_slice(), &[1, 2, 3]);
+
+        for truncated in &[&bytes[..bytes.len() - 1], &bytes[..4]] {
+            assert!(matches!(
+                MockFamStructWrapper::from_bytes(truncated),
+                Err(Error::InvalidByteSize)
+            ));
+        }
+
+        let mut too_long = MockFamStructWrapper::new(0).into_bytes();
+        too_long[0..4].copy_from_slice(&(MAX_LEN as u32 + 1).to_ne_bytes());
+        assert!(matches!(
+            MockFamStructWrapper::from_bytes(&too_long),
+            Err(Error::SizeLimitExceeded)
+        ));
+    }
+
     #[test]
     fn test_entries_slice() {
         let num_entries = 10;
@@ -613,7 +793,7 @@ mod tests {
             let num_elements = pair.0;
             let required_mem_allocator_len = pair.1;
 
-            adapter.reserve(num_elements);
+            adapter.reserve(num_elements).unwrap();
 
             assert!(adapter.mem_allocator.capacity() >= required_mem_allocator_len);
             assert_eq!(0, adapter.len());
@@ -622,8 +802,61 @@ mod tests {
 
         // test that when the capacity is already reserved, the method doesn't do anything
         let current_capacity = adapter.capacity();
-        adapter.reserve(current_capacity - 1);
+        adapter.reserve(MAX_LEN - 1).unwrap();
         assert_eq!(current_capacity, adapter.capacity());
+
+        // test that the max len can't be exceeded
+        assert!(adapter.reserve(MAX_LEN + 1).is_err());
+        adapter.push(1).unwrap();
+        assert!(adapter.reserve(MAX_LEN).is_err());
+        assert!(adapter.reserve(usize::MAX).is_err());
+        assert_eq!(1, adapter.len());
+    }
+
+    // Stand-ins for C functions taking a pointer to the whole FAM struct.
+    unsafe fn sum_entries(fam: *const MockFamStruct) -> u32 {
+        let entries = (fam as *const u32).add(ENTRIES_OFFSET);
+        (0..(*fam).len as usize).map(|i| *entries.add(i)).sum()
+    }
+
+    unsafe fn double_entries(fam: *mut MockFamStruct) {
+        let entries = (fam as *mut u32).add(ENTRIES_OFFSET);
+        for i in 0..(*fam).len as usize {
+            *entries.add(i) *= 2;
+        }
+    }
+
+    #[test]
+    fn test_fam_struct_ptr() {
+        let mut adapter = MockFamStructWrapper::from_entries(&[1, 2, 3, 4]);
+        assert_eq!(
+            adapter.as_fam_struct_ptr(),
+            adapter.as_fam_struct_ref() as *const MockFamStruct
+        );
+        assert_eq!(unsafe { sum_entries(adapter.as_fam_struct_ptr()) }, 10);
+
+        unsafe { double_entries(adapter.as_mut_fam_struct_ptr()) };
+        assert_eq!(adapter.as_slice(), &[2, 4, 6, 8]);
+        assert_eq!(adapter.as_fam_struct_ref().len, 4);
+    }
+
+    #[test]
+    fn test_reserve_then_push() {
+        let mut adapter = MockFamStructWrapper::new(0);
+        adapter.reserve(50).unwrap();
+        let capacity = adapter.capacity();
+        let ptr = adapter.as_fam_struct_ptr();
+
+        for i in 0..50 {
+            adapter.push(i as u32).unwrap();
+        }
+        // The entries were pushed without reallocating.
+        assert_eq!(ptr, adapter.as_fam_struct_ptr());
+        assert_eq!(capacity, adapter.capacity());
+        assert_eq!(50, adapter.len());
+        for (i, entry) in adapter.as_slice().iter().enumerate() {
+            assert_eq!(i as u32, *entry);
+        }
     }
 
     #[test]
@@ -717,6 +950,35 @@ mod tests {
         assert!(adapter == adapter.clone());
     }
 
+    #[repr(C)]
+    #[derive(Default)]
+    struct DropFamStruct {
+        pub len: u32,
+        pub padding: u32,
+        pub entries: __IncompleteArrayField<u32>,
+    }
+
+    impl Drop for DropFamStruct {
+        fn drop(&mut self) {}
+    }
+
+    generate_fam_struct_impl!(DropFamStruct, u32, entries, u32, len, 100);
+
+    #[test]
+    #[should_panic(expected = "must not need to be dropped")]
+    fn test_new_drop_glue() {
+        FamStructWrapper::<DropFamStruct>::new(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not need to be dropped")]
+    fn test_from_vec_drop_glue() {
+        let content = vec![DropFamStruct::default()];
+        // The panic happens before the wrapper exists, so `content` is dropped as a
+        // regular `Vec` of valid structs.
+        let _ = FamStructWrapper::<DropFamStruct>::from(content);
+    }
+
     #[test]
     fn test_raw_content() {
         let data = vec![
@@ -0,0 +1,114 @@
+// Copyright 2026 rust-vmm Authors or its affiliates. All Rights Reserved.
+//
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Helpers for changing file descriptor flags with
+//! [`fcntl`](http://man7.org/linux/man-pages/man2/fcntl.2.html).
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+use libc::{c_int, fcntl, FD_CLOEXEC, F_GETFD, F_GETFL, F_SETFD, F_SETFL, O_NONBLOCK};
+
+// Set or clear `flag` in the flags read by the `get` command, writing them back with the `set`
+// command only if they changed, so that the other flags are preserved.
+fn update_flag(fd: RawFd, get: c_int, set: c_int, flag: c_int, on: bool) -> io::Result<()> {
+    // Safe because no third parameter is expected and we check the return result.
+    let old_flags = unsafe { fcntl(fd, get) };
+    if old_flags < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let new_flags = if on {
+        old_flags | flag
+    } else {
+        old_flags & !flag
+    };
+    if new_flags != old_flags {
+        // Safe because we supply the third parameter and we check the return result.
+        let ret = unsafe { fcntl(fd, set, new_flags) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Set or clear the `O_NONBLOCK` file status flag of a file descriptor.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to be updated.
+/// * `on`: whether `O_NONBLOCK` should be set or cleared.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::eventfd::EventFd;
+/// use vmm_sys_util::fcntl::set_nonblocking;
+/// # use std::os::unix::io::AsRawFd;
+///
+/// let evt = EventFd::new(0).unwrap();
+/// set_nonblocking(evt.as_raw_fd(), true).unwrap();
+/// ```
+pub fn set_nonblocking(fd: RawFd, on: bool) -> io::Result<()> {
+    update_flag(fd, F_GETFL, F_SETFL, O_NONBLOCK, on)
+}
+
+/// Set or clear the `FD_CLOEXEC` file descriptor flag of a file descriptor.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to be updated.
+/// * `on`: whether `FD_CLOEXEC` should be set or cleared.
+pub fn set_cloexec(fd: RawFd, on: bool) -> io::Result<()> {
+    update_flag(fd, F_GETFD, F_SETFD, FD_CLOEXEC, on)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::AsRawFd;
+
+    use libc::O_APPEND;
+
+    use crate::tempfile::TempFile;
+
+    #[test]
+    fn test_set_nonblocking() {
+        let tempfile = TempFile::new_with_prefix("/tmp/fcntl_test").unwrap();
+        let fd = tempfile.as_file().as_raw_fd();
+
+        // Set another status flag to check it's preserved.
+        assert_eq!(unsafe { fcntl(fd, F_SETFL, O_APPEND) }, 0);
+
+        set_nonblocking(fd, true).unwrap();
+        let flags = unsafe { fcntl(fd, F_GETFL) };
+        assert_eq!(flags & O_NONBLOCK, O_NONBLOCK);
+        assert_eq!(flags & O_APPEND, O_APPEND);
+
+        set_nonblocking(fd, false).unwrap();
+        let flags = unsafe { fcntl(fd, F_GETFL) };
+        assert_eq!(flags & O_NONBLOCK, 0);
+        assert_eq!(flags & O_APPEND, O_APPEND);
+    }
+
+    #[test]
+    fn test_set_cloexec() {
+        let tempfile = TempFile::new_with_prefix("/tmp/fcntl_test").unwrap();
+        let fd = tempfile.as_file().as_raw_fd();
+
+        set_cloexec(fd, false).unwrap();
+        assert_eq!(unsafe { fcntl(fd, F_GETFD) } & FD_CLOEXEC, 0);
+
+        set_cloexec(fd, true).unwrap();
+        assert_eq!(unsafe { fcntl(fd, F_GETFD) } & FD_CLOEXEC, FD_CLOEXEC);
+    }
+
+    #[test]
+    fn test_invalid_fd() {
+        assert!(set_nonblocking(-1, true).is_err());
+        assert!(set_cloexec(-1, true).is_err());
+    }
+}
@@ -7,7 +7,18 @@
 //! Traits for handling file synchronization and length.
 
 use std::fs::File;
-use std::io::Result;
+use std::io::{Error, ErrorKind, Read, Result};
+use std::mem;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::Path;
+
+use libc::{
+    fcntl, flock, fstat, stat, EWOULDBLOCK, F_DUPFD_CLOEXEC, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+};
+
+use crate::fallocate::{fallocate, FallocateMode};
+use crate::write_zeroes::{punch_hole_chunked, PUNCH_HOLE_MAX_CHUNK_LEN};
 
 /// A trait for flushing the contents of a file to disk.
 ///
@@ -25,6 +36,12 @@ impl FileSync for File {
     }
 }
 
+impl<T: FileSync + ?Sized> FileSync for &mut T {
+    fn fsync(&mut self) -> Result<()> {
+        (**self).fsync()
+    }
+}
+
 /// A trait for setting the size of a file.
 ///
 /// This is equivalent to
@@ -48,6 +65,219 @@ impl FileSetLen for File {
     }
 }
 
+impl<T: FileSetLen + ?Sized> FileSetLen for &mut T {
+    fn set_len(&self, len: u64) -> Result<()> {
+        (**self).set_len(len)
+    }
+}
+
+/// A trait for managing the space allocated to a file.
+///
+/// This combines reserving space with
+/// [`fallocate`](http://man7.org/linux/man-pages/man2/fallocate.2.html) and releasing it
+/// by punching holes, so that generic disk backends only need a single bound.
+pub trait FileAllocate {
+    /// Allocate the space for the range, growing the file if the range extends past its end.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: offset of the file where the range starts.
+    /// * `len`: the length of the range.
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()>;
+
+    /// Deallocate the space of the range, replacing it with a hole.
+    ///
+    /// The size of the file is not changed and the range reads back as zeroes.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: offset of the file where the range starts.
+    /// * `len`: the length of the range.
+    fn deallocate(&mut self, offset: u64, len: u64) -> Result<()>;
+}
+
+impl FileAllocate for File {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        fallocate(self, FallocateMode::Allocate, false, offset, len)
+            .map_err(|e| Error::from_raw_os_error(e.errno()))
+    }
+
+    fn deallocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        punch_hole_chunked(self, offset, len, PUNCH_HOLE_MAX_CHUNK_LEN)
+    }
+}
+
+impl<T: FileAllocate + ?Sized> FileAllocate for &mut T {
+    fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        (**self).allocate(offset, len)
+    }
+
+    fn deallocate(&mut self, offset: u64, len: u64) -> Result<()> {
+        (**self).deallocate(offset, len)
+    }
+}
+
+/// A trait for advisory locking of whole files, e.g. for keeping two VMMs from using the
+/// same disk image.
+///
+/// The locks are taken with [`flock`](http://man7.org/linux/man-pages/man2/flock.2.html),
+/// so they are advisory: they only exclude other processes also taking them, and don't
+/// prevent anyone from reading or writing the file. A lock belongs to the open file
+/// description rather than to the process, so it is shared with the duplicates of the file
+/// descriptor (including the ones inherited by a child process across `fork`) and is only
+/// released when all of them are closed, or on [`funlock`](#tymethod.funlock). Opening the
+/// same file again creates a new open file description, with its own lock.
+///
+/// The methods are prefixed with `f`, so that they don't clash with the inherent locking
+/// methods of `File` in recent versions of the standard library.
+pub trait FileLock {
+    /// Take an exclusive lock, waiting until the file is unlocked by the other holders.
+    ///
+    /// A shared lock already held through this file is converted to an exclusive one.
+    fn flock_exclusive(&self) -> Result<()>;
+
+    /// Take a shared lock, waiting until an exclusive lock held by someone else is released.
+    ///
+    /// An exclusive lock already held through this file is converted to a shared one.
+    fn flock_shared(&self) -> Result<()>;
+
+    /// Try to take an exclusive lock without waiting.
+    ///
+    /// Returns `false` if the file is already locked by someone else.
+    fn try_flock_exclusive(&self) -> Result<bool>;
+
+    /// Release the lock held through this file, if any.
+    fn funlock(&self) -> Result<()>;
+}
+
+fn flock_file(file: &File, operation: i32) -> Result<()> {
+    // Safe because this doesn't modify any memory and we check the return value.
+    let ret = unsafe { flock(file.as_raw_fd(), operation) };
+    if ret < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl FileLock for File {
+    fn flock_exclusive(&self) -> Result<()> {
+        flock_file(self, LOCK_EX)
+    }
+
+    fn flock_shared(&self) -> Result<()> {
+        flock_file(self, LOCK_SH)
+    }
+
+    fn try_flock_exclusive(&self) -> Result<bool> {
+        match flock_file(self, LOCK_EX | LOCK_NB) {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.raw_os_error() == Some(EWOULDBLOCK) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn funlock(&self) -> Result<()> {
+        flock_file(self, LOCK_UN)
+    }
+}
+
+/// Read the whole content of the file at `path`, if its size is at most `max` bytes.
+///
+/// The size is checked before reading anything, so that a huge file is rejected
+/// without being loaded in memory. Reading also stops after `max` bytes, in case
+/// the file grows in the meantime.
+///
+/// # Arguments
+///
+/// * `path`: the path of the file to read.
+/// * `max`: the maximum size of the file, in bytes.
+///
+/// # Errors
+///
+/// Returns an error of kind `InvalidData` if the file is larger than `max` bytes.
+pub fn read_file_capped(path: &Path, max: u64) -> Result<Vec<u8>> {
+    let too_large = |size: u64| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("file size {} exceeds the maximum of {} bytes", size, max),
+        )
+    };
+
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    if size > max {
+        return Err(too_large(size));
+    }
+
+    let mut content = Vec::with_capacity(size as usize);
+    let read = file.take(max.saturating_add(1)).read_to_end(&mut content)? as u64;
+    if read > max {
+        return Err(too_large(read));
+    }
+    Ok(content)
+}
+
+/// Duplicate the file descriptor of `file`, with the `FD_CLOEXEC` flag set on the duplicate.
+///
+/// A plain [`dup`](http://man7.org/linux/man-pages/man2/dup.2.html) clears `FD_CLOEXEC`,
+/// which leaks the duplicate to the programs started by `exec`. This uses
+/// `fcntl(F_DUPFD_CLOEXEC)` instead, which sets the flag atomically, so no other thread can
+/// `exec` before it is set. The duplicate shares the file offset and status flags of `file`.
+///
+/// # Arguments
+///
+/// * `file`: the file to be duplicated.
+pub fn clone_file_cloexec(file: &File) -> Result<File> {
+    // Safe because this doesn't modify any memory and we check the return value.
+    let fd = unsafe { fcntl(file.as_raw_fd(), F_DUPFD_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    // Safe because we checked fd for success and we own the new fd.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Get the number of bytes allocated on disk for `file`.
+///
+/// This is `st_blocks * 512` as reported by
+/// [`fstat`](http://man7.org/linux/man-pages/man2/fstat.2.html), which is smaller than
+/// the logical length of a sparse file, or larger than it when space was preallocated past
+/// the end of the file.
+///
+/// # Arguments
+///
+/// * `file`: the file to be queried.
+pub fn allocated_size(file: &File) -> Result<u64> {
+    // `st_blocks` is always counted in 512 byte units, regardless of the block size of the
+    // filesystem.
+    Ok(file.metadata()?.blocks() * 512)
+}
+
+/// Check whether two file descriptors refer to the same file.
+///
+/// The files are compared by the device and inode numbers reported by
+/// [`fstat`](http://man7.org/linux/man-pages/man2/fstat.2.html), so this detects the
+/// duplicates of a file descriptor as well as separate opens of the same file (e.g. to
+/// deduplicate the file descriptors received over a socket).
+///
+/// # Arguments
+///
+/// * `a`: the first file descriptor to be compared.
+/// * `b`: the second file descriptor to be compared.
+pub fn same_file(a: &impl AsRawFd, b: &impl AsRawFd) -> Result<bool> {
+    let file_id = |fd: &dyn AsRawFd| -> Result<(u64, u64)> {
+        // Safe because `stat` only contains primitive fields.
+        let mut st: stat = unsafe { mem::zeroed() };
+        // Safe because the kernel only writes to `st`, which is large enough, and we check
+        // the return value.
+        if unsafe { fstat(fd.as_raw_fd(), &mut st) } < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok((st.st_dev, st.st_ino))
+    };
+    Ok(file_id(a)? == file_id(b)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -55,19 +285,58 @@ mod tests {
     use std::io::{Seek, SeekFrom, Write};
     use std::path::PathBuf;
 
+    use crate::fallocate::is_range_allocated;
     use crate::tempdir::TempDir;
+    use crate::tempfile::TempFile;
 
-    #[test]
-    fn test_fsync() {
-        let tempdir = TempDir::new_with_prefix("/tmp/fsync_test").unwrap();
+    // Keeps track of which bytes are allocated.
+    struct FakeFile {
+        allocated: Vec<bool>,
+    }
+
+    impl FileAllocate for FakeFile {
+        fn allocate(&mut self, offset: u64, len: u64) -> Result<()> {
+            let end = (offset + len) as usize;
+            if end > self.allocated.len() {
+                self.allocated.resize(end, false);
+            }
+            for a in &mut self.allocated[offset as usize..end] {
+                *a = true;
+            }
+            Ok(())
+        }
+
+        fn deallocate(&mut self, offset: u64, len: u64) -> Result<()> {
+            let end = std::cmp::min((offset + len) as usize, self.allocated.len());
+            for a in &mut self.allocated[offset as usize..end] {
+                *a = false;
+            }
+            Ok(())
+        }
+    }
+
+    // Generic code that only relies on the trait.
+    fn reserve_then_trim<F: FileAllocate>(mut f: F) {
+        f.allocate(0, 8192).unwrap();
+        f.deallocate(0, 4096).unwrap();
+    }
+
+    fn create_file(tempdir: &TempDir) -> File {
         let mut path = PathBuf::from(tempdir.as_path());
         path.push("file");
-        let mut f = OpenOptions::new()
+        OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&path)
-            .unwrap();
+            .unwrap()
+    }
+
+    #[test]
+    fn test_fsync() {
+        let tempdir = TempDir::new_with_prefix("/tmp/fsync_test").unwrap();
+        let mut f = create_file(&tempdir);
         f.write_all(b"Hello, world!").unwrap();
         f.fsync().unwrap();
         assert_eq!(f.metadata().unwrap().len(), 13);
@@ -76,15 +345,132 @@ mod tests {
     #[test]
     fn test_set_len() {
         let tempdir = TempDir::new_with_prefix("/tmp/set_len_test").unwrap();
-        let mut path = PathBuf::from(tempdir.as_path());
-        path.push("file");
-        let mut f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&path)
-            .unwrap();
+        let mut f = create_file(&tempdir);
         f.set_len(10).unwrap();
         assert_eq!(f.seek(SeekFrom::End(0)).unwrap(), 10);
     }
+
+    #[test]
+    fn test_file_allocate_fake() {
+        let mut fake = FakeFile {
+            allocated: Vec::new(),
+        };
+        reserve_then_trim(&mut fake);
+        assert_eq!(fake.allocated.len(), 8192);
+        assert!(fake.allocated[..4096].iter().all(|a| !a));
+        assert!(fake.allocated[4096..].iter().all(|a| *a));
+    }
+
+    #[test]
+    fn test_file_allocate() {
+        let tempfile = TempFile::new().unwrap();
+        let mut f = tempfile.as_file().try_clone().unwrap();
+
+        f.allocate(0, 8192).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 8192);
+        assert!(is_range_allocated(&f, 0, 8192).unwrap());
+
+        f.deallocate(0, 4096).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 8192);
+        assert!(!is_range_allocated(&f, 0, 4096).unwrap());
+        assert!(is_range_allocated(&f, 4096, 4096).unwrap());
+
+        // The generic path works on real files too.
+        reserve_then_trim(&mut f);
+        assert!(!is_range_allocated(&f, 0, 4096).unwrap());
+    }
+
+    #[test]
+    fn test_clone_file_cloexec() {
+        let tempfile = TempFile::new().unwrap();
+        let mut f = tempfile.as_file();
+        // Clear FD_CLOEXEC on the original, which the clone must not inherit.
+        crate::fcntl::set_cloexec(f.as_raw_fd(), false).unwrap();
+
+        let mut clone = clone_file_cloexec(f).unwrap();
+        assert_ne!(clone.as_raw_fd(), f.as_raw_fd());
+        let flags = unsafe { fcntl(clone.as_raw_fd(), libc::F_GETFD) };
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+        let flags = unsafe { fcntl(f.as_raw_fd(), libc::F_GETFD) };
+        assert_eq!(flags & libc::FD_CLOEXEC, 0);
+
+        // The clone shares the file offset.
+        clone.write_all(b"abc").unwrap();
+        assert_eq!(f.stream_position().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_file_lock() {
+        let tempfile = TempFile::new().unwrap();
+        let f = tempfile.as_file();
+        let other = File::open(tempfile.as_path()).unwrap();
+
+        f.flock_exclusive().unwrap();
+        assert!(!other.try_flock_exclusive().unwrap());
+        // The lock is shared with the duplicates of the file descriptor.
+        assert!(clone_file_cloexec(f)
+            .unwrap()
+            .try_flock_exclusive()
+            .unwrap());
+
+        f.funlock().unwrap();
+        assert!(other.try_flock_exclusive().unwrap());
+        assert!(!f.try_flock_exclusive().unwrap());
+
+        // Shared locks can be held together, but exclude exclusive ones.
+        other.flock_shared().unwrap();
+        f.flock_shared().unwrap();
+        other.funlock().unwrap();
+        assert!(!other.try_flock_exclusive().unwrap());
+        f.funlock().unwrap();
+        assert!(other.try_flock_exclusive().unwrap());
+    }
+
+    #[test]
+    fn test_same_file() {
+        let tempfile = TempFile::new().unwrap();
+        let f = tempfile.as_file();
+        assert!(same_file(f, f).unwrap());
+        assert!(same_file(f, &clone_file_cloexec(f).unwrap()).unwrap());
+        assert!(same_file(f, &File::open(tempfile.as_path()).unwrap()).unwrap());
+
+        let other = TempFile::new().unwrap();
+        assert!(!same_file(f, other.as_file()).unwrap());
+    }
+
+    #[test]
+    fn test_allocated_size() {
+        let tempfile = TempFile::new().unwrap();
+        let mut f = tempfile.as_file();
+        assert_eq!(allocated_size(f).unwrap(), 0);
+
+        // A large sparse file with a single small write only allocates a few blocks.
+        let len = 64 * 1024 * 1024;
+        f.set_len(len).unwrap();
+        f.seek(SeekFrom::Start(len / 2)).unwrap();
+        f.write_all(b"data").unwrap();
+        f.sync_all().unwrap();
+
+        let allocated = allocated_size(f).unwrap();
+        assert!(allocated > 0);
+        assert!(allocated < len / 64);
+        assert_eq!(f.metadata().unwrap().len(), len);
+    }
+
+    #[test]
+    fn test_read_file_capped() {
+        let tempdir = TempDir::new_with_prefix("/tmp/read_file_capped_test").unwrap();
+        let mut f = create_file(&tempdir);
+        f.write_all(b"Hello, world!").unwrap();
+
+        let mut path = tempdir.as_path().join("file");
+        assert_eq!(read_file_capped(&path, 13).unwrap(), b"Hello, world!");
+        assert_eq!(read_file_capped(&path, 100).unwrap(), b"Hello, world!");
+
+        let err = read_file_capped(&path, 12).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        path.push("missing");
+        assert!(read_file_capped(&path, 100).is_err());
+    }
 }
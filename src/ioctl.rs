@@ -9,8 +9,14 @@
 //! Macros and functions for working with
 //! [`ioctl`](http://man7.org/linux/man-pages/man2/ioctl.2.html).
 use libc;
+use std::fmt::{self, Display};
+use std::io;
+use std::mem;
 use std::os::raw::{c_int, c_ulong, c_void};
 use std::os::unix::io::AsRawFd;
+use std::result;
+
+use crate::errno;
 
 /// Expression that calculates an ioctl number.
 ///
@@ -372,8 +378,149 @@ pub unsafe fn ioctl_with_mut_ptr<F: AsRawFd, T>(fd: &F, req: c_ulong, arg: *mut
     libc::ioctl(fd.as_raw_fd(), req as IoctlRequest, arg as *mut c_void)
 }
 
+/// The error of a failed ioctl, along with its request code.
+///
+/// A bare errno doesn't tell which ioctl failed, which makes the failures deep in
+/// device code hard to diagnose. Keeping the request code around tells it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IoctlError {
+    /// The request code of the failed ioctl.
+    pub request: c_ulong,
+    /// The error returned by the ioctl.
+    pub errno: errno::Error,
+}
+
+impl Display for IoctlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ioctl {:#x} failed: {}", self.request, self.errno)
+    }
+}
+
+/// Run an [`ioctl`](http://man7.org/linux/man-pages/man2/ioctl.2.html)
+/// with an immutable reference, reporting the request code on failure.
+///
+/// On success, returns the non-negative return value of the ioctl. On failure,
+/// returns an [`IoctlError`](struct.IoctlError.html) holding both `req` and the
+/// errno set by the ioctl.
+///
+/// # Arguments
+///
+/// * `fd`: an open file descriptor corresponding to the device on which
+///   to call the ioctl.
+/// * `req`: a device-dependent request code.
+/// * `arg`: an immutable reference passed to ioctl.
+///
+/// # Safety
+///
+/// As for [`ioctl_with_ref`](fn.ioctl_with_ref.html), the caller should ensure
+/// to pass a valid file descriptor, and that `req` only reads from `arg`.
+pub unsafe fn ioctl_with_ref_named<F: AsRawFd, T>(
+    fd: &F,
+    req: c_ulong,
+    arg: &T,
+) -> result::Result<c_int, IoctlError> {
+    let ret = ioctl_with_ref(fd, req, arg);
+    if ret < 0 {
+        return Err(IoctlError {
+            request: req,
+            errno: errno::Error::last(),
+        });
+    }
+    Ok(ret)
+}
+
+/// Check whether a file descriptor refers to a character device.
+///
+/// Device ioctls issued on other kinds of files (e.g. a regular file passed by
+/// mistake) fail with a confusing `ENOTTY`, so this can be used for catching
+/// such wiring bugs before calling the ioctl.
+///
+/// # Arguments
+///
+/// * `fd`: an open file descriptor.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::fs::File;
+/// use vmm_sys_util::ioctl::is_char_device;
+///
+/// let null = File::open("/dev/null").unwrap();
+/// assert!(is_char_device(&null).unwrap());
+/// ```
+pub fn is_char_device<F: AsRawFd>(fd: &F) -> io::Result<bool> {
+    // This is safe because `stat64` only contains primitive fields.
+    let mut st: libc::stat64 = unsafe { mem::zeroed() };
+    // This is safe because the kernel only writes to `st`, which is properly sized,
+    // and we check the return value.
+    let ret = unsafe { libc::fstat64(fd.as_raw_fd(), &mut st) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(st.st_mode & libc::S_IFMT == libc::S_IFCHR)
+}
+
+/// Set or clear the non-blocking mode of a file descriptor with the `FIONBIO` ioctl.
+///
+/// This has the same effect as [`fcntl::set_nonblocking`](../fcntl/fn.set_nonblocking.html),
+/// but it is a single call instead of reading and then writing the status flags.
+///
+/// # Arguments
+///
+/// * `fd`: an open file descriptor.
+/// * `on`: whether to enable or disable the non-blocking mode.
+pub fn set_nonblocking_ioctl<F: AsRawFd>(fd: &F, on: bool) -> io::Result<()> {
+    let arg = c_int::from(on);
+    // This is safe because FIONBIO only reads the int we pass, and we check the return value.
+    let ret = unsafe { ioctl_with_ref(fd, libc::FIONBIO as c_ulong, &arg) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Get the number of bytes that can be read from a file descriptor without blocking, with
+/// the `FIONREAD` ioctl.
+///
+/// For a datagram socket, this is the size of the next datagram, which can be used for
+/// sizing the receive buffer.
+///
+/// # Arguments
+///
+/// * `fd`: an open file descriptor, e.g. a socket, a pipe or a terminal.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::io::Write;
+/// # use std::os::unix::net::UnixStream;
+/// use vmm_sys_util::ioctl::bytes_available;
+///
+/// let (mut s1, s2) = UnixStream::pair().unwrap();
+/// s1.write_all(b"hello").unwrap();
+/// assert_eq!(bytes_available(&s2).unwrap(), 5);
+/// ```
+pub fn bytes_available<F: AsRawFd>(fd: &F) -> io::Result<usize> {
+    let mut count: c_int = 0;
+    // This is safe because FIONREAD only writes an int to `count`, and we check the return
+    // value.
+    let ret = unsafe { ioctl_with_mut_ref(fd, libc::FIONREAD as c_ulong, &mut count) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(count as usize)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::tempfile::TempFile;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::net::{UnixDatagram, UnixStream};
+
     const TUNTAP: ::std::os::raw::c_uint = 0x54;
     const VHOST: ::std::os::raw::c_uint = 0xAF;
     const EVDEV: ::std::os::raw::c_uint = 0x45;
@@ -402,4 +549,67 @@ mod tests {
         assert_eq!(0x8080_4522, EVIOCGBIT(2));
         assert_eq!(0x0000_4509, FAKE_IOCTL_2_ARG(3, 5));
     }
+
+    #[test]
+    fn test_ioctl_with_ref_named() {
+        let tempfile = TempFile::new().unwrap();
+        // Safe because winsize only contains primitive fields.
+        let ws: libc::winsize = unsafe { mem::zeroed() };
+        // Setting the window size of a regular file fails, and TIOCSWINSZ only reads `ws`.
+        let err =
+            unsafe { ioctl_with_ref_named(tempfile.as_file(), libc::TIOCSWINSZ, &ws) }.unwrap_err();
+        assert_eq!(err.request, libc::TIOCSWINSZ);
+        assert_eq!(err.errno, errno::Error::new(libc::ENOTTY));
+        assert!(format!("{}", err).starts_with(&format!("ioctl {:#x} failed", libc::TIOCSWINSZ)));
+    }
+
+    #[test]
+    fn test_set_nonblocking_ioctl() {
+        let (_s1, mut s2) = UnixStream::pair().unwrap();
+        let nonblocking = |s: &UnixStream| {
+            let flags = unsafe { libc::fcntl(s.as_raw_fd(), libc::F_GETFL) };
+            flags & libc::O_NONBLOCK != 0
+        };
+
+        set_nonblocking_ioctl(&s2, true).unwrap();
+        assert!(nonblocking(&s2));
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            s2.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        set_nonblocking_ioctl(&s2, false).unwrap();
+        assert!(!nonblocking(&s2));
+    }
+
+    #[test]
+    fn test_bytes_available() {
+        let (mut s1, mut s2) = UnixStream::pair().unwrap();
+        assert_eq!(bytes_available(&s2).unwrap(), 0);
+        s1.write_all(&[0u8; 100]).unwrap();
+        s1.write_all(&[0u8; 23]).unwrap();
+        assert_eq!(bytes_available(&s2).unwrap(), 123);
+
+        let mut buf = [0u8; 100];
+        s2.read_exact(&mut buf).unwrap();
+        assert_eq!(bytes_available(&s2).unwrap(), 23);
+
+        // For datagrams, only the size of the next one is reported.
+        let (d1, d2) = UnixDatagram::pair().unwrap();
+        d1.send(&[0u8; 10]).unwrap();
+        d1.send(&[0u8; 20]).unwrap();
+        assert_eq!(bytes_available(&d2).unwrap(), 10);
+
+        assert!(bytes_available(&File::open("/dev/null").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_is_char_device() {
+        let tempfile = TempFile::new().unwrap();
+        assert!(!is_char_device(tempfile.as_file()).unwrap());
+
+        let null = File::open("/dev/null").unwrap();
+        assert!(is_char_device(&null).unwrap());
+    }
 }
@@ -15,11 +15,13 @@ pub mod errno;
 pub mod eventfd;
 pub mod fallocate;
 pub mod fam;
+pub mod fcntl;
 pub mod file_traits;
 pub mod poll;
 pub mod rand;
 pub mod seek_hole;
 pub mod signal;
+pub mod signalfd;
 pub mod sock_ctrl_msg;
 pub mod tempdir;
 pub mod tempfile;
@@ -9,35 +9,48 @@
 
 use std::cell::{Cell, Ref, RefCell};
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::i32;
 use std::i64;
-use std::io::{stderr, Cursor, Write};
+use std::io::{self, stderr, Cursor, Write};
 use std::marker::PhantomData;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::panic::Location;
 use std::ptr::null_mut;
 use std::slice;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use libc::{
-    c_int, epoll_create1, epoll_ctl, epoll_event, epoll_wait, EINTR, EPOLLHUP, EPOLLIN, EPOLLOUT,
-    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD,
+    c_int, c_short, epoll_create1, epoll_ctl, epoll_event, epoll_wait, poll, pollfd, EBADF, EINTR,
+    EPOLLERR, EPOLLET, EPOLLHUP, EPOLLIN, EPOLLOUT, EPOLLPRI, EPOLL_CLOEXEC, EPOLL_CTL_ADD,
+    EPOLL_CTL_DEL, EPOLL_CTL_MOD, POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT,
 };
 
 use crate::errno::{errno_result, Error, Result};
+use crate::eventfd::{EventFd, EFD_NONBLOCK};
+use crate::timerfd::TimerFd;
+
+// Convert `timeout` to a number of milliseconds suitable for `epoll_wait` and `poll`.
+fn duration_to_millis(timeout: Duration) -> c_int {
+    // In cases where we the number of milliseconds would overflow an i32, we substitute the
+    // maximum timeout which is ~24.8 days.
+    let millis = timeout
+        .as_secs()
+        .checked_mul(1_000)
+        .and_then(|ms| ms.checked_add(u64::from(timeout.subsec_nanos()) / 1_000_000))
+        .unwrap_or(i32::max_value() as u64);
+    min(i32::max_value() as u64, millis) as i32
+}
 
-macro_rules! handle_eintr_errno {
-    ($x:expr) => {{
-        let mut res;
-        loop {
-            res = $x;
-            if res != -1 || Error::last() != Error::new(EINTR) {
-                break;
-            }
-        }
-        res
-    }};
+// Convert the time remaining until `deadline` to a number of milliseconds for resuming an
+// interrupted wait. This is rounded up, so that the resumed wait doesn't end before the
+// original timeout.
+fn remaining_millis(deadline: &Deadline) -> c_int {
+    deadline.remaining().map_or(0, |remaining| {
+        duration_to_millis(remaining + Duration::from_nanos(999_999))
+    })
 }
 
 const POLL_CONTEXT_MAX_EVENTS: usize = 16;
@@ -157,6 +170,24 @@ impl<'a, T: PollToken> PollEvent<'a, T> {
         self.event.events & (EPOLLIN as u32) != 0
     }
 
+    /// Checks if the event is writable.
+    ///
+    /// True if the `fd` associated with this token in
+    /// [`PollContext::add_fd_with_events`](struct.PollContext.html#method.add_fd_with_events)
+    /// is writable.
+    pub fn writable(&self) -> bool {
+        self.event.events & (EPOLLOUT as u32) != 0
+    }
+
+    /// Gets all the events reported for the `fd` associated with this token.
+    ///
+    /// This is useful for telling apart the kinds of readiness of an `fd` watched for
+    /// several events, e.g. with
+    /// `(readiness.get_raw() & WatchingEvents::empty().set_write().get_raw()) != 0`.
+    pub fn readiness(&self) -> WatchingEvents {
+        WatchingEvents::new(self.event.events)
+    }
+
     /// Checks if the event has been hangup on.
     ///
     /// True if the `fd` associated with this token in
@@ -164,6 +195,31 @@ impl<'a, T: PollToken> PollEvent<'a, T> {
     pub fn hungup(&self) -> bool {
         self.event.events & (EPOLLHUP as u32) != 0
     }
+
+    /// Checks if an error condition happened on the event.
+    ///
+    /// True if the `fd` associated with this token in
+    /// [`PollContext::add`](struct.PollContext.html#method.add) has an error condition,
+    /// such as a pipe whose read end has been closed.
+    ///
+    /// Hangups and errors are always reported, whatever events are watched, so an `fd`
+    /// reporting them should be removed with
+    /// [`PollContext::delete`](struct.PollContext.html#method.delete) to avoid having
+    /// every following wait return immediately.
+    /// [`PollContext::wait_handling_errors`](struct.PollContext.html#method.wait_handling_errors)
+    /// does this automatically.
+    pub fn error(&self) -> bool {
+        self.event.events & (EPOLLERR as u32) != 0
+    }
+
+    /// Checks if the event has priority data available.
+    ///
+    /// True if the `fd` associated with this token in
+    /// [`PollContext::add`](struct.PollContext.html#method.add) has an exceptional condition,
+    /// such as out-of-band data on a TCP socket or a change of a sysfs GPIO value file.
+    pub fn priority(&self) -> bool {
+        self.event.events & (EPOLLPRI as u32) != 0
+    }
 }
 
 /// An iterator over a subset of events returned by
@@ -240,6 +296,24 @@ impl<'a, T: PollToken> PollEvents<'a, T> {
             tokens: PhantomData,
         }
     }
+
+    /// Iterates over each event with an error condition.
+    pub fn iter_error(&self) -> PollEventIter<'_, slice::Iter<'_, epoll_event>, T> {
+        PollEventIter {
+            mask: EPOLLERR as u32,
+            iter: self.events[..self.count].iter(),
+            tokens: PhantomData,
+        }
+    }
+
+    /// Iterates over each priority event.
+    pub fn iter_priority(&self) -> PollEventIter<'_, slice::Iter<'_, epoll_event>, T> {
+        PollEventIter {
+            mask: EPOLLPRI as u32,
+            iter: self.events[..self.count].iter(),
+            tokens: PhantomData,
+        }
+    }
 }
 
 /// A deep copy of the event records from [`PollEvents`](struct.PollEvents.html).
@@ -302,12 +376,53 @@ impl WatchingEvents {
         WatchingEvents(self.0 | EPOLLOUT as u32)
     }
 
+    /// Sets priority events.
+    ///
+    /// Sets the events to report urgent (priority) data, i.e. `EPOLLPRI`.
+    #[inline(always)]
+    pub fn set_priority(self) -> WatchingEvents {
+        WatchingEvents(self.0 | EPOLLPRI as u32)
+    }
+
+    /// Sets edge-triggered notifications.
+    ///
+    /// Sets the events to be reported only when they occur, i.e. `EPOLLET`, instead of for as
+    /// long as they are pending. See
+    /// [`EpollContext::add_edge_triggered`](struct.EpollContext.html#method.add_edge_triggered)
+    /// for what this requires from the handlers.
+    #[inline(always)]
+    pub fn set_edge_triggered(self) -> WatchingEvents {
+        WatchingEvents(self.0 | EPOLLET as u32)
+    }
+
     /// Gets the underlying epoll events.
     pub fn get_raw(&self) -> u32 {
         self.0
     }
 }
 
+/// Operation to be performed on an `fd` of an [`EpollContext`](struct.EpollContext.html)
+/// through [`ctl`](struct.EpollContext.html#method.ctl).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlOperation {
+    /// Add the `fd` to the context (`EPOLL_CTL_ADD`).
+    Add,
+    /// Change the events and token associated with the `fd` (`EPOLL_CTL_MOD`).
+    Modify,
+    /// Remove the `fd` from the context (`EPOLL_CTL_DEL`).
+    Delete,
+}
+
+impl ControlOperation {
+    fn as_raw(self) -> c_int {
+        match self {
+            ControlOperation::Add => EPOLL_CTL_ADD,
+            ControlOperation::Modify => EPOLL_CTL_MOD,
+            ControlOperation::Delete => EPOLL_CTL_DEL,
+        }
+    }
+}
+
 /// A wrapper of linux [`epoll`](http://man7.org/linux/man-pages/man7/epoll.7.html).
 ///
 /// It provides similar interface to [`PollContext`](struct.PollContext.html).
@@ -424,24 +539,81 @@ impl<T: PollToken> EpollContext<T> {
         events: WatchingEvents,
         token: T,
     ) -> Result<()> {
-        let mut evt = epoll_event {
-            events: events.get_raw(),
-            u64: token.as_raw_token(),
-        };
-        // Safe because we give a valid epoll FD and FD to watch, as well as a valid epoll_event
-        // structure. Then we check the return value.
-        let ret = unsafe {
-            epoll_ctl(
-                self.epoll_ctx.as_raw_fd(),
-                EPOLL_CTL_ADD,
-                fd.as_raw_fd(),
-                &mut evt,
-            )
-        };
-        if ret < 0 {
-            return errno_result();
-        };
-        Ok(())
+        self.ctl(ControlOperation::Add, fd, events, token)
+    }
+
+    /// Adds the given `fd` to this context, reporting its readable events as edge-triggered
+    /// (`EPOLLET`), and associates the given `token` with those events.
+    ///
+    /// **The handler of each event must drain `fd`**, e.g. by reading it until it fails with
+    /// `EAGAIN`. An edge-triggered event is reported once when `fd` becomes readable, and not
+    /// again until new data arrives: the data left unread after an event doesn't make
+    /// [`wait`](struct.EpollContext.html#method.wait) return, and may never be handled. This
+    /// is why `fd` should be nonblocking, so that draining it doesn't block on the last read.
+    /// Wrapping the handlers in [`handle_edge_triggered`](fn.handle_edge_triggered.html)
+    /// reports the ones that don't drain `fd` in debug builds.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`: the target file descriptor to be added.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+    /// use vmm_sys_util::poll::{EpollContext, EpollEvents};
+    ///
+    /// let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+    /// let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+    /// let events = EpollEvents::new();
+    /// ctx.add_edge_triggered(&evt, 1).unwrap();
+    ///
+    /// evt.write(1).unwrap();
+    /// assert_eq!(ctx.wait(&events).unwrap().iter_readable().count(), 1);
+    /// // A single read drains an `EventFd`.
+    /// evt.read().unwrap();
+    /// ```
+    pub fn add_edge_triggered(&self, fd: &dyn AsRawFd, token: T) -> Result<()> {
+        self.add_fd_with_events(
+            fd,
+            WatchingEvents::empty().set_read().set_edge_triggered(),
+            token,
+        )
+    }
+
+    /// Creates a new [`EventFd`](../eventfd/struct.EventFd.html) and adds it to this context,
+    /// associating the given `token` with its readable events.
+    ///
+    /// The caller owns the returned `EventFd`, which stops being reported by `wait` when it
+    /// is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags`: the flags of the new `EventFd`, e.g. `EFD_NONBLOCK`.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::EFD_NONBLOCK;
+    /// use vmm_sys_util::poll::{EpollContext, EpollEvents};
+    ///
+    /// let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+    /// let events = EpollEvents::new();
+    /// let evt = ctx.add_eventfd(EFD_NONBLOCK, 1).unwrap();
+    ///
+    /// evt.write(1).unwrap();
+    /// for event in ctx.wait(&events).unwrap().iter_readable() {
+    ///     assert_eq!(event.token(), 1);
+    /// }
+    /// ```
+    pub fn add_eventfd(&self, flags: i32, token: T) -> Result<EventFd> {
+        let evt = EventFd::new(flags).map_err(Error::from)?;
+        self.add(&evt, token)?;
+        Ok(evt)
     }
 
     /// Changes the setting associated with the given `fd` in this context.
@@ -468,16 +640,53 @@ impl<T: PollToken> EpollContext<T> {
     /// ctx.modify(&evt, WatchingEvents::empty().set_write(), 2).unwrap();
     /// ```
     pub fn modify(&self, fd: &dyn AsRawFd, events: WatchingEvents, token: T) -> Result<()> {
+        self.ctl(ControlOperation::Modify, fd, events, token)
+    }
+
+    /// Performs the control operation `op` on the given `fd` in this context.
+    ///
+    /// Adding an `fd` that is already in this context fails with `EEXIST`, while modifying or
+    /// deleting one that isn't fails with `ENOENT`. The `events` and `token` are ignored when
+    /// deleting.
+    ///
+    /// # Arguments
+    ///
+    /// * `op`: the operation to be performed.
+    /// * `fd`: the target file descriptor.
+    /// * `events`: specifies the events to be watched.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::EventFd;
+    /// use vmm_sys_util::poll::{ControlOperation, EpollContext, WatchingEvents};
+    ///
+    /// let evt = EventFd::new(0).unwrap();
+    /// let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+    /// ctx.ctl(ControlOperation::Add, &evt, WatchingEvents::empty().set_read(), 1)
+    ///     .unwrap();
+    /// ctx.ctl(ControlOperation::Delete, &evt, WatchingEvents::empty(), 1)
+    ///     .unwrap();
+    /// ```
+    pub fn ctl(
+        &self,
+        op: ControlOperation,
+        fd: &dyn AsRawFd,
+        events: WatchingEvents,
+        token: T,
+    ) -> Result<()> {
         let mut evt = epoll_event {
-            events: events.0,
+            events: events.get_raw(),
             u64: token.as_raw_token(),
         };
-        // Safe because we give a valid epoll FD and FD to modify, as well as a valid epoll_event
-        // structure. Then we check the return value.
+        // Safe because we give a valid epoll FD and FD to operate on, as well as a valid
+        // epoll_event structure. Then we check the return value.
         let ret = unsafe {
             epoll_ctl(
                 self.epoll_ctx.as_raw_fd(),
-                EPOLL_CTL_MOD,
+                op.as_raw(),
                 fd.as_raw_fd(),
                 &mut evt,
             )
@@ -566,7 +775,10 @@ impl<T: PollToken> EpollContext<T> {
     /// maximum of the given `timeout`.
     ///
     /// This may return earlier than `timeout` with zero events if the duration indicated exceeds
-    /// system limits.
+    /// system limits. A wait interrupted by a signal is resumed for the time remaining until
+    /// the original timeout, so that signals don't extend the wait; see
+    /// [`wait_timeout_interruptible`](struct.EpollContext.html#method.wait_timeout_interruptible)
+    /// for returning on interruptions instead.
     ///
     /// # Arguments
     ///
@@ -596,32 +808,61 @@ impl<T: PollToken> EpollContext<T> {
         events: &'a EpollEvents,
         timeout: Duration,
     ) -> Result<PollEvents<'a, T>> {
-        let timeout_millis = if timeout.as_secs() as i64 == i64::max_value() {
+        self.wait_events(events, timeout, true)
+    }
+
+    /// Like [`wait_timeout`](struct.EpollContext.html#method.wait_timeout) except that it fails
+    /// with `EINTR` when the wait is interrupted by a signal.
+    ///
+    /// This is meant for callers using a signal for breaking out of the wait (e.g. for asking
+    /// the waiting thread to stop); any other caller should use `wait_timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `events`: the events to wait for.
+    /// * `timeout`: specifies the timeout that will block.
+    pub fn wait_timeout_interruptible<'a>(
+        &self,
+        events: &'a EpollEvents,
+        timeout: Duration,
+    ) -> Result<PollEvents<'a, T>> {
+        self.wait_events(events, timeout, false)
+    }
+
+    fn wait_events<'a>(
+        &self,
+        events: &'a EpollEvents,
+        timeout: Duration,
+        restart: bool,
+    ) -> Result<PollEvents<'a, T>> {
+        let deadline = if timeout.as_secs() as i64 == i64::MAX {
             // We make the convenient assumption that 2^63 seconds is an effectively unbounded time
             // frame. This is meant to mesh with `wait` calling us with no timeout.
-            -1
+            None
         } else {
-            // In cases where we the number of milliseconds would overflow an i32, we substitute the
-            // maximum timeout which is ~24.8 days.
-            let millis = timeout
-                .as_secs()
-                .checked_mul(1_000)
-                .and_then(|ms| ms.checked_add(u64::from(timeout.subsec_nanos()) / 1_000_000))
-                .unwrap_or(i32::max_value() as u64);
-            min(i32::max_value() as u64, millis) as i32
+            Some(Deadline::new(timeout))
         };
-        let ret = {
-            let mut epoll_events = events.0.borrow_mut();
-            let max_events = epoll_events.len() as c_int;
-            // Safe because we give an epoll context and a properly sized epoll_events array
-            // pointer, which we trust the kernel to fill in properly.
-            unsafe {
-                handle_eintr_errno!(epoll_wait(
-                    self.epoll_ctx.as_raw_fd(),
-                    &mut epoll_events[0],
-                    max_events,
-                    timeout_millis
-                ))
+        let mut timeout_millis = deadline.map_or(-1, |_| duration_to_millis(timeout));
+        let ret = loop {
+            let ret = {
+                let mut epoll_events = events.0.borrow_mut();
+                let max_events = epoll_events.len() as c_int;
+                // Safe because we give an epoll context and a properly sized epoll_events array
+                // pointer, which we trust the kernel to fill in properly.
+                unsafe {
+                    epoll_wait(
+                        self.epoll_ctx.as_raw_fd(),
+                        &mut epoll_events[0],
+                        max_events,
+                        timeout_millis,
+                    )
+                }
+            };
+            if ret >= 0 || !restart || Error::last() != Error::new(EINTR) {
+                break ret;
+            }
+            if let Some(deadline) = deadline {
+                timeout_millis = remaining_millis(&deadline);
             }
         };
         if ret < 0 {
@@ -643,6 +884,12 @@ impl<T: PollToken> AsRawFd for EpollContext<T> {
     }
 }
 
+impl<T: PollToken> AsFd for EpollContext<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epoll_ctx.as_fd()
+    }
+}
+
 impl<T: PollToken> IntoRawFd for EpollContext<T> {
     fn into_raw_fd(self) -> RawFd {
         self.epoll_ctx.into_raw_fd()
@@ -683,6 +930,13 @@ pub struct PollContext<T> {
     // Hangup busy loop detection variables. See `check_for_hungup_busy_loop`.
     hangups: Cell<usize>,
     max_hangups: Cell<usize>,
+
+    // The raw watched events and token of each FD added to this context, for `registered`.
+    registered: RefCell<BTreeMap<RawFd, (u32, u64)>>,
+
+    // The FD and the result of reading the expirations in the last wait of each timer added
+    // with `add_timerfd`, by raw token.
+    timers: RefCell<BTreeMap<u64, (RawFd, Result<u64>)>>,
 }
 
 impl<T: PollToken> PollContext<T> {
@@ -693,9 +947,50 @@ impl<T: PollToken> PollContext<T> {
             events: EpollEvents::new(),
             hangups: Cell::new(0),
             max_hangups: Cell::new(0),
+            registered: RefCell::new(BTreeMap::new()),
+            timers: RefCell::new(BTreeMap::new()),
         })
     }
 
+    /// Creates a new `PollContext` watching each of the given file descriptors.
+    ///
+    /// This is a shorthand for calling [`new`](struct.PollContext.html#method.new) and then
+    /// [`add_fd_with_events`](struct.PollContext.html#method.add_fd_with_events) for each item,
+    /// e.g. for a device with a fixed set of file descriptors. Fails with the first error
+    /// returned when adding a file descriptor, in which case the context is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `items`: the file descriptors to be added, along with their watched events and tokens.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::os::unix::io::AsRawFd;
+    /// # use vmm_sys_util::eventfd::EventFd;
+    /// # use vmm_sys_util::poll::{PollContext, WatchingEvents};
+    /// let evt1 = EventFd::new(0).unwrap();
+    /// let evt2 = EventFd::new(0).unwrap();
+    ///
+    /// let ctx: PollContext<u32> = PollContext::build_with(vec![
+    ///     (evt1.as_raw_fd(), WatchingEvents::empty().set_read(), 1),
+    ///     (evt2.as_raw_fd(), WatchingEvents::empty().set_write(), 2),
+    /// ])
+    /// .unwrap();
+    /// assert_eq!(ctx.len(), 2);
+    /// ```
+    pub fn build_with<I>(items: I) -> Result<PollContext<T>>
+    where
+        I: IntoIterator<Item = (RawFd, WatchingEvents, T)>,
+    {
+        let ctx = PollContext::new()?;
+        for (fd, events, token) in items {
+            ctx.add_fd_with_events(&fd, events, token)?;
+        }
+        Ok(ctx)
+    }
+
     /// Adds the given `fd` to this context and associates the given `token` with the `fd`'s
     /// readable events.
     ///
@@ -703,6 +998,10 @@ impl<T: PollToken> PollContext<T> {
     /// there were no duplicated file descriptors (i.e. adding the same descriptor with a different
     /// FD number) added to this context, events will not be reported by `wait` anymore.
     ///
+    /// Adding a `fd` that is already in this context fails with `EEXIST` and leaves its
+    /// registration unchanged. Use [`replace`](struct.PollContext.html#method.replace) for
+    /// updating the registration of a `fd` that may already be in this context.
+    ///
     /// # Arguments
     ///
     /// * `fd`: the target file descriptor to be added.
@@ -718,6 +1017,9 @@ impl<T: PollToken> PollContext<T> {
     /// there were no duplicated file descriptors (i.e. adding the same descriptor with a different
     /// FD number) added to this context, events will not be reported by `wait` anymore.
     ///
+    /// As for [`add`](struct.PollContext.html#method.add), adding a `fd` that is already in
+    /// this context fails with `EEXIST`.
+    ///
     /// # Arguments
     ///
     /// * `fd`: the target file descriptor to be added.
@@ -729,12 +1031,107 @@ impl<T: PollToken> PollContext<T> {
         events: WatchingEvents,
         token: T,
     ) -> Result<()> {
+        let entry = (events.get_raw(), token.as_raw_token());
         self.epoll_ctx.add_fd_with_events(fd, events, token)?;
+        self.registered.borrow_mut().insert(fd.as_raw_fd(), entry);
         self.hangups.set(0);
         self.max_hangups.set(self.max_hangups.get() + 1);
         Ok(())
     }
 
+    /// Creates a new [`EventFd`](../eventfd/struct.EventFd.html) and adds it to this context,
+    /// associating the given `token` with its readable events.
+    ///
+    /// The caller owns the returned `EventFd`, which stops being reported by `wait` when it
+    /// is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags`: the flags of the new `EventFd`, e.g. `EFD_NONBLOCK`.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    pub fn add_eventfd(&self, flags: i32, token: T) -> Result<EventFd> {
+        let evt = EventFd::new(flags).map_err(Error::from)?;
+        self.add(&evt, token)?;
+        Ok(evt)
+    }
+
+    /// Adds the given timer to this context, associating the given `token` with its
+    /// expirations, which are read by the waits of this context.
+    ///
+    /// A timer stays readable until its expirations are read, so a wait loop that forgets
+    /// to read it degenerates into a busy loop. Instead, when a wait reports the `token` of
+    /// a timer added with this method, the expirations of the timer have already been read,
+    /// and their number is returned by [`expirations`](struct.PollContext.html#method.expirations).
+    /// Modifying or deleting the timer in this context stops reading its expirations.
+    ///
+    /// # Arguments
+    ///
+    /// * `tfd`: the timer to be added.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::poll::PollContext;
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut tfd = TimerFd::new().unwrap();
+    /// let ctx: PollContext<u32> = PollContext::new().unwrap();
+    /// ctx.add_timerfd(&tfd, 1).unwrap();
+    ///
+    /// tfd.reset(Duration::from_millis(10), None).unwrap();
+    /// for event in ctx.wait().unwrap().iter_readable() {
+    ///     assert_eq!(event.token(), 1);
+    /// }
+    /// assert_eq!(ctx.expirations(1).unwrap(), 1);
+    /// ```
+    pub fn add_timerfd(&self, tfd: &TimerFd, token: T) -> Result<()> {
+        let raw_token = token.as_raw_token();
+        self.add(tfd, token)?;
+        self.timers
+            .borrow_mut()
+            .insert(raw_token, (tfd.as_raw_fd(), Ok(0)));
+        Ok(())
+    }
+
+    /// Returns the number of expirations read by the last wait for the timer added with
+    /// [`add_timerfd`](struct.PollContext.html#method.add_timerfd) with the given `token`.
+    ///
+    /// This is zero if the last wait didn't report the timer, if the timer was reset or
+    /// disarmed (e.g. by another thread) before its expirations were read, or if no timer was
+    /// added with `token`. Fails with the error of reading the expirations, which doesn't
+    /// fail the wait itself, so the events of the other FDs are still returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `token`: the token of the timer.
+    pub fn expirations(&self, token: T) -> Result<u64> {
+        self.timers
+            .borrow()
+            .get(&token.as_raw_token())
+            .map_or(Ok(0), |(_, result)| *result)
+    }
+
+    // Reads the expirations of the readable timers added with `add_timerfd`.
+    fn read_timers(&self, events: &PollEvents<T>) {
+        let mut timers = self.timers.borrow_mut();
+        if timers.is_empty() {
+            return;
+        }
+        for (_, result) in timers.values_mut() {
+            *result = Ok(0);
+        }
+        for event in events.iter_readable() {
+            // Copy the token out of the packed `epoll_event` before borrowing it.
+            let raw_token = event.event.u64;
+            if let Some((fd, result)) = timers.get_mut(&raw_token) {
+                *result = read_timer_expirations(*fd);
+            }
+        }
+    }
+
     /// Changes the setting associated with the given `fd` in this context.
     ///
     /// If `fd` was previously added to this context, the watched events will be replaced with
@@ -746,7 +1143,56 @@ impl<T: PollToken> PollContext<T> {
     /// * `events`: specifies the events to be watched.
     /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
     pub fn modify(&self, fd: &dyn AsRawFd, events: WatchingEvents, token: T) -> Result<()> {
-        self.epoll_ctx.modify(fd, events, token)
+        let entry = (events.get_raw(), token.as_raw_token());
+        self.epoll_ctx.modify(fd, events, token)?;
+        self.registered.borrow_mut().insert(fd.as_raw_fd(), entry);
+        self.forget_timer(fd.as_raw_fd());
+        Ok(())
+    }
+
+    fn forget_timer(&self, fd: RawFd) {
+        self.timers
+            .borrow_mut()
+            .retain(|_, (timer_fd, _)| *timer_fd != fd);
+    }
+
+    /// Adds the given `fd` to this context, or replaces its setting if it was already added.
+    ///
+    /// Unlike [`add_fd_with_events`](struct.PollContext.html#method.add_fd_with_events), this
+    /// doesn't fail when `fd` is already in this context: its watched events and token are
+    /// replaced with `events` and `token` instead, as with
+    /// [`modify`](struct.PollContext.html#method.modify).
+    ///
+    /// # Arguments
+    ///
+    /// * `fd`: the target file descriptor to be added or modified.
+    /// * `events`: specifies the events to be watched.
+    /// * `token`: a `PollToken` implementation, used to be as u64 of `libc::epoll_event` structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::eventfd::EventFd;
+    /// use vmm_sys_util::poll::{PollContext, WatchingEvents};
+    ///
+    /// let evt = EventFd::new(0).unwrap();
+    /// let ctx: PollContext<u32> = PollContext::new().unwrap();
+    /// ctx.replace(&evt, WatchingEvents::empty().set_read(), 1).unwrap();
+    /// ctx.replace(&evt, WatchingEvents::empty().set_read(), 2).unwrap();
+    /// assert_eq!(ctx.len(), 1);
+    /// ```
+    pub fn replace(&self, fd: &dyn AsRawFd, events: WatchingEvents, token: T) -> Result<()> {
+        let raw_events = events.get_raw();
+        let raw_token = token.as_raw_token();
+        match self.add_fd_with_events(fd, events, token) {
+            Err(ref e) if e.errno() == libc::EEXIST => self.modify(
+                fd,
+                WatchingEvents::new(raw_events),
+                T::from_raw_token(raw_token),
+            ),
+            res => res,
+        }
     }
 
     /// Deletes the given `fd` from this context.
@@ -761,11 +1207,61 @@ impl<T: PollToken> PollContext<T> {
     /// * `fd`: the target file descriptor to be removed.
     pub fn delete(&self, fd: &dyn AsRawFd) -> Result<()> {
         self.epoll_ctx.delete(fd)?;
+        self.registered.borrow_mut().remove(&fd.as_raw_fd());
+        self.forget_timer(fd.as_raw_fd());
         self.hangups.set(0);
         self.max_hangups.set(self.max_hangups.get() - 1);
         Ok(())
     }
 
+    /// Returns the number of FDs in this context.
+    ///
+    /// This only accounts for `add` and `delete` calls: an FD that is closed without being
+    /// deleted is removed from the underlying epoll, but is still counted.
+    pub fn len(&self) -> usize {
+        self.registered.borrow().len()
+    }
+
+    /// Returns true if there are no FDs in this context.
+    ///
+    /// The same caveat as for [`len`](struct.PollContext.html#method.len) applies.
+    pub fn is_empty(&self) -> bool {
+        self.registered.borrow().is_empty()
+    }
+
+    /// Returns the FDs in this context, along with their watched events and raw tokens, in
+    /// ascending order of FD.
+    ///
+    /// This is a snapshot meant for debugging, e.g. logging the interest list of an event loop.
+    /// The same caveat as for [`len`](struct.PollContext.html#method.len) applies.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use std::os::unix::io::AsRawFd;
+    /// use vmm_sys_util::eventfd::EventFd;
+    /// use vmm_sys_util::poll::PollContext;
+    ///
+    /// let evt = EventFd::new(0).unwrap();
+    /// let ctx: PollContext<u32> = PollContext::new().unwrap();
+    /// ctx.add(&evt, 1).unwrap();
+    ///
+    /// for (fd, _events, token) in ctx.registered() {
+    ///     assert_eq!(fd, evt.as_raw_fd());
+    ///     assert_eq!(token, 1);
+    /// }
+    /// ```
+    pub fn registered(&self) -> impl Iterator<Item = (RawFd, WatchingEvents, u64)> {
+        let registered: Vec<_> = self
+            .registered
+            .borrow()
+            .iter()
+            .map(|(fd, (events, token))| (*fd, WatchingEvents::new(*events), *token))
+            .collect();
+        registered.into_iter()
+    }
+
     // This method determines if the the user of wait is misusing the `PollContext` by leaving FDs
     // in this `PollContext` that have been shutdown or hungup on. Such an FD will cause `wait` to
     // return instantly with a hungup event. If that FD is perpetually left in this context, a busy
@@ -837,7 +1333,9 @@ impl<T: PollToken> PollContext<T> {
     /// maximum of the given `timeout`.
     ///
     /// This may return earlier than `timeout` with zero events if the duration indicated exceeds
-    /// system limits.
+    /// system limits. As for
+    /// [`EpollContext::wait_timeout`](struct.EpollContext.html#method.wait_timeout), a wait
+    /// interrupted by a signal is resumed without extending the timeout.
     ///
     /// # Arguments
     ///
@@ -846,28 +1344,494 @@ impl<T: PollToken> PollContext<T> {
         let events = self.epoll_ctx.wait_timeout(&self.events, timeout)?;
         let hangups = events.iter_hungup().count();
         self.check_for_hungup_busy_loop(hangups);
+        self.read_timers(&events);
         Ok(events)
     }
-}
-
-impl<T: PollToken> AsRawFd for PollContext<T> {
-    fn as_raw_fd(&self) -> RawFd {
-        self.epoll_ctx.as_raw_fd()
-    }
-}
 
-impl<T: PollToken> IntoRawFd for PollContext<T> {
-    fn into_raw_fd(self) -> RawFd {
+    /// Like [`wait_timeout`](struct.PollContext.html#method.wait_timeout) except that it fails
+    /// with `EINTR` when the wait is interrupted by a signal, instead of resuming it.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout`: specify the time that will block.
+    pub fn wait_timeout_interruptible(&self, timeout: Duration) -> Result<PollEvents<'_, T>> {
+        let events = self
+            .epoll_ctx
+            .wait_timeout_interruptible(&self.events, timeout)?;
+        let hangups = events.iter_hungup().count();
+        self.check_for_hungup_busy_loop(hangups);
+        self.read_timers(&events);
+        Ok(events)
+    }
+
+    /// Like [`wait`](struct.PollContext.html#method.wait) except that the FDs reporting a
+    /// hangup or an error condition are deleted from this context.
+    ///
+    /// Hangups and errors are reported whatever events are watched, so an FD that isn't
+    /// deleted after reporting them makes every following wait return immediately. On
+    /// success, returns a tuple `(events, errored FDs)`, where the events still include the
+    /// ones of the errored FDs, so the caller can handle them (e.g. by closing the FDs).
+    ///
+    /// Several FDs may share the token of an event, so each FD registered with such a token is
+    /// polled to find out whether it is the one reporting a hangup or an error, and FDs that
+    /// don't are left in this context. Every errored FD comes with the result of deleting it,
+    /// and a failure to delete one FD doesn't prevent deleting the others.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use std::os::unix::io::AsRawFd;
+    /// use std::os::unix::net::UnixStream;
+    /// use vmm_sys_util::poll::PollContext;
+    ///
+    /// let (s1, s2) = UnixStream::pair().unwrap();
+    /// let ctx: PollContext<u32> = PollContext::new().unwrap();
+    /// ctx.add(&s1, 1).unwrap();
+    ///
+    /// drop(s2);
+    /// let (events, errored) = ctx.wait_handling_errors().unwrap();
+    /// assert_eq!(events.iter_hungup().count(), 1);
+    /// assert_eq!(errored, [(s1.as_raw_fd(), Ok(()))]);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn wait_handling_errors(&self) -> Result<(PollEvents<'_, T>, Vec<(RawFd, Result<()>)>)> {
+        let events = self.wait()?;
+        let mask = (EPOLLHUP | EPOLLERR) as u32;
+        let tokens: Vec<u64> = events
+            .iter()
+            .filter(|event| event.event.events & mask != 0)
+            .map(|event| event.event.u64)
+            .collect();
+        let candidates: Vec<RawFd> = self
+            .registered
+            .borrow()
+            .iter()
+            .filter(|(_, (_, token))| tokens.contains(token))
+            .map(|(fd, _)| *fd)
+            .collect();
+
+        let mut errored = Vec::new();
+        for fd in candidates {
+            // POLLHUP and POLLERR are always reported, without being requested.
+            let res = match wait_fd(&fd, 0, Some(Duration::from_millis(0))) {
+                Ok(revents) if revents & (POLLHUP | POLLERR) == 0 => continue,
+                Ok(_) => self.delete(&fd),
+                Err(e) => Err(e),
+            };
+            errored.push((fd, res));
+        }
+        Ok((events, errored))
+    }
+}
+
+impl<T: PollToken> AsRawFd for PollContext<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll_ctx.as_raw_fd()
+    }
+}
+
+impl<T: PollToken> AsFd for PollContext<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.epoll_ctx.as_fd()
+    }
+}
+
+impl<T: PollToken> IntoRawFd for PollContext<T> {
+    fn into_raw_fd(self) -> RawFd {
         self.epoll_ctx.into_raw_fd()
     }
 }
 
+/// An overall deadline for a sequence of waits.
+///
+/// Waiting in a loop (e.g. because a wait was interrupted by a signal, or returned events
+/// that didn't complete the operation) with the same timeout each time keeps pushing back
+/// the deadline. Instead, each wait can be given the time remaining until the deadline.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::time::Duration;
+/// use vmm_sys_util::eventfd::EventFd;
+/// use vmm_sys_util::poll::{Deadline, PollContext};
+///
+/// let evt = EventFd::new(0).unwrap();
+/// let ctx: PollContext<u32> = PollContext::new().unwrap();
+/// ctx.add(&evt, 1).unwrap();
+///
+/// let deadline = Deadline::new(Duration::from_millis(10));
+/// while let Some(remaining) = deadline.remaining() {
+///     if ctx.wait_timeout(remaining).unwrap().iter_readable().count() > 0 {
+///         break;
+///     }
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    start: Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    /// Create a new `Deadline` expiring `timeout` from now.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout`: the time until the deadline.
+    pub fn new(timeout: Duration) -> Self {
+        Deadline {
+            start: Instant::now(),
+            timeout,
+        }
+    }
+
+    /// Returns the time remaining until the deadline, or `None` if it has expired.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.timeout
+            .checked_sub(self.start.elapsed())
+            .filter(|remaining| *remaining > Duration::from_secs(0))
+    }
+
+    /// Checks if the deadline has expired.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_none()
+    }
+}
+
+// Reads the expirations of the timer `fd` without blocking, even though `TimerFd`s are blocking.
+// The timer isn't readable anymore if it was reset or disarmed since it was reported readable,
+// so it is checked first and reported with zero expirations in that case.
+fn read_timer_expirations(fd: RawFd) -> Result<u64> {
+    loop {
+        if wait_fd(&fd, POLLIN, Some(Duration::from_millis(0)))? & POLLIN == 0 {
+            return Ok(0);
+        }
+        let mut expirations = 0u64;
+        // Safe because we give a valid pointer to a u64, along with its size, and we check the
+        // return value.
+        let ret = unsafe {
+            libc::read(
+                fd,
+                &mut expirations as *mut u64 as *mut libc::c_void,
+                std::mem::size_of::<u64>(),
+            )
+        };
+        if ret >= 0 {
+            return Ok(expirations);
+        }
+        let e = Error::last();
+        match e.errno() {
+            EINTR => {}
+            // The timer was made nonblocking by its owner, and reset since the check.
+            libc::EAGAIN => return Ok(0),
+            _ => return Err(e),
+        }
+    }
+}
+
+// Polls `pfds`, until `timeout` elapses if it is not `None`. A poll interrupted by a signal is
+// resumed without extending the timeout. Returns the number of `pfds` with events, which is
+// zero on timeout.
+pub(crate) fn poll_fds(pfds: &mut [pollfd], timeout: Option<Duration>) -> Result<usize> {
+    let deadline = timeout.map(Deadline::new);
+    let mut timeout_millis = timeout.map_or(-1, duration_to_millis);
+    loop {
+        // Safe because we give a valid array of `pfds.len()` pollfds and check the return value.
+        let ret = unsafe {
+            poll(
+                pfds.as_mut_ptr(),
+                pfds.len() as libc::nfds_t,
+                timeout_millis,
+            )
+        };
+        if ret >= 0 {
+            return Ok(ret as usize);
+        }
+        if Error::last() != Error::new(EINTR) {
+            return errno_result();
+        }
+        if let Some(deadline) = deadline {
+            timeout_millis = remaining_millis(&deadline);
+        }
+    }
+}
+
+// Returns the events reported for `fd`, which are empty on timeout. Fails with `EBADF` if `fd`
+// is not an open file descriptor.
+fn wait_fd(fd: &dyn AsRawFd, events: c_short, timeout: Option<Duration>) -> Result<c_short> {
+    let mut pfd = pollfd {
+        fd: fd.as_raw_fd(),
+        events,
+        revents: 0,
+    };
+    poll_fds(slice::from_mut(&mut pfd), timeout)?;
+    if pfd.revents & POLLNVAL != 0 {
+        return Err(Error::new(EBADF));
+    }
+    Ok(pfd.revents)
+}
+
+/// Wait until `fd` is readable, or until `timeout` elapses if it is not `None`.
+///
+/// Returns `true` if `fd` became readable before the timeout, and `false` otherwise.
+/// This is a single [`poll`](http://man7.org/linux/man-pages/man2/poll.2.html) call,
+/// meant for one-off waits where creating a [`PollContext`](struct.PollContext.html)
+/// would be overkill. A hung up or errored `fd` is reported as readable, because
+/// reading from it won't block. A wait interrupted by a signal is resumed without
+/// extending the timeout.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to wait for.
+/// * `timeout`: the maximum time to wait, rounded down to milliseconds. If `None`, wait
+///   for as long as it takes.
+///
+/// # Errors
+///
+/// Fails with `EBADF` if `fd` is not an open file descriptor.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::time::Duration;
+/// use vmm_sys_util::eventfd::EventFd;
+/// use vmm_sys_util::poll::wait_readable;
+///
+/// let evt = EventFd::new(0).unwrap();
+/// assert!(!wait_readable(&evt, Some(Duration::from_millis(1))).unwrap());
+/// evt.write(1).unwrap();
+/// assert!(wait_readable(&evt, None).unwrap());
+/// ```
+pub fn wait_readable(fd: &dyn AsRawFd, timeout: Option<Duration>) -> Result<bool> {
+    Ok(wait_fd(fd, POLLIN, timeout)? != 0)
+}
+
+/// Wait until `fd` is writable, or until `timeout` elapses if it is not `None`.
+///
+/// Returns `true` if `fd` became writable before the timeout, and `false` otherwise.
+/// As for [`wait_readable`](fn.wait_readable.html), a hung up or errored `fd` is
+/// reported as ready.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to wait for.
+/// * `timeout`: the maximum time to wait, rounded down to milliseconds. If `None`, wait
+///   for as long as it takes.
+///
+/// # Errors
+///
+/// Fails with `EBADF` if `fd` is not an open file descriptor.
+pub fn wait_writable(fd: &dyn AsRawFd, timeout: Option<Duration>) -> Result<bool> {
+    Ok(wait_fd(fd, POLLOUT, timeout)? != 0)
+}
+
+/// Wait until `fd` is hung up, or until `timeout` elapses if it is not `None`.
+///
+/// Returns `true` if `fd` was hung up before the timeout (e.g. the peer of a socket was
+/// closed), and `false` otherwise. Unlike [`wait_readable`](fn.wait_readable.html), data
+/// becoming available on `fd` doesn't end the wait, so there is no need to tell a zero
+/// length read apart from a regular one. An errored `fd` (e.g. the write end of a pipe
+/// whose read end was closed) is reported as hung up too.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to wait for.
+/// * `timeout`: the maximum time to wait, rounded down to milliseconds. If `None`, wait
+///   for as long as it takes.
+///
+/// # Errors
+///
+/// Fails with `EBADF` if `fd` is not an open file descriptor.
+pub fn wait_for_hangup(fd: &dyn AsRawFd, timeout: Option<Duration>) -> Result<bool> {
+    // POLLHUP and POLLERR are always reported, without being requested.
+    let revents = wait_fd(fd, 0, timeout)?;
+    Ok(revents & (POLLHUP | POLLERR) != 0)
+}
+
+/// Runs `handler` for an edge-triggered readable event of `fd`, checking that it drains `fd`
+/// in debug builds.
+///
+/// In debug builds, a warning pointing at the caller is written to stderr when `fd` is still
+/// readable after `handler` returns, since no event is reported for the data left unread when
+/// `fd` was added with
+/// [`EpollContext::add_edge_triggered`](struct.EpollContext.html#method.add_edge_triggered).
+/// In release builds, this only calls `handler`.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor that the event was reported for.
+/// * `handler`: the handler of the event, which should drain `fd`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::eventfd::{EventFd, EFD_NONBLOCK};
+/// use vmm_sys_util::poll::handle_edge_triggered;
+///
+/// let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+/// evt.write(2).unwrap();
+/// let count = handle_edge_triggered(&evt, || evt.read().unwrap());
+/// assert_eq!(count, 2);
+/// ```
+#[track_caller]
+pub fn handle_edge_triggered<F, R>(fd: &dyn AsRawFd, handler: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    handle_edge_triggered_to(fd, handler, &mut stderr())
+}
+
+// Same as `handle_edge_triggered`, writing the warning to `out`.
+#[track_caller]
+fn handle_edge_triggered_to<F, R>(fd: &dyn AsRawFd, handler: F, out: &mut dyn Write) -> R
+where
+    F: FnOnce() -> R,
+{
+    let res = handler();
+    if cfg!(debug_assertions) && wait_readable(fd, Some(Duration::from_millis(0))).unwrap_or(false)
+    {
+        let caller = Location::caller();
+        let _ = writeln!(
+            out,
+            "[{}:{}] edge-triggered fd {} is still readable after its event was handled",
+            caller.file(),
+            caller.line(),
+            fd.as_raw_fd()
+        );
+    }
+    res
+}
+
+/// Wakes up a thread waiting on a [`PollContext`](struct.PollContext.html) from another thread.
+///
+/// A `Waker` is an [`EventFd`](../eventfd/struct.EventFd.html) added to the context with its own
+/// token, which [`wake`](struct.Waker.html#method.wake) makes readable so that the wait returns
+/// that token, e.g. for letting the waiting thread update its list of file descriptors. The
+/// wake-ups are level triggered: they keep being reported until they are consumed with
+/// [`reset`](struct.Waker.html#method.reset).
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use std::thread;
+/// use vmm_sys_util::poll::{PollContext, Waker};
+///
+/// let ctx: PollContext<u32> = PollContext::new().unwrap();
+/// let waker = Waker::new(&ctx, 0).unwrap();
+///
+/// let handle = thread::spawn(move || {
+///     let tokens: Vec<u32> = ctx.wait().unwrap().iter().map(|e| e.token()).collect();
+///     tokens
+/// });
+/// waker.wake().unwrap();
+/// assert_eq!(handle.join().unwrap(), [0]);
+/// waker.reset().unwrap();
+/// ```
+pub struct Waker {
+    evt: EventFd,
+}
+
+impl Waker {
+    /// Creates a new `Waker` and adds it to `ctx`, with the given `token`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx`: the context to be woken up.
+    /// * `token`: the token returned by the wait of `ctx` when it is woken up.
+    pub fn new<T: PollToken>(ctx: &PollContext<T>, token: T) -> Result<Waker> {
+        let evt = EventFd::new(EFD_NONBLOCK).map_err(Error::from)?;
+        ctx.add(&evt, token)?;
+        Ok(Waker { evt })
+    }
+
+    /// Wakes up the waits of the context, until [`reset`](struct.Waker.html#method.reset) is
+    /// called.
+    ///
+    /// Waking up a context that was already woken up doesn't do anything.
+    pub fn wake(&self) -> Result<()> {
+        // If the counter can't be incremented, it is at its maximum and a wake-up is already
+        // pending.
+        self.evt.try_write(1).map(|_| ()).map_err(Error::from)
+    }
+
+    /// Consumes the pending wake-ups, so that they stop being reported by the waits.
+    ///
+    /// This should be called by the woken up thread when the wait returns the token of this
+    /// `Waker`.
+    pub fn reset(&self) -> Result<()> {
+        match self.evt.read() {
+            Ok(_) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+}
+
+impl AsRawFd for Waker {
+    fn as_raw_fd(&self) -> RawFd {
+        self.evt.as_raw_fd()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::eventfd::EventFd;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
     use std::os::unix::net::UnixStream;
-    use std::time::Instant;
+    use std::sync::mpsc;
+
+    use libc::{c_void, siginfo_t};
+
+    use crate::signal::{register_signal_handler, Killable, SignalFlag, SIGRTMIN};
+
+    #[test]
+    fn test_waker() {
+        let evt = EventFd::new(0).unwrap();
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        ctx.add(&evt, 1).unwrap();
+        let waker = Waker::new(&ctx, 2).unwrap();
+        assert_eq!(ctx.len(), 2);
+
+        // Nothing is reported until the context is woken up.
+        assert_eq!(
+            ctx.wait_timeout(Duration::from_millis(0))
+                .unwrap()
+                .iter()
+                .count(),
+            0
+        );
+        waker.wake().unwrap();
+        waker.wake().unwrap();
+        let tokens: Vec<u32> = ctx.wait().unwrap().iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, [2]);
+        waker.reset().unwrap();
+        waker.reset().unwrap();
+        assert_eq!(
+            ctx.wait_timeout(Duration::from_millis(0))
+                .unwrap()
+                .iter()
+                .count(),
+            0
+        );
+
+        // Wake up a thread blocked in `wait`.
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let tokens: Vec<u32> = ctx.wait().unwrap().iter().map(|e| e.token()).collect();
+            (tokens, start.elapsed())
+        });
+        thread::sleep(Duration::from_millis(50));
+        waker.wake().unwrap();
+        let (tokens, elapsed) = handle.join().unwrap();
+        assert_eq!(tokens, [2]);
+        assert!(elapsed >= Duration::from_millis(40));
+    }
 
     #[test]
     fn test_poll_context() {
@@ -899,6 +1863,386 @@ mod tests {
         assert_eq!(evt_count, 2);
     }
 
+    #[test]
+    fn test_epoll_context_ctl() {
+        let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+        let events = EpollEvents::new();
+        let timeout = Duration::from_millis(10);
+        let evt = EventFd::new(0).unwrap();
+        evt.write(1).unwrap();
+
+        let read = WatchingEvents::empty().set_read();
+        ctx.ctl(ControlOperation::Add, &evt, WatchingEvents::empty(), 1)
+            .unwrap();
+        assert_eq!(
+            ctx.ctl(ControlOperation::Add, &evt, WatchingEvents::empty(), 1)
+                .unwrap_err(),
+            Error::new(libc::EEXIST)
+        );
+        assert_eq!(
+            ctx.wait_timeout(&events, timeout).unwrap().iter().count(),
+            0
+        );
+
+        ctx.ctl(ControlOperation::Modify, &evt, read, 2).unwrap();
+        {
+            let ready = ctx.wait_timeout(&events, timeout).unwrap();
+            let tokens: Vec<u32> = ready.iter_readable().map(|e| e.token()).collect();
+            assert_eq!(tokens, [2]);
+        }
+
+        ctx.ctl(ControlOperation::Delete, &evt, WatchingEvents::empty(), 2)
+            .unwrap();
+        assert_eq!(
+            ctx.wait_timeout(&events, timeout).unwrap().iter().count(),
+            0
+        );
+        assert_eq!(
+            ctx.ctl(ControlOperation::Delete, &evt, WatchingEvents::empty(), 2)
+                .unwrap_err(),
+            Error::new(libc::ENOENT)
+        );
+        assert_eq!(
+            ctx.ctl(
+                ControlOperation::Modify,
+                &evt,
+                WatchingEvents::empty().set_read(),
+                2
+            )
+            .unwrap_err(),
+            Error::new(libc::ENOENT)
+        );
+    }
+
+    #[test]
+    fn test_as_fd() {
+        let epoll_ctx: EpollContext<u32> = EpollContext::new().unwrap();
+        assert_eq!(epoll_ctx.as_fd().as_raw_fd(), epoll_ctx.as_raw_fd());
+
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        assert_eq!(ctx.as_fd().as_raw_fd(), ctx.as_raw_fd());
+    }
+
+    #[test]
+    fn test_deadline() {
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        let evt = EventFd::new(0).unwrap();
+        ctx.add(&evt, 1).unwrap();
+
+        let timeout = Duration::from_millis(100);
+        let start = Instant::now();
+        let deadline = Deadline::new(timeout);
+        assert!(!deadline.is_expired());
+        assert!(deadline.remaining().unwrap() <= timeout);
+
+        // Each wait returns early, as if interrupted, but the loop still ends at the deadline.
+        let mut waits = 0;
+        while let Some(remaining) = deadline.remaining() {
+            assert!(remaining <= timeout);
+            let events = ctx
+                .wait_timeout(min(remaining, Duration::from_millis(10)))
+                .unwrap();
+            assert_eq!(events.iter().count(), 0);
+            waits += 1;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= timeout);
+        assert!(elapsed < timeout * 3, "elapsed = {:?}", elapsed);
+        assert!(waits > 1);
+        assert!(deadline.is_expired());
+
+        assert!(Deadline::new(Duration::from_secs(0)).is_expired());
+    }
+
+    #[test]
+    fn test_wait_readable() {
+        let evt = EventFd::new(0).unwrap();
+        let start = Instant::now();
+        assert!(!wait_readable(&evt, Some(Duration::from_millis(20))).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(!wait_readable(&evt, Some(Duration::from_millis(0))).unwrap());
+
+        evt.write(1).unwrap();
+        assert!(wait_readable(&evt, Some(Duration::from_millis(20))).unwrap());
+        assert!(wait_readable(&evt, None).unwrap());
+    }
+
+    #[test]
+    fn test_wait_readable_interrupted() {
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+
+        let signal = SIGRTMIN() + 16;
+        register_signal_handler(signal, handle_signal).unwrap();
+
+        // Interrupting the wait several times doesn't push back its end.
+        let timeout = Duration::from_millis(200);
+        let waiter = thread::spawn(move || {
+            let evt = EventFd::new(0).unwrap();
+            let start = Instant::now();
+            let readable = wait_readable(&evt, Some(timeout)).unwrap();
+            (readable, start.elapsed())
+        });
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(40));
+            waiter.kill(signal).unwrap();
+        }
+        let (readable, elapsed) = waiter.join().unwrap();
+        assert!(!readable);
+        assert!(elapsed >= timeout, "elapsed = {:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(80),
+            "elapsed = {:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_wait_writable() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        assert!(wait_writable(&evt, Some(Duration::from_millis(20))).unwrap());
+
+        // The counter can't be incremented any further, so it's not writable anymore.
+        evt.write(u64::MAX - 1).unwrap();
+        assert!(!wait_writable(&evt, Some(Duration::from_millis(20))).unwrap());
+    }
+
+    #[test]
+    fn test_wait_closed_fd() {
+        // Duplicate an eventfd to a high number that won't be reused by the other tests, and
+        // close it.
+        let evt = EventFd::new(0).unwrap();
+        let fd = unsafe { libc::fcntl(evt.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 512) };
+        assert!(fd >= 512);
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+
+        let timeout = Some(Duration::from_millis(20));
+        assert_eq!(wait_readable(&fd, timeout).unwrap_err().errno(), EBADF);
+        assert_eq!(wait_writable(&fd, timeout).unwrap_err().errno(), EBADF);
+        assert_eq!(wait_for_hangup(&fd, timeout).unwrap_err().errno(), EBADF);
+    }
+
+    #[test]
+    fn test_wait_for_hangup() {
+        let (mut s1, s2) = UnixStream::pair().unwrap();
+        assert!(!wait_for_hangup(&s1, Some(Duration::from_millis(20))).unwrap());
+
+        // Readable data doesn't end the wait.
+        let start = Instant::now();
+        (&s2).write_all(b"data").unwrap();
+        assert!(!wait_for_hangup(&s1, Some(Duration::from_millis(20))).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let closer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(s2);
+        });
+        assert!(wait_for_hangup(&s1, None).unwrap());
+        closer.join().unwrap();
+
+        // The data sent before the hangup can still be read.
+        let mut buf = [0u8; 4];
+        s1.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"data");
+    }
+
+    #[test]
+    fn test_epoll_context_hangup_error() {
+        let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+        let events = EpollEvents::new();
+
+        let (s1, s2) = UnixStream::pair().unwrap();
+        ctx.add(&s1, 1).unwrap();
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let read_end = unsafe { File::from_raw_fd(fds[0]) };
+        let write_end = unsafe { File::from_raw_fd(fds[1]) };
+        ctx.add_fd_with_events(&write_end, WatchingEvents::empty(), 2)
+            .unwrap();
+
+        let timeout = Duration::from_millis(10);
+        assert_eq!(
+            ctx.wait_timeout(&events, timeout).unwrap().iter().count(),
+            0
+        );
+
+        drop(s2);
+        drop(read_end);
+        {
+            let ready = ctx.wait_timeout(&events, timeout).unwrap();
+            let hungup: Vec<u32> = ready.iter_hungup().map(|e| e.token()).collect();
+            assert_eq!(hungup, [1]);
+            let errored: Vec<u32> = ready.iter_error().map(|e| e.token()).collect();
+            assert_eq!(errored, [2]);
+            for event in ready.iter() {
+                assert_eq!(event.hungup(), event.token() == 1);
+                assert_eq!(event.error(), event.token() == 2);
+            }
+        }
+
+        // Once removed, the fds don't wake up the context anymore.
+        ctx.delete(&s1).unwrap();
+        ctx.delete(&write_end).unwrap();
+        assert_eq!(
+            ctx.wait_timeout(&events, timeout).unwrap().iter().count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_poll_context_readiness() {
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        let (s1, mut s2) = UnixStream::pair().unwrap();
+        let read_write = WatchingEvents::empty().set_read().set_write();
+        let read_write_raw = read_write.get_raw();
+        ctx.add_fd_with_events(&s1, read_write, 1).unwrap();
+
+        {
+            let events = ctx.wait_timeout(Duration::from_millis(10)).unwrap();
+            let event = events.iter().next().unwrap();
+            assert_eq!(event.token(), 1);
+            assert!(event.writable());
+            assert!(!event.readable());
+            assert_eq!(event.readiness().get_raw(), EPOLLOUT as u32);
+        }
+
+        s2.write_all(&[1]).unwrap();
+        let events = ctx.wait_timeout(Duration::from_millis(10)).unwrap();
+        let event = events.iter().next().unwrap();
+        assert!(event.writable());
+        assert!(event.readable());
+        assert!(!event.hungup());
+        assert_eq!(event.readiness().get_raw(), read_write_raw);
+    }
+
+    #[test]
+    fn test_poll_context_registered() {
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        assert!(ctx.is_empty());
+
+        let evts: Vec<EventFd> = (0..3).map(|_| EventFd::new(0).unwrap()).collect();
+        ctx.add(&evts[0], 10).unwrap();
+        ctx.add_fd_with_events(&evts[1], WatchingEvents::empty().set_write(), 11)
+            .unwrap();
+        ctx.add(&evts[2], 12).unwrap();
+        assert_eq!(ctx.len(), 3);
+        // Failed operations don't change the list.
+        assert!(ctx.add(&evts[2], 13).is_err());
+        assert_eq!(ctx.len(), 3);
+
+        ctx.delete(&evts[0]).unwrap();
+        ctx.modify(
+            &evts[2],
+            WatchingEvents::empty().set_read().set_priority(),
+            14,
+        )
+        .unwrap();
+        assert_eq!(ctx.len(), 2);
+
+        let mut expected = vec![
+            (
+                evts[1].as_raw_fd(),
+                WatchingEvents::empty().set_write().get_raw(),
+                11,
+            ),
+            (
+                evts[2].as_raw_fd(),
+                WatchingEvents::empty().set_read().set_priority().get_raw(),
+                14,
+            ),
+        ];
+        expected.sort();
+        let registered: Vec<(RawFd, u32, u64)> = ctx
+            .registered()
+            .map(|(fd, events, token)| (fd, events.get_raw(), token))
+            .collect();
+        assert_eq!(registered, expected);
+    }
+
+    #[test]
+    fn test_poll_context_build_with() {
+        let evts: Vec<EventFd> = (0..3).map(|_| EventFd::new(0).unwrap()).collect();
+        let read = || WatchingEvents::empty().set_read();
+        let items = vec![
+            (evts[0].as_raw_fd(), read(), 10),
+            (evts[1].as_raw_fd(), WatchingEvents::empty().set_write(), 11),
+            (evts[2].as_raw_fd(), read(), 12),
+        ];
+        let mut expected: Vec<(RawFd, u32, u64)> = items
+            .iter()
+            .map(|(fd, events, token)| (*fd, events.get_raw(), u64::from(*token)))
+            .collect();
+        expected.sort();
+
+        let ctx: PollContext<u32> = PollContext::build_with(items).unwrap();
+        let registered: Vec<(RawFd, u32, u64)> = ctx
+            .registered()
+            .map(|(fd, events, token)| (fd, events.get_raw(), token))
+            .collect();
+        assert_eq!(registered, expected);
+
+        evts[2].write(1).unwrap();
+        let tokens: Vec<u32> = ctx
+            .wait()
+            .unwrap()
+            .iter_readable()
+            .map(|e| e.token())
+            .collect();
+        assert_eq!(tokens, vec![12]);
+
+        // The first failed registration is returned.
+        let items = vec![
+            (evts[0].as_raw_fd(), read(), 1),
+            (evts[0].as_raw_fd(), read(), 2),
+        ];
+        let err = PollContext::<u32>::build_with(items).err().unwrap();
+        assert_eq!(err.errno(), libc::EEXIST);
+        assert_eq!(
+            PollContext::<u32>::build_with(vec![(-1, read(), 1)])
+                .err()
+                .unwrap()
+                .errno(),
+            libc::EBADF
+        );
+    }
+
+    #[test]
+    fn test_poll_context_duplicate() {
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        let evt = EventFd::new(0).unwrap();
+        let registration = |ctx: &PollContext<u32>| {
+            ctx.registered()
+                .map(|(fd, events, token)| (fd, events.get_raw(), token))
+                .collect::<Vec<_>>()
+        };
+
+        ctx.add(&evt, 1).unwrap();
+        // Adding the same fd again fails and keeps the first registration.
+        assert_eq!(
+            ctx.add_fd_with_events(&evt, WatchingEvents::empty().set_write(), 2)
+                .unwrap_err()
+                .errno(),
+            libc::EEXIST
+        );
+        let read_raw = WatchingEvents::empty().set_read().get_raw();
+        assert_eq!(registration(&ctx), vec![(evt.as_raw_fd(), read_raw, 1)]);
+
+        // Replacing updates the existing registration.
+        let write_raw = WatchingEvents::empty().set_write().get_raw();
+        ctx.replace(&evt, WatchingEvents::empty().set_write(), 2)
+            .unwrap();
+        assert_eq!(registration(&ctx), vec![(evt.as_raw_fd(), write_raw, 2)]);
+        let events = ctx.wait_timeout(Duration::from_millis(0)).unwrap();
+        let tokens: Vec<u32> = events.iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, vec![2]);
+
+        // Replacing adds a fd that isn't in the context yet.
+        let evt2 = EventFd::new(0).unwrap();
+        ctx.replace(&evt2, WatchingEvents::empty().set_read(), 3)
+            .unwrap();
+        assert_eq!(ctx.len(), 2);
+    }
+
     #[test]
     fn test_poll_context_overflow() {
         const EVT_COUNT: usize = POLL_CONTEXT_MAX_EVENTS * 2 + 1;
@@ -936,6 +2280,213 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_wait_handling_errors() {
+        let (s1, s2) = UnixStream::pair().unwrap();
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        ctx.add(&s1, 1).unwrap();
+        ctx.add(&evt, 2).unwrap();
+
+        // A healthy fd sharing the token of the hungup one.
+        let (s3, _s4) = UnixStream::pair().unwrap();
+        ctx.add(&s3, 1).unwrap();
+
+        evt.write(1).unwrap();
+        drop(s2);
+        {
+            let (events, errored) = ctx.wait_handling_errors().unwrap();
+            let hungup: Vec<u32> = events.iter_hungup().map(|e| e.token()).collect();
+            assert_eq!(hungup, [1]);
+            assert_eq!(events.iter_readable().count(), 2);
+            assert_eq!(errored, [(s1.as_raw_fd(), Ok(()))]);
+        }
+        let mut registered: Vec<RawFd> = ctx.registered().map(|(fd, _, _)| fd).collect();
+        let mut expected = vec![evt.as_raw_fd(), s3.as_raw_fd()];
+        registered.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(registered, expected);
+        ctx.delete(&s3).unwrap();
+
+        // The hungup fd was removed, so it doesn't wake up the context anymore.
+        evt.read().unwrap();
+        assert_eq!(
+            ctx.wait_timeout(Duration::from_millis(10))
+                .unwrap()
+                .iter()
+                .count(),
+            0
+        );
+
+        // An fd closed after being added is still watched through a duplicate of it, until the
+        // duplicate is closed. It can't be deleted anymore, which doesn't prevent deleting the
+        // other errored fds.
+        let (s5, s6) = UnixStream::pair().unwrap();
+        let (s7, s8) = UnixStream::pair().unwrap();
+        let fd = unsafe { libc::fcntl(s5.as_raw_fd(), libc::F_DUPFD_CLOEXEC, 540) };
+        assert!(fd >= 540);
+        ctx.add(&fd, 3).unwrap();
+        ctx.add(&s7, 4).unwrap();
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+        drop(s6);
+        drop(s8);
+        let (_, mut errored) = ctx.wait_handling_errors().unwrap();
+        errored.sort_unstable_by_key(|(fd, _)| *fd);
+        assert_eq!(
+            errored,
+            [(s7.as_raw_fd(), Ok(())), (fd, Err(Error::new(EBADF)))]
+        );
+        drop(s5);
+    }
+
+    #[test]
+    fn test_add_eventfd() {
+        let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+        let events = EpollEvents::new();
+        let evt1 = ctx.add_eventfd(EFD_NONBLOCK, 1).unwrap();
+        let evt2 = ctx.add_eventfd(0, 2).unwrap();
+
+        evt2.write(1).unwrap();
+        let tokens: Vec<u32> = ctx
+            .wait(&events)
+            .unwrap()
+            .iter_readable()
+            .map(|e| e.token())
+            .collect();
+        assert_eq!(tokens, [2]);
+        assert!(evt1.read().is_err());
+
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        let evt = ctx.add_eventfd(EFD_NONBLOCK, 3).unwrap();
+        assert_eq!(ctx.len(), 1);
+        evt.write(1).unwrap();
+        let tokens: Vec<u32> = ctx
+            .wait()
+            .unwrap()
+            .iter_readable()
+            .map(|e| e.token())
+            .collect();
+        assert_eq!(tokens, [3]);
+    }
+
+    #[test]
+    fn test_add_timerfd() {
+        let mut tfd = TimerFd::new().unwrap();
+        let evt = EventFd::new(0).unwrap();
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        ctx.add_timerfd(&tfd, 1).unwrap();
+        ctx.add(&evt, 2).unwrap();
+
+        let interval = Duration::from_millis(20);
+        tfd.reset(interval, Some(interval)).unwrap();
+        let tokens: Vec<u32> = ctx.wait().unwrap().iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, [1]);
+        assert_eq!(ctx.expirations(1).unwrap(), 1);
+        assert_eq!(ctx.expirations(2).unwrap(), 0);
+        // The expirations were read, so the timer isn't readable until the next one.
+        assert!(!wait_readable(&tfd, Some(Duration::from_millis(0))).unwrap());
+
+        // The missed expirations are counted.
+        thread::sleep(interval * 3 + interval / 2);
+        assert_eq!(ctx.wait().unwrap().iter().count(), 1);
+        let expirations = ctx.expirations(1).unwrap();
+        assert!(expirations >= 3, "expirations = {}", expirations);
+
+        // A wait that doesn't report the timer doesn't count expirations for it.
+        evt.write(1).unwrap();
+        tfd.reset(Duration::from_secs(10), None).unwrap();
+        let tokens: Vec<u32> = ctx.wait().unwrap().iter().map(|e| e.token()).collect();
+        assert_eq!(tokens, [2]);
+        assert_eq!(ctx.expirations(1).unwrap(), 0);
+
+        // Once deleted, the timer is left to the caller to read.
+        ctx.delete(&tfd).unwrap();
+        ctx.add(&tfd, 1).unwrap();
+        evt.read().unwrap();
+        tfd.reset(Duration::from_millis(1), None).unwrap();
+        assert_eq!(ctx.wait().unwrap().iter().count(), 1);
+        assert_eq!(ctx.expirations(1).unwrap(), 0);
+        assert_eq!(tfd.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_read_timer_expirations() {
+        let mut tfd = TimerFd::new().unwrap();
+        tfd.reset(Duration::from_millis(1), None).unwrap();
+        assert!(wait_readable(&tfd, None).unwrap());
+        assert_eq!(read_timer_expirations(tfd.as_raw_fd()).unwrap(), 1);
+
+        // A timer disarmed after it was reported readable doesn't block the read.
+        tfd.reset(Duration::from_millis(1), None).unwrap();
+        assert!(wait_readable(&tfd, None).unwrap());
+        tfd.clear().unwrap();
+        assert_eq!(read_timer_expirations(tfd.as_raw_fd()).unwrap(), 0);
+
+        // Directories are always readable, but reading them fails.
+        let dir = File::open("/").unwrap();
+        assert_eq!(
+            read_timer_expirations(dir.as_raw_fd()).unwrap_err().errno(),
+            libc::EISDIR
+        );
+    }
+
+    #[test]
+    fn test_edge_triggered() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        let ctx: EpollContext<u32> = EpollContext::new().unwrap();
+        let events = EpollEvents::new();
+        ctx.add_edge_triggered(&evt, 1).unwrap();
+        let count = |ctx: &EpollContext<u32>| {
+            ctx.wait_timeout(&events, Duration::from_millis(0))
+                .unwrap()
+                .iter_readable()
+                .count()
+        };
+
+        // Both writes are reported by a single event.
+        evt.write(1).unwrap();
+        evt.write(2).unwrap();
+        assert_eq!(count(&ctx), 1);
+        // The event isn't reported again, even though `evt` wasn't read.
+        assert_eq!(count(&ctx), 0);
+        assert!(wait_readable(&evt, Some(Duration::from_millis(0))).unwrap());
+
+        // Once drained, the next write is reported.
+        assert_eq!(handle_edge_triggered(&evt, || evt.read().unwrap()), 3);
+        assert_eq!(count(&ctx), 0);
+        evt.write(4).unwrap();
+        assert_eq!(count(&ctx), 1);
+        assert_eq!(count(&ctx), 0);
+        assert_eq!(handle_edge_triggered(&evt, || evt.read().unwrap()), 4);
+    }
+
+    #[test]
+    fn test_handle_edge_triggered_warning() {
+        let evt = EventFd::new(EFD_NONBLOCK).unwrap();
+        let mut out = Vec::new();
+
+        // A handler draining the fd doesn't trigger the warning.
+        evt.write(1).unwrap();
+        handle_edge_triggered_to(&evt, || evt.read().unwrap(), &mut out);
+        assert!(out.is_empty());
+
+        // A handler leaving the fd readable triggers it, pointing at the caller.
+        evt.write(1).unwrap();
+        let line = line!() + 1;
+        handle_edge_triggered_to(&evt, || (), &mut out);
+        let warning = String::from_utf8(out).unwrap();
+        if cfg!(debug_assertions) {
+            assert!(
+                warning.starts_with(&format!("[{}:{}] ", file!(), line)),
+                "warning = {:?}",
+                warning
+            );
+            assert!(warning.contains(&format!("fd {} is still readable", evt.as_raw_fd())));
+        } else {
+            assert!(warning.is_empty());
+        }
+    }
+
     #[test]
     fn test_poll_context_timeout() {
         let ctx: PollContext<u32> = PollContext::new().unwrap();
@@ -944,4 +2495,114 @@ mod tests {
         ctx.wait_timeout(dur).unwrap();
         assert!(start_inst.elapsed() >= dur);
     }
+
+    #[test]
+    fn test_poll_context_timeout_interrupted() {
+        static INTERRUPTED: SignalFlag = SignalFlag::new();
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            INTERRUPTED.set();
+        }
+
+        let signal = SIGRTMIN() + 15;
+        register_signal_handler(signal, handle_signal).unwrap();
+
+        // Interrupting the wait several times doesn't push back its end.
+        let timeout = Duration::from_millis(200);
+        let waiter = thread::spawn(move || {
+            let ctx: PollContext<u32> = PollContext::new().unwrap();
+            let start = Instant::now();
+            let count = ctx.wait_timeout(timeout).unwrap().iter().count();
+            (count, start.elapsed())
+        });
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(40));
+            waiter.kill(signal).unwrap();
+        }
+        let (count, elapsed) = waiter.join().unwrap();
+        assert!(INTERRUPTED.is_set());
+        assert_eq!(count, 0);
+        assert!(elapsed >= timeout, "elapsed = {:?}", elapsed);
+        assert!(
+            elapsed < timeout + Duration::from_millis(80),
+            "elapsed = {:?}",
+            elapsed
+        );
+
+        // The interruptible wait reports the interruption instead.
+        let (sender, receiver) = mpsc::channel();
+        let waiter = thread::spawn(move || {
+            let ctx: PollContext<u32> = PollContext::new().unwrap();
+            let res = ctx
+                .wait_timeout_interruptible(Duration::from_secs(10))
+                .map(|events| events.iter().count());
+            sender.send(res).unwrap();
+        });
+        let res = loop {
+            waiter.kill(signal).unwrap();
+            if let Ok(res) = receiver.recv_timeout(Duration::from_millis(20)) {
+                break res;
+            }
+        };
+        waiter.join().unwrap();
+        assert_eq!(res.unwrap_err(), Error::new(EINTR));
+    }
+
+    #[test]
+    fn test_poll_context_priority() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut receiver, _) = listener.accept().unwrap();
+
+        let ctx: PollContext<u32> = PollContext::new().unwrap();
+        ctx.add_fd_with_events(
+            &receiver,
+            WatchingEvents::empty().set_read().set_priority(),
+            1,
+        )
+        .unwrap();
+
+        // Out-of-band data makes the socket report a priority event.
+        let oob = [0x55u8];
+        let ret = unsafe {
+            libc::send(
+                sender.as_raw_fd(),
+                oob.as_ptr() as *const libc::c_void,
+                oob.len(),
+                libc::MSG_OOB,
+            )
+        };
+        assert_eq!(ret, 1);
+
+        let events = ctx.wait_timeout(Duration::from_secs(5)).unwrap();
+        let tokens: Vec<u32> = events.iter_priority().map(|e| e.token()).collect();
+        assert_eq!(&tokens[..], &[1]);
+        drop(events);
+
+        // Regular data is reported as readable, but not as priority data.
+        let mut oob_byte = [0u8];
+        let ret = unsafe {
+            libc::recv(
+                receiver.as_raw_fd(),
+                oob_byte.as_mut_ptr() as *mut libc::c_void,
+                oob_byte.len(),
+                libc::MSG_OOB,
+            )
+        };
+        assert_eq!(ret, 1);
+        assert_eq!(oob_byte[0], 0x55);
+
+        (&sender).write_all(b"data").unwrap();
+        let mut data = [0u8; 4];
+        loop {
+            let events = ctx.wait_timeout(Duration::from_secs(5)).unwrap();
+            let event = events.iter().next().unwrap();
+            assert_eq!(event.token(), 1);
+            assert!(!event.priority());
+            if event.readable() {
+                break;
+            }
+        }
+        receiver.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"data");
+    }
 }
@@ -9,8 +9,11 @@
 //! unique ID's that will not be shared over the network.
 
 extern crate libc;
+use std::cmp::min;
+use std::collections::BTreeSet;
 use std::ffi::OsString;
 use std::str;
+use std::time::Duration;
 
 /// Gets an ever increasing u64 (at least for this process).
 ///
@@ -87,6 +90,359 @@ pub fn rand_alphanumerics(len: usize) -> OsString {
     rand_alphanumerics_impl(&xor_psuedo_rng_u32, len)
 }
 
+/// A seedable pseudo random number generator.
+///
+/// This implements the
+/// [`xorshift128+`](https://en.wikipedia.org/wiki/Xorshift#xorshift+) algorithm, so for a
+/// given seed it generates the same sequence of numbers on every platform. This makes it
+/// suitable for reproducible randomized tests, but not for anything security sensitive.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::rand::Xorshift;
+///
+/// let mut rng = Xorshift::new(42);
+/// let n = rng.rand_range(10, 20);
+/// assert!((10..20).contains(&n));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Xorshift {
+    state: [u64; 2],
+}
+
+impl Xorshift {
+    /// Create a new generator from `seed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed`: the seed from which the internal state is derived. Generators created
+    ///   from the same seed produce the same sequence of numbers.
+    pub fn new(seed: u64) -> Self {
+        // Expand the seed with splitmix64, so that similar seeds give unrelated states.
+        let mut x = seed;
+        let mut splitmix64 = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        let mut state = [splitmix64(), splitmix64()];
+        // The all zero state is the only one that xorshift can't get out of.
+        if state == [0, 0] {
+            state[0] = 1;
+        }
+        Xorshift { state }
+    }
+
+    /// Generate a pseudo random u64.
+    pub fn next_u64(&mut self) -> u64 {
+        let mut s1 = self.state[0];
+        let s0 = self.state[1];
+        let result = s0.wrapping_add(s1);
+        self.state[0] = s0;
+        s1 ^= s1 << 23;
+        self.state[1] = s1 ^ s0 ^ (s1 >> 18) ^ (s0 >> 5);
+        result
+    }
+
+    /// Advance the generator by 2^64 steps.
+    ///
+    /// This is equivalent to calling [`next_u64`](#method.next_u64) 2^64 times, but it is
+    /// computed with the jump polynomial of the generator in 128 steps. Cloning a generator
+    /// and jumping the clone gives independent streams for parallel workers: the streams
+    /// don't overlap for 2^64 numbers, and the full period of the generator allows for
+    /// 2^64 such streams.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let mut rng = Xorshift::new(42);
+    /// let mut workers = Vec::new();
+    /// for _ in 0..4 {
+    ///     workers.push(rng.clone());
+    ///     rng.jump();
+    /// }
+    /// ```
+    pub fn jump(&mut self) {
+        // Coefficients of x^(2^64) modulo the characteristic polynomial of the generator.
+        const JUMP: [u64; 2] = [0x8a5c_d789_635d_2dff, 0x121f_d215_5c47_2f96];
+
+        let mut state = [0u64; 2];
+        for word in JUMP.iter() {
+            for bit in 0..64 {
+                if word & (1 << bit) != 0 {
+                    state[0] ^= self.state[0];
+                    state[1] ^= self.state[1];
+                }
+                self.next_u64();
+            }
+        }
+        self.state = state;
+    }
+
+    /// Generate a pseudo random f64 uniformly distributed in [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        // Use the 53 high bits, which is the precision of the f64 mantissa, so that all the
+        // values are evenly spaced.
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Generate a pseudo random u64 uniformly distributed in [`lower`, `upper`).
+    ///
+    /// # Arguments
+    ///
+    /// * `lower`: the inclusive lower bound of the range.
+    /// * `upper`: the exclusive upper bound of the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lower` is not smaller than `upper`.
+    pub fn rand_range(&mut self, lower: u64, upper: u64) -> u64 {
+        assert!(lower < upper, "empty range");
+        let span = upper - lower;
+        // Reject the lowest `2^64 % span` values, so that the remaining ones can be evenly
+        // mapped onto the range.
+        let threshold = span.wrapping_neg() % span;
+        loop {
+            let r = self.next_u64();
+            if r >= threshold {
+                return lower + r % span;
+            }
+        }
+    }
+
+    /// Generate a pseudo random port number outside of the well-known range.
+    ///
+    /// The returned port is uniformly distributed in [`1024`, `65535`], so it doesn't
+    /// require privileges for binding, which makes it handy for picking the ports of
+    /// test servers (e.g. over TCP or vsock). The port may still be in use, so the
+    /// callers must be prepared to retry with another one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let port = Xorshift::new(42).rand_port();
+    /// assert!(port >= 1024);
+    /// ```
+    pub fn rand_port(&mut self) -> u16 {
+        self.rand_range(1024, u64::from(u16::MAX) + 1) as u16
+    }
+
+    /// Shuffle `slice` in place.
+    ///
+    /// This uses the Fisher-Yates algorithm, so all the permutations are equally likely.
+    ///
+    /// # Arguments
+    ///
+    /// * `slice`: the slice to be shuffled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let mut v = vec![1, 2, 3, 4];
+    /// Xorshift::new(42).shuffle(&mut v);
+    /// v.sort();
+    /// assert_eq!(v, [1, 2, 3, 4]);
+    /// ```
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.rand_range(0, i as u64 + 1) as usize;
+            slice.swap(i, j);
+        }
+    }
+
+    /// Pick a random element of `slice`.
+    ///
+    /// Returns `None` if `slice` is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `slice`: the slice to pick an element from.
+    pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+        if slice.is_empty() {
+            return None;
+        }
+        Some(&slice[self.rand_range(0, slice.len() as u64) as usize])
+    }
+
+    /// Pick `k` distinct random elements of `slice`.
+    ///
+    /// The elements are picked with a partial Fisher-Yates shuffle of their indices, so all
+    /// the subsets of size `k` are equally likely, and are returned in the order they are
+    /// picked. If `k` is larger than the length of `slice`, all of its elements are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `slice`: the slice to pick the elements from.
+    /// * `k`: the number of elements to pick.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let v = [1, 2, 3, 4, 5];
+    /// let picked = Xorshift::new(42).choose_multiple(&v, 2);
+    /// assert_eq!(picked.len(), 2);
+    /// assert_ne!(picked[0], picked[1]);
+    /// ```
+    pub fn choose_multiple<'a, T>(&mut self, slice: &'a [T], k: usize) -> Vec<&'a T> {
+        let k = min(k, slice.len());
+        let mut indices: Vec<usize> = (0..slice.len()).collect();
+        for i in 0..k {
+            let j = self.rand_range(i as u64, slice.len() as u64) as usize;
+            indices.swap(i, j);
+        }
+        indices[..k].iter().map(|i| &slice[*i]).collect()
+    }
+
+    /// Pick `count` distinct random indices in [`0`, `range`), in ascending order.
+    ///
+    /// The indices are picked with Robert Floyd's sampling algorithm, so all the subsets of
+    /// size `count` are equally likely, and the work only depends on `count`, even for a
+    /// large `range` (e.g. picking a few positions of a large descriptor ring).
+    ///
+    /// Returns `None` if `count` is larger than `range`.
+    ///
+    /// # Arguments
+    ///
+    /// * `count`: the number of indices to pick.
+    /// * `range`: the exclusive upper bound of the indices.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let indices = Xorshift::new(42).distinct_sorted_indices(3, 1 << 20).unwrap();
+    /// assert_eq!(indices.len(), 3);
+    /// assert!(indices[0] < indices[1] && indices[1] < indices[2]);
+    /// ```
+    pub fn distinct_sorted_indices(&mut self, count: usize, range: usize) -> Option<Vec<usize>> {
+        if count > range {
+            return None;
+        }
+        let mut picked = BTreeSet::new();
+        for j in (range - count)..range {
+            let t = self.rand_range(0, j as u64 + 1) as usize;
+            if !picked.insert(t) {
+                picked.insert(j);
+            }
+        }
+        Some(picked.into_iter().collect())
+    }
+
+    /// Generate a pseudo random f64 following an exponential distribution of rate `lambda`.
+    ///
+    /// The samples have a mean of `1 / lambda`, which makes them suitable as inter-arrival
+    /// times of events happening on average `lambda` times per unit of time.
+    ///
+    /// # Arguments
+    ///
+    /// * `lambda`: the rate of the distribution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lambda` is not positive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let delay = Xorshift::new(42).rand_exp(0.5);
+    /// assert!(delay >= 0.0);
+    /// ```
+    pub fn rand_exp(&mut self, lambda: f64) -> f64 {
+        assert!(lambda > 0.0, "non-positive rate");
+        // Inverse transform sampling. `1 - u` is in (0, 1], so the logarithm is finite.
+        -(1.0 - self.next_f64()).ln() / lambda
+    }
+
+    /// Generate a pseudo random duration uniformly distributed in [`min`, `max`].
+    ///
+    /// The duration is sampled with nanosecond granularity, which makes it handy for
+    /// sleeping a random interval between the operations of chaos or fuzz tests. The
+    /// difference between `max` and `min` is capped to `u64::MAX` nanoseconds (more
+    /// than 584 years).
+    ///
+    /// # Arguments
+    ///
+    /// * `min`: the inclusive lower bound of the duration.
+    /// * `max`: the inclusive upper bound of the duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` is greater than `max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use std::time::Duration;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let (min, max) = (Duration::from_millis(1), Duration::from_millis(10));
+    /// let delay = Xorshift::new(42).rand_duration(min, max);
+    /// assert!(delay >= min && delay <= max);
+    /// ```
+    pub fn rand_duration(&mut self, min: Duration, max: Duration) -> Duration {
+        assert!(min <= max, "empty range");
+        let span = (max - min).min(Duration::from_nanos(u64::MAX)).as_nanos() as u64;
+        let offset = if span == u64::MAX {
+            self.next_u64()
+        } else {
+            self.rand_range(0, span + 1)
+        };
+        min + Duration::from_nanos(offset)
+    }
+
+    /// Generate a pseudo random version 4 UUID, as defined by
+    /// [`RFC 4122`](https://tools.ietf.org/html/rfc4122#section-4.4).
+    ///
+    /// The returned bytes are in network byte order, with the version and variant bits
+    /// set. As for the other methods of the generator, the same seed gives the same
+    /// UUIDs, which is useful for identifying resources in tests. This makes them
+    /// guessable though, so identifiers that need to be unpredictable must be generated
+    /// from a cryptographically secure source (e.g.
+    /// [`getrandom`](http://man7.org/linux/man-pages/man2/getrandom.2.html)) instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::rand::Xorshift;
+    ///
+    /// let uuid = Xorshift::new(42).rand_uuid_v4();
+    /// assert_eq!(uuid[6] >> 4, 4);
+    /// ```
+    pub fn rand_uuid_v4(&mut self) -> [u8; 16] {
+        let mut uuid = [0u8; 16];
+        uuid[..8].copy_from_slice(&self.next_u64().to_be_bytes());
+        uuid[8..].copy_from_slice(&self.next_u64().to_be_bytes());
+        // The 4 high bits of the time_hi_and_version field are the version.
+        uuid[6] = (uuid[6] & 0x0f) | 0x40;
+        // The 2 high bits of the clock_seq_hi_and_reserved field are the variant.
+        uuid[8] = (uuid[8] & 0x3f) | 0x80;
+        uuid
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,4 +481,225 @@ mod tests {
         let s = rand_alphanumerics(5);
         assert_eq!(5, s.len());
     }
+
+    #[test]
+    fn test_xorshift_reproducible() {
+        let mut rng1 = Xorshift::new(1234);
+        let mut rng2 = Xorshift::new(1234);
+        let mut rng3 = Xorshift::new(1235);
+        for _ in 0..1000 {
+            let n = rng1.next_u64();
+            assert_eq!(n, rng2.next_u64());
+            assert_ne!(n, rng3.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut rng = Xorshift { state: [1, 2] };
+        rng.jump();
+        assert_eq!(
+            rng.state,
+            [16_097_136_559_366_334_698, 6_335_159_599_533_594_559]
+        );
+
+        let mut rng1 = Xorshift::new(1234);
+        let mut rng2 = rng1.clone();
+        rng2.jump();
+        let mut rng3 = rng2.clone();
+        rng3.jump();
+        for _ in 0..1000 {
+            let (n1, n2, n3) = (rng1.next_u64(), rng2.next_u64(), rng3.next_u64());
+            assert_ne!(n1, n2);
+            assert_ne!(n1, n3);
+            assert_ne!(n2, n3);
+        }
+    }
+
+    #[test]
+    fn test_rand_range() {
+        let mut rng = Xorshift::new(1234);
+        let mut hits = [0u32; 10];
+        for _ in 0..10000 {
+            let n = rng.rand_range(5, 15);
+            assert!((5..15).contains(&n));
+            hits[(n - 5) as usize] += 1;
+        }
+        for h in hits.iter() {
+            assert!((801..1200).contains(h));
+        }
+        assert_eq!(rng.rand_range(7, 8), 7);
+        rng.rand_range(0, u64::MAX);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rand_range_empty() {
+        Xorshift::new(1234).rand_range(5, 5);
+    }
+
+    #[test]
+    fn test_rand_port() {
+        let mut rng = Xorshift::new(1234);
+        let ports: Vec<u16> = (0..10000).map(|_| rng.rand_port()).collect();
+        assert!(ports.iter().all(|&port| port >= 1024));
+        // Both halves of the range are covered.
+        assert!(ports.iter().any(|&port| port < 33280));
+        assert!(ports.iter().any(|&port| port >= 33280));
+
+        let mut rng = Xorshift::new(1234);
+        assert_eq!(rng.rand_port(), ports[0]);
+        assert_eq!(rng.rand_port(), ports[1]);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut v: Vec<u32> = (0..10).collect();
+        Xorshift::new(1234).shuffle(&mut v);
+
+        let mut sorted = v.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<u32>>());
+        assert_eq!(v, [7, 1, 4, 6, 0, 2, 5, 8, 9, 3]);
+
+        let mut empty: [u32; 0] = [];
+        Xorshift::new(1234).shuffle(&mut empty);
+    }
+
+    #[test]
+    fn test_choose() {
+        let mut rng = Xorshift::new(1234);
+        let empty: [u32; 0] = [];
+        assert!(rng.choose(&empty).is_none());
+
+        let v = [0usize, 1, 2, 3, 4];
+        let mut hits = [0u32; 5];
+        for _ in 0..10000 {
+            hits[*rng.choose(&v).unwrap()] += 1;
+        }
+        for h in hits.iter() {
+            assert!((1801..2200).contains(h));
+        }
+    }
+
+    #[test]
+    fn test_choose_multiple() {
+        let v: Vec<u32> = (0..20).collect();
+        let mut rng = Xorshift::new(1234);
+        for k in 0..=20 {
+            let mut picked = rng.choose_multiple(&v, k);
+            assert_eq!(picked.len(), k);
+            picked.sort();
+            picked.dedup();
+            assert_eq!(picked.len(), k);
+        }
+        assert_eq!(rng.choose_multiple(&v, 30).len(), 20);
+
+        assert_eq!(
+            Xorshift::new(1234).choose_multiple(&v, 5),
+            Xorshift::new(1234).choose_multiple(&v, 5)
+        );
+    }
+
+    #[test]
+    fn test_distinct_sorted_indices() {
+        let mut rng = Xorshift::new(1234);
+        for &(count, range) in &[(0, 0), (0, 10), (1, 1), (5, 10), (10, 10), (20, 1 << 40)] {
+            let indices = rng.distinct_sorted_indices(count, range).unwrap();
+            assert_eq!(indices.len(), count);
+            assert!(indices.windows(2).all(|w| w[0] < w[1]), "{:?}", indices);
+            assert!(indices.iter().all(|i| *i < range));
+        }
+        assert_eq!(
+            rng.distinct_sorted_indices(10, 10).unwrap(),
+            (0..10).collect::<Vec<usize>>()
+        );
+        assert_eq!(rng.distinct_sorted_indices(11, 10), None);
+
+        // All the indices get picked.
+        let mut counts = [0; 8];
+        for _ in 0..1000 {
+            for i in rng.distinct_sorted_indices(2, 8).unwrap() {
+                counts[i] += 1;
+            }
+        }
+        assert!(counts.iter().all(|c| *c > 150), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_next_f64() {
+        let mut rng = Xorshift::new(1234);
+        let mut sum = 0.0;
+        for _ in 0..10000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+            sum += x;
+        }
+        assert!((sum / 10000.0 - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_rand_exp() {
+        for &lambda in &[0.1, 1.0, 4.0] {
+            let mut rng = Xorshift::new(1234);
+            let mut sum = 0.0;
+            for _ in 0..100_000 {
+                let x = rng.rand_exp(lambda);
+                assert!(x >= 0.0 && x.is_finite());
+                sum += x;
+            }
+            let mean = sum / 100_000.0;
+            assert!((mean * lambda - 1.0).abs() < 0.02, "mean = {}", mean);
+        }
+
+        assert_eq!(
+            Xorshift::new(1234).rand_exp(1.0),
+            Xorshift::new(1234).rand_exp(1.0)
+        );
+    }
+
+    #[test]
+    fn test_rand_duration() {
+        let mut rng = Xorshift::new(1234);
+        let min = Duration::from_micros(10);
+        let max = Duration::from_micros(20);
+        for _ in 0..10000 {
+            let d = rng.rand_duration(min, max);
+            assert!(d >= min && d <= max);
+        }
+        assert_eq!(rng.rand_duration(max, max), max);
+        assert!(rng.rand_duration(Duration::ZERO, Duration::MAX) >= Duration::ZERO);
+
+        // The same seed gives the same durations.
+        let a: Vec<_> = (0..10)
+            .scan(Xorshift::new(42), |r, _| Some(r.rand_duration(min, max)))
+            .collect();
+        let b: Vec<_> = (0..10)
+            .scan(Xorshift::new(42), |r, _| Some(r.rand_duration(min, max)))
+            .collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_rand_uuid_v4() {
+        let mut rng = Xorshift::new(1234);
+        let uuids: Vec<[u8; 16]> = (0..100).map(|_| rng.rand_uuid_v4()).collect();
+        for uuid in &uuids {
+            assert_eq!(uuid[6] & 0xf0, 0x40);
+            assert_eq!(uuid[8] & 0xc0, 0x80);
+        }
+        for (i, uuid) in uuids.iter().enumerate() {
+            assert!(!uuids[i + 1..].contains(uuid));
+        }
+
+        let mut rng = Xorshift::new(1234);
+        assert_eq!(rng.rand_uuid_v4(), uuids[0]);
+        assert_ne!(Xorshift::new(4321).rand_uuid_v4(), uuids[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-positive rate")]
+    fn test_rand_exp_invalid_rate() {
+        Xorshift::new(1234).rand_exp(0.0);
+    }
 }
@@ -10,19 +10,26 @@
 //! [`signal`](http://man7.org/linux/man-pages/man7/signal.7.html).
 
 use libc::{
-    c_int, c_void, pthread_kill, pthread_sigmask, pthread_t, sigaction, sigaddset, sigemptyset,
-    sigfillset, siginfo_t, sigismember, sigpending, sigset_t, sigtimedwait, timespec, EAGAIN,
-    EINTR, EINVAL, SIG_BLOCK, SIG_UNBLOCK,
+    c_int, c_void, pid_t, pthread_kill, pthread_sigmask, pthread_t, sigaction, sigaddset,
+    sigaltstack, sigdelset, sigemptyset, sigfillset, siginfo_t, sigismember, sigpending, sigset_t,
+    sigtimedwait, stack_t, timespec, EAGAIN, EBUSY, EINTR, EINVAL, SIG_BLOCK, SIG_SETMASK,
+    SIG_UNBLOCK,
 };
 
+use crate::eventfd::EventFd;
 use errno;
 use std::fmt::{self, Display};
 use std::io;
 use std::mem;
+use std::os::unix::io::AsRawFd;
 use std::os::unix::thread::JoinHandleExt;
 use std::ptr::{null, null_mut};
 use std::result;
-use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+// Reexport the `sigaction` flags accepted by `register_signal_handler_with_flags` from libc.
+pub use libc::{SA_NODEFER, SA_ONSTACK, SA_RESTART, SA_SIGINFO};
 
 /// The error cases enumeration for signal handling.
 #[derive(Debug)]
@@ -45,6 +52,8 @@ pub enum Error {
     ClearGetPending(errno::Error),
     /// Failed to check if given signal is in the set of pending signals.
     ClearCheckPending(errno::Error),
+    /// The signal mask could not be restored.
+    RestoreSignalMask(errno::Error),
 }
 
 impl Display for Error {
@@ -73,6 +82,7 @@ impl Display for Error {
                 "failed to check whether given signal is in the pending set: {}",
                 e,
             ),
+            RestoreSignalMask(e) => write!(f, "signal mask could not be restored: {}", e),
         }
     }
 }
@@ -103,6 +113,130 @@ pub fn SIGRTMAX() -> c_int {
     unsafe { __libc_current_sigrtmax() }
 }
 
+// The names of the standard signals.
+const SIGNAL_NAMES: &[(c_int, &str)] = &[
+    (libc::SIGHUP, "SIGHUP"),
+    (libc::SIGINT, "SIGINT"),
+    (libc::SIGQUIT, "SIGQUIT"),
+    (libc::SIGILL, "SIGILL"),
+    (libc::SIGTRAP, "SIGTRAP"),
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGBUS, "SIGBUS"),
+    (libc::SIGFPE, "SIGFPE"),
+    (libc::SIGKILL, "SIGKILL"),
+    (libc::SIGUSR1, "SIGUSR1"),
+    (libc::SIGSEGV, "SIGSEGV"),
+    (libc::SIGUSR2, "SIGUSR2"),
+    (libc::SIGPIPE, "SIGPIPE"),
+    (libc::SIGALRM, "SIGALRM"),
+    (libc::SIGTERM, "SIGTERM"),
+    (libc::SIGSTKFLT, "SIGSTKFLT"),
+    (libc::SIGCHLD, "SIGCHLD"),
+    (libc::SIGCONT, "SIGCONT"),
+    (libc::SIGSTOP, "SIGSTOP"),
+    (libc::SIGTSTP, "SIGTSTP"),
+    (libc::SIGTTIN, "SIGTTIN"),
+    (libc::SIGTTOU, "SIGTTOU"),
+    (libc::SIGURG, "SIGURG"),
+    (libc::SIGXCPU, "SIGXCPU"),
+    (libc::SIGXFSZ, "SIGXFSZ"),
+    (libc::SIGVTALRM, "SIGVTALRM"),
+    (libc::SIGPROF, "SIGPROF"),
+    (libc::SIGWINCH, "SIGWINCH"),
+    (libc::SIGIO, "SIGIO"),
+    (libc::SIGPWR, "SIGPWR"),
+    (libc::SIGSYS, "SIGSYS"),
+];
+
+// The names of the realtime signals, relative to `SIGRTMIN()`.
+const RT_SIGNAL_NAMES: &[&str] = &[
+    "SIGRTMIN",
+    "SIGRTMIN+1",
+    "SIGRTMIN+2",
+    "SIGRTMIN+3",
+    "SIGRTMIN+4",
+    "SIGRTMIN+5",
+    "SIGRTMIN+6",
+    "SIGRTMIN+7",
+    "SIGRTMIN+8",
+    "SIGRTMIN+9",
+    "SIGRTMIN+10",
+    "SIGRTMIN+11",
+    "SIGRTMIN+12",
+    "SIGRTMIN+13",
+    "SIGRTMIN+14",
+    "SIGRTMIN+15",
+    "SIGRTMIN+16",
+    "SIGRTMIN+17",
+    "SIGRTMIN+18",
+    "SIGRTMIN+19",
+    "SIGRTMIN+20",
+    "SIGRTMIN+21",
+    "SIGRTMIN+22",
+    "SIGRTMIN+23",
+    "SIGRTMIN+24",
+    "SIGRTMIN+25",
+    "SIGRTMIN+26",
+    "SIGRTMIN+27",
+    "SIGRTMIN+28",
+    "SIGRTMIN+29",
+    "SIGRTMIN+30",
+    "SIGRTMIN+31",
+    "SIGRTMIN+32",
+];
+
+/// Get the name of a signal.
+///
+/// Realtime signals are named relative to `SIGRTMIN()`, e.g. `"SIGRTMIN+2"`.
+/// Returns `None` if `signum` is not a valid signal number.
+///
+/// # Arguments
+///
+/// * `signum`: the signal number.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{signal_name, SIGRTMIN};
+///
+/// assert_eq!(signal_name(libc::SIGSEGV), Some("SIGSEGV"));
+/// assert_eq!(signal_name(SIGRTMIN() + 1), Some("SIGRTMIN+1"));
+/// ```
+pub fn signal_name(signum: c_int) -> Option<&'static str> {
+    if signum >= SIGRTMIN() && signum <= SIGRTMAX() {
+        return RT_SIGNAL_NAMES.get((signum - SIGRTMIN()) as usize).cloned();
+    }
+    SIGNAL_NAMES
+        .iter()
+        .find(|(num, _)| *num == signum)
+        .map(|(_, name)| *name)
+}
+
+/// Get the number of a signal from its name.
+///
+/// This is the reverse of [`signal_name`](fn.signal_name.html). Returns `None` if
+/// `name` is not the name of a signal.
+///
+/// # Arguments
+///
+/// * `name`: the signal name, e.g. `"SIGTERM"` or `"SIGRTMIN+3"`.
+pub fn signal_from_name(name: &str) -> Option<c_int> {
+    if let Some(offset) = RT_SIGNAL_NAMES.iter().position(|n| *n == name) {
+        let signum = SIGRTMIN() + offset as c_int;
+        return if signum <= SIGRTMAX() {
+            Some(signum)
+        } else {
+            None
+        };
+    }
+    SIGNAL_NAMES
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(num, _)| *num)
+}
+
 /// Verify that a signal number is valid.
 ///
 /// Supported signals range from `SIGHUP` to `SIGSYS` and from `SIGRTMIN` to `SIGRTMAX`.
@@ -154,12 +288,61 @@ pub fn validate_signal_num(num: c_int) -> errno::Result<()> {
 /// ```
 
 pub fn register_signal_handler(num: c_int, handler: SignalHandler) -> errno::Result<()> {
+    register_signal_handler_with_flags(num, handler, SA_SIGINFO)
+}
+
+/// Register the signal handler of `signum` with the given `sigaction` flags.
+///
+/// The flags of interest are:
+/// * `SA_SIGINFO`: the handler receives the `siginfo_t` and context arguments. Without
+///   it, the handler is called with the signal number only, so it must not access its
+///   other arguments.
+/// * `SA_RESTART`: the system calls interrupted by the signal are restarted instead
+///   of failing with `EINTR`. A handler used for kicking a vCPU out of `KVM_RUN` must
+///   not set it, since `KVM_RUN` has to return `EINTR` for the kick to work.
+/// * `SA_NODEFER`: the signal isn't blocked while its handler is running, so the
+///   handler may be interrupted by another occurrence of the same signal.
+/// * `SA_ONSTACK`: the handler runs on the alternate signal stack of the thread, if
+///   one was installed with [`set_alt_stack`](fn.set_alt_stack.html).
+///
+/// Refer to [`sigaction`](http://man7.org/linux/man-pages/man2/sigaction.2.html) for
+/// the other flags. As for [`register_signal_handler`](fn.register_signal_handler.html),
+/// all the other signals are blocked while `handler` is running.
+///
+/// # Safety
+///
+/// This is considered unsafe because the given handler will be called
+/// asynchronously, interrupting whatever the thread was doing and therefore
+/// must only do async-signal-safe operations.
+///
+/// # Arguments
+///
+/// * `num`: the signal number to be registered.
+/// * `handler`: the signal handler function to register.
+/// * `flags`: the `sigaction` flags, e.g. `SA_SIGINFO | SA_RESTART`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// # use libc::{c_int, c_void, siginfo_t};
+/// use vmm_sys_util::signal::{register_signal_handler_with_flags, SA_SIGINFO, SIGRTMIN};
+///
+/// extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+/// register_signal_handler_with_flags(SIGRTMIN(), handle_signal, SA_SIGINFO).unwrap();
+/// ```
+pub fn register_signal_handler_with_flags(
+    num: c_int,
+    handler: SignalHandler,
+    flags: c_int,
+) -> errno::Result<()> {
     validate_signal_num(num)?;
 
     // Safe, because this is a POD struct.
     let mut act: sigaction = unsafe { mem::zeroed() };
     act.sa_sigaction = handler as *const () as usize;
-    act.sa_flags = libc::SA_SIGINFO;
+    act.sa_flags = flags;
 
     // Block all signals while the `handler` is running.
     // Blocking other signals is needed to make sure the execution of
@@ -175,6 +358,233 @@ pub fn register_signal_handler(num: c_int, handler: SignalHandler) -> errno::Res
     }
 }
 
+/// Reset the disposition of all the signals to the default one.
+///
+/// The signal handlers are inherited across `fork`, so a child process that is about to
+/// `exec` should call this first, to make sure none of the handlers of the parent runs
+/// in the child (the ignored signals would also stay ignored after `exec` otherwise).
+/// `SIGKILL` and `SIGSTOP` are skipped because their disposition can't be changed.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::reset_all_signal_handlers;
+///
+/// // fork() here, then in the child:
+/// reset_all_signal_handlers().unwrap();
+/// // exec() the new program.
+/// ```
+pub fn reset_all_signal_handlers() -> errno::Result<()> {
+    // Safe, because this is a POD struct. An all-zero `sigaction` has the `SIG_DFL` handler,
+    // an empty mask and no flags.
+    let act: sigaction = unsafe { mem::zeroed() };
+    for num in (libc::SIGHUP..=libc::SIGSYS).chain(SIGRTMIN()..=SIGRTMAX()) {
+        if num == libc::SIGKILL || num == libc::SIGSTOP {
+            continue;
+        }
+        // Safe because the parameters are valid and we check the return value.
+        if unsafe { sigaction(num, &act, null_mut()) } < 0 {
+            return errno::errno_result();
+        }
+    }
+    Ok(())
+}
+
+// The initial values of the arrays below, which are only used for repeating them.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_EVENTFD: AtomicI32 = AtomicI32::new(-1);
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+// The eventfd written by `write_signal_eventfd` for each signal number, or -1 if there is none.
+static SIGNAL_EVENTFDS: [AtomicI32; 65] = [NO_EVENTFD; 65];
+// The number of `write_signal_eventfd` calls running for each signal number, which may be
+// using the eventfd.
+static SIGNAL_EVENTFD_WRITERS: [AtomicUsize; 65] = [NO_HANDLER; 65];
+
+extern "C" fn write_signal_eventfd(num: c_int, _: *mut siginfo_t, _: *mut c_void) {
+    let (slot, writers) = match (
+        SIGNAL_EVENTFDS.get(num as usize),
+        SIGNAL_EVENTFD_WRITERS.get(num as usize),
+    ) {
+        (Some(slot), Some(writers)) => (slot, writers),
+        _ => return,
+    };
+    // Announce the write before loading the fd, so that the guard dropping the registration
+    // waits for it before closing the fd.
+    writers.fetch_add(1, Ordering::SeqCst);
+    let fd = slot.load(Ordering::SeqCst);
+    if fd < 0 {
+        writers.fetch_sub(1, Ordering::SeqCst);
+        return;
+    }
+
+    let val: u64 = 1;
+    // This is safe because `write` is async-signal-safe, it only reads the 8 bytes of `val`
+    // and `errno` is restored so the interrupted code doesn't observe the handler's errors.
+    unsafe {
+        let saved_errno = *libc::__errno_location();
+        libc::write(
+            fd,
+            &val as *const u64 as *const c_void,
+            mem::size_of::<u64>(),
+        );
+        *libc::__errno_location() = saved_errno;
+    }
+    writers.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// Guard for a signal handler registered with
+/// [`register_signal_to_eventfd`](fn.register_signal_to_eventfd.html).
+///
+/// Dropping the guard restores the previous disposition of the signal. It then waits for
+/// the handlers already running on other threads to finish writing the eventfd, so that
+/// they never write to the eventfd once it is closed (or its fd is reused).
+pub struct SignalHandlerGuard {
+    num: c_int,
+    prev: sigaction,
+    // Keeps the fd written by the handler open for as long as it is registered.
+    _evt: EventFd,
+}
+
+impl Drop for SignalHandlerGuard {
+    fn drop(&mut self) {
+        // Safe because `prev` was filled in by the kernel when registering the handler.
+        unsafe { sigaction(self.num, &self.prev, null_mut()) };
+        SIGNAL_EVENTFDS[self.num as usize].store(-1, Ordering::SeqCst);
+        // The handlers starting from now see that there is no eventfd, so this only waits for
+        // the ones which may have loaded it already. `_evt` is closed afterwards.
+        while SIGNAL_EVENTFD_WRITERS[self.num as usize].load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+    }
+}
+
+/// Register a handler that writes to `evt` each time the signal `num` is delivered.
+///
+/// This allows handling the signal from an event loop polling `evt`, since the handler
+/// itself only does a single async-signal-safe `write`. Only one eventfd can be registered
+/// for a given signal at a time, otherwise this fails with `EBUSY`.
+///
+/// # Arguments
+///
+/// * `num`: the signal number to be registered.
+/// * `evt`: the eventfd to be written each time the signal is delivered.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::eventfd::EventFd;
+/// use vmm_sys_util::signal::{register_signal_to_eventfd, SIGRTMIN};
+///
+/// let evt = EventFd::new(0).unwrap();
+/// let guard = register_signal_to_eventfd(SIGRTMIN(), &evt).unwrap();
+///
+/// unsafe { libc::raise(SIGRTMIN()) };
+/// assert_eq!(evt.read().unwrap(), 1);
+/// ```
+pub fn register_signal_to_eventfd(num: c_int, evt: &EventFd) -> errno::Result<SignalHandlerGuard> {
+    validate_signal_num(num)?;
+    let slot = SIGNAL_EVENTFDS
+        .get(num as usize)
+        .ok_or_else(|| errno::Error::new(EINVAL))?;
+
+    let evt = evt
+        .try_clone()
+        .map_err(|e| errno::Error::new(e.raw_os_error().unwrap_or(EINVAL)))?;
+    if slot
+        .compare_exchange(-1, evt.as_raw_fd(), Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        return Err(errno::Error::new(EBUSY));
+    }
+
+    // Safe, because this is a POD struct.
+    let mut act: sigaction = unsafe { mem::zeroed() };
+    act.sa_sigaction = write_signal_eventfd as *const () as usize;
+    act.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+    // Safe, because this is a POD struct.
+    let mut prev: sigaction = unsafe { mem::zeroed() };
+
+    // Safe because the parameters are valid and we check the return value.
+    if unsafe { sigaction(num, &act, &mut prev) } < 0 {
+        let err = errno::Error::last();
+        slot.store(-1, Ordering::SeqCst);
+        return Err(err);
+    }
+
+    Ok(SignalHandlerGuard {
+        num,
+        prev,
+        _evt: evt,
+    })
+}
+
+/// Guard for an alternate signal stack installed with [`set_alt_stack`](fn.set_alt_stack.html).
+///
+/// Dropping the guard restores the previous alternate signal stack of the thread and then frees
+/// the memory of the stack. The guard owns that memory, so forgetting the guard leaks it instead
+/// of leaving freed memory installed as the signal stack. It can't be sent to another thread,
+/// because the alternate signal stack is a per-thread setting.
+pub struct SigAltStackGuard {
+    prev: stack_t,
+    _mem: Vec<u8>,
+}
+
+impl Drop for SigAltStackGuard {
+    fn drop(&mut self) {
+        // Safe because `prev` was filled in by the kernel when installing the stack. This fails
+        // with EPERM while a handler runs on the stack, which can't be the case here since the
+        // thread is running this code.
+        unsafe { sigaltstack(&self.prev, null_mut()) };
+    }
+}
+
+/// Allocate an alternate signal stack of `size` bytes and install it for the calling thread.
+///
+/// The handlers registered with `SA_ONSTACK` (see
+/// [`register_signal_handler_with_flags`](fn.register_signal_handler_with_flags.html)) run
+/// on this stack, which allows e.g. a `SIGSEGV` handler to run after a stack overflow. Refer
+/// to [`sigaltstack`](http://man7.org/linux/man-pages/man2/sigaltstack.2.html). The stack
+/// is used until the returned guard is dropped, which restores the previous one.
+///
+/// Fails with `ENOMEM` if `size` is smaller than `MINSIGSTKSZ`; `SIGSTKSZ` bytes is the
+/// usual size for a signal stack.
+///
+/// # Arguments
+///
+/// * `size`: the size in bytes of the alternate signal stack.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::set_alt_stack;
+///
+/// let guard = set_alt_stack(64 * 1024).unwrap();
+/// drop(guard);
+/// ```
+pub fn set_alt_stack(size: usize) -> errno::Result<SigAltStackGuard> {
+    let mut mem = vec![0u8; size];
+    let stack = stack_t {
+        ss_sp: mem.as_mut_ptr() as *mut c_void,
+        ss_flags: 0,
+        ss_size: mem.len(),
+    };
+    // Safe because this is a POD struct.
+    let mut prev: stack_t = unsafe { mem::zeroed() };
+
+    // Safe because `stack` describes memory owned by the guard, which is only freed after the
+    // guard restored the previous stack, and we check the return value.
+    if unsafe { sigaltstack(&stack, &mut prev) } < 0 {
+        return errno::errno_result();
+    }
+    Ok(SigAltStackGuard { prev, _mem: mem })
+}
+
 /// Create a `sigset` with given signals.
 ///
 /// An array of signal numbers are added into the signal set by
@@ -218,6 +628,119 @@ pub fn create_sigset(signals: &[c_int]) -> errno::Result<sigset_t> {
     Ok(sigset)
 }
 
+/// A set of signals, wrapping a `sigset_t`.
+///
+/// This is easier to build and inspect than a raw `sigset_t`, which can only be accessed
+/// through the [`sigsetops`](http://man7.org/linux/man-pages/man3/sigsetops.3.html) functions.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{SigSet, SIGRTMIN};
+///
+/// let mut set = SigSet::from_signals(&[libc::SIGINT]).unwrap();
+/// set.add(SIGRTMIN()).unwrap();
+/// assert!(set.contains(SIGRTMIN()));
+/// assert_eq!(set.signals(), vec![libc::SIGINT, SIGRTMIN()]);
+/// ```
+#[derive(Clone, Copy)]
+pub struct SigSet(sigset_t);
+
+impl SigSet {
+    /// Create an empty `SigSet`.
+    pub fn new() -> Self {
+        // Safe because sigemptyset only fails for a null pointer, and it initializes `sigset`.
+        unsafe {
+            let mut sigset: sigset_t = mem::zeroed();
+            sigemptyset(&mut sigset);
+            SigSet(sigset)
+        }
+    }
+
+    /// Create a `SigSet` containing `signals`.
+    ///
+    /// Fails with `EINVAL` if any of `signals` is not a valid signal number.
+    ///
+    /// # Arguments
+    ///
+    /// * `signals`: signal numbers to be added to the set.
+    pub fn from_signals(signals: &[c_int]) -> errno::Result<Self> {
+        create_sigset(signals).map(SigSet)
+    }
+
+    /// Add `num` to the set.
+    ///
+    /// Fails with `EINVAL` if `num` is not a valid signal number.
+    ///
+    /// # Arguments
+    ///
+    /// * `num`: the signal to be added.
+    pub fn add(&mut self, num: c_int) -> errno::Result<()> {
+        // Safe because this only modifies the set and we check the return value.
+        if unsafe { sigaddset(&mut self.0, num) } < 0 {
+            return errno::errno_result();
+        }
+        Ok(())
+    }
+
+    /// Remove `num` from the set.
+    ///
+    /// Fails with `EINVAL` if `num` is not a valid signal number.
+    ///
+    /// # Arguments
+    ///
+    /// * `num`: the signal to be removed.
+    pub fn remove(&mut self, num: c_int) -> errno::Result<()> {
+        // Safe because this only modifies the set and we check the return value.
+        if unsafe { sigdelset(&mut self.0, num) } < 0 {
+            return errno::errno_result();
+        }
+        Ok(())
+    }
+
+    /// Check whether the set contains `num`.
+    ///
+    /// An invalid signal number is never contained in the set.
+    ///
+    /// # Arguments
+    ///
+    /// * `num`: the signal to be looked up.
+    pub fn contains(&self, num: c_int) -> bool {
+        // Safe because this only reads the set.
+        unsafe { sigismember(&self.0, num) > 0 }
+    }
+
+    /// Get the signals contained in the set, in increasing order.
+    pub fn signals(&self) -> Vec<c_int> {
+        (1..=SIGRTMAX()).filter(|num| self.contains(*num)).collect()
+    }
+
+    /// Get a reference to the wrapped `sigset_t`, e.g. for passing it to `pthread_sigmask`.
+    pub fn as_sigset(&self) -> &sigset_t {
+        &self.0
+    }
+}
+
+impl Default for SigSet {
+    fn default() -> Self {
+        SigSet::new()
+    }
+}
+
+impl From<sigset_t> for SigSet {
+    fn from(sigset: sigset_t) -> Self {
+        SigSet(sigset)
+    }
+}
+
+impl fmt::Debug for SigSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.signals()).finish()
+    }
+}
+
 /// Retrieve the signal mask that is blocked of the current thread.
 ///
 /// Use [`pthread_sigmask`](http://man7.org/linux/man-pages/man3/pthread_sigmask.3.html)
@@ -320,6 +843,139 @@ pub fn unblock_signal(num: c_int) -> SignalResult<()> {
     Ok(())
 }
 
+/// Mask all the signals that can be blocked.
+///
+/// Returns the signal mask of the current thread before the call, which can be
+/// given to [`restore_signals`](fn.restore_signals.html). This is typically done
+/// around a `fork`, so that the child starts with a known signal mask.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{block_all_signals, restore_signals};
+///
+/// let prev = block_all_signals().unwrap();
+/// // fork() and set up the child here.
+/// restore_signals(&prev).unwrap();
+/// ```
+pub fn block_all_signals() -> SignalResult<SigSet> {
+    // Safe - return values are checked.
+    unsafe {
+        // sigset will actually be initialized by sigfillset below.
+        let mut sigset: sigset_t = mem::zeroed();
+        if sigfillset(&mut sigset) < 0 {
+            return Err(Error::CreateSigset(errno::Error::last()));
+        }
+
+        let mut old_sigset = SigSet::new();
+        let ret = pthread_sigmask(SIG_BLOCK, &sigset, &mut old_sigset.0);
+        if ret != 0 {
+            return Err(Error::BlockSignal(errno::Error::new(ret)));
+        }
+        Ok(old_sigset)
+    }
+}
+
+/// Replace the signal mask of the current thread with `prev`.
+///
+/// Signals unblocked by this call which are pending are delivered before it returns.
+///
+/// # Arguments
+///
+/// * `prev`: the signal mask to be restored, as returned by
+///   [`block_all_signals`](fn.block_all_signals.html).
+pub fn restore_signals(prev: &SigSet) -> SignalResult<()> {
+    // Safe - return value is checked.
+    let ret = unsafe { pthread_sigmask(SIG_SETMASK, prev.as_sigset(), null_mut()) };
+    if ret != 0 {
+        return Err(Error::RestoreSignalMask(errno::Error::new(ret)));
+    }
+    Ok(())
+}
+
+/// Replace the signal mask of the current thread with `mask`, returning the previous mask.
+///
+/// The mask is swapped with a single
+/// [`pthread_sigmask`](http://man7.org/linux/man-pages/man3/pthread_sigmask.3.html) call, so
+/// no signal can be delivered with a mask that is neither the previous nor the new one.
+/// Passing the returned mask to a second call restores it. As for
+/// [`restore_signals`](fn.restore_signals.html), the pending signals unblocked by this call
+/// are delivered before it returns.
+///
+/// # Arguments
+///
+/// * `mask`: the new signal mask.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{get_blocked_signals, set_signal_mask, SigSet, SIGRTMIN};
+///
+/// let prev = set_signal_mask(&SigSet::from_signals(&[SIGRTMIN()]).unwrap()).unwrap();
+/// assert_eq!(get_blocked_signals().unwrap(), vec![SIGRTMIN()]);
+/// set_signal_mask(&prev).unwrap();
+/// ```
+pub fn set_signal_mask(mask: &SigSet) -> errno::Result<SigSet> {
+    let mut prev = SigSet::new();
+    // Safe because pthread_sigmask only writes to `prev` and we check the return value.
+    let ret = unsafe { pthread_sigmask(SIG_SETMASK, mask.as_sigset(), &mut prev.0) };
+    if ret != 0 {
+        return Err(errno::Error::new(ret));
+    }
+    Ok(prev)
+}
+
+// Restores the signal mask of the thread when dropped, including while unwinding.
+struct SignalMaskGuard(SigSet);
+
+impl Drop for SignalMaskGuard {
+    fn drop(&mut self) {
+        let _ = set_signal_mask(&self.0);
+    }
+}
+
+/// Run `f` with the `signals` unblocked in the current thread.
+///
+/// The previous signal mask is restored when `f` returns, and also when it panics. This is
+/// meant for the sections that must be interruptible by signals which are otherwise blocked
+/// (e.g. allowing `SIGINT` only while waiting for user input). The pending signals are
+/// delivered as soon as they are unblocked, before `f` runs.
+///
+/// # Arguments
+///
+/// * `signals`: the signals to unblock.
+/// * `f`: the closure to run.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{
+///     block_signal, get_blocked_signals, with_signals_unblocked, SIGRTMIN,
+/// };
+///
+/// block_signal(SIGRTMIN()).unwrap();
+/// with_signals_unblocked(&[SIGRTMIN()], || {
+///     assert!(!get_blocked_signals().unwrap().contains(&SIGRTMIN()));
+/// })
+/// .unwrap();
+/// assert!(get_blocked_signals().unwrap().contains(&SIGRTMIN()));
+/// ```
+pub fn with_signals_unblocked<R, F: FnOnce() -> R>(signals: &[c_int], f: F) -> errno::Result<R> {
+    let unblocked = SigSet::from_signals(signals)?;
+    let mut prev = SigSet::new();
+    // Safe because pthread_sigmask only writes to `prev` and we check the return value.
+    let ret = unsafe { pthread_sigmask(SIG_UNBLOCK, unblocked.as_sigset(), &mut prev.0) };
+    if ret != 0 {
+        return Err(errno::Error::new(ret));
+    }
+
+    let _guard = SignalMaskGuard(prev);
+    Ok(f())
+}
+
 /// Clear a pending signal.
 ///
 /// # Arguments
@@ -395,6 +1051,60 @@ pub fn clear_signal(num: c_int) -> SignalResult<()> {
     Ok(())
 }
 
+/// Consume all the pending instances of signal `num`, returning how many were consumed.
+///
+/// Multiple instances of a real-time signal may be queued while it is blocked, so this
+/// keeps taking them until none is pending, e.g. for discarding the vCPU kicks pending
+/// when a VM is paused. Only the signals pending for the calling thread or for the whole
+/// process can be consumed, and `num` should be blocked so that it isn't delivered to a
+/// handler in the meantime.
+///
+/// # Arguments
+///
+/// * `num`: the signal to be drained.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::{block_signal, drain_pending_signal, SIGRTMIN};
+///
+/// block_signal(SIGRTMIN()).unwrap();
+/// unsafe {
+///     libc::raise(SIGRTMIN());
+///     libc::raise(SIGRTMIN());
+/// }
+/// assert_eq!(drain_pending_signal(SIGRTMIN()).unwrap(), 2);
+/// ```
+pub fn drain_pending_signal(num: c_int) -> errno::Result<usize> {
+    let sigset = create_sigset(&[num])?;
+    let ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let mut count = 0;
+    loop {
+        // Safe because this only fills `siginfo`, which is a POD struct, and we check the
+        // return value.
+        let ret = unsafe {
+            let mut siginfo: siginfo_t = mem::zeroed();
+            sigtimedwait(&sigset, &mut siginfo, &ts)
+        };
+        if ret >= 0 {
+            count += 1;
+            continue;
+        }
+        let e = errno::Error::last();
+        match e.errno() {
+            // No more instances of the signal are pending.
+            EAGAIN => return Ok(count),
+            EINTR => {}
+            _ => return Err(e),
+        }
+    }
+}
+
 /// Trait for threads that can be signalled via `pthread_kill`.
 ///
 /// Note that this is only useful for signals between `SIGRTMIN()` and
@@ -438,9 +1148,99 @@ unsafe impl<T> Killable for JoinHandle<T> {
     }
 }
 
+/// A flag that can be set from a signal handler and checked from the rest of the program.
+///
+/// Setting the flag is a single atomic store, so it is async-signal-safe, unlike most
+/// operations. This makes it suitable for e.g. a handler requesting a shutdown, which the
+/// main loop observes by polling the flag.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate libc;
+/// extern crate vmm_sys_util;
+/// # use libc::{c_int, c_void, siginfo_t};
+/// use vmm_sys_util::signal::{register_signal_handler, SignalFlag, SIGRTMIN};
+///
+/// static SHUTDOWN: SignalFlag = SignalFlag::new();
+///
+/// extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+///     SHUTDOWN.set();
+/// }
+///
+/// register_signal_handler(SIGRTMIN(), handle_signal).unwrap();
+/// unsafe { libc::raise(SIGRTMIN()) };
+/// assert!(SHUTDOWN.check_and_clear());
+/// ```
+#[derive(Debug, Default)]
+pub struct SignalFlag(AtomicBool);
+
+impl SignalFlag {
+    /// Create a new, cleared `SignalFlag`.
+    pub const fn new() -> Self {
+        SignalFlag(AtomicBool::new(false))
+    }
+
+    /// Set the flag.
+    ///
+    /// This is async-signal-safe.
+    pub fn set(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether the flag is set, without clearing it.
+    pub fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clear the flag, returning whether it was set.
+    pub fn check_and_clear(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+// Parses the `TracerPid` field of the content of a `/proc/<pid>/status` file.
+fn parse_tracer_pid(status: &str) -> errno::Result<Option<pid_t>> {
+    let value = status
+        .lines()
+        .find_map(|line| line.strip_prefix("TracerPid:"))
+        .ok_or_else(|| errno::Error::new(EINVAL))?;
+    match value.trim().parse::<pid_t>() {
+        Ok(0) => Ok(None),
+        Ok(pid) if pid > 0 => Ok(Some(pid)),
+        _ => Err(errno::Error::new(EINVAL)),
+    }
+}
+
+/// Get the PID of the process tracing the current process (e.g. a debugger), if any.
+///
+/// This reads the `TracerPid` field of `/proc/self/status`. Code installing signal
+/// handlers can check it to leave the signals used by a debugger (e.g. `SIGTRAP`) alone
+/// when one is attached. A tracer can attach or detach at any time, so the result may be
+/// stale as soon as this returns.
+///
+/// Fails with `EINVAL` if the `TracerPid` field is missing or malformed.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::signal::tracer_pid;
+///
+/// if let Some(pid) = tracer_pid().unwrap() {
+///     println!("traced by {}", pid);
+/// }
+/// ```
+pub fn tracer_pid() -> errno::Result<Option<pid_t>> {
+    let status = std::fs::read_to_string("/proc/self/status")?;
+    parse_tracer_pid(&status)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
 
@@ -462,6 +1262,174 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_register_signal_to_eventfd() {
+        let signal = SIGRTMIN() + 4;
+        let evt = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+
+        let guard = register_signal_to_eventfd(signal, &evt).unwrap();
+        // Only one eventfd can be registered for a signal.
+        assert_eq!(
+            register_signal_to_eventfd(signal, &evt)
+                .err()
+                .unwrap()
+                .errno(),
+            EBUSY
+        );
+        assert!(evt.read().is_err());
+
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+        assert_eq!(evt.read().unwrap(), 1);
+
+        // Dropping the guard restores the default disposition.
+        drop(guard);
+        let mut act: sigaction = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { sigaction(signal, null(), &mut act) }, 0);
+        assert_eq!(act.sa_sigaction, libc::SIG_DFL);
+
+        // The signal can be registered again once the guard is gone.
+        let _guard = register_signal_to_eventfd(signal, &evt).unwrap();
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+        assert_eq!(evt.read().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_signal_handler_guard_waits_for_writers() {
+        let signal = SIGRTMIN() + 19;
+        let evt = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let guard = register_signal_to_eventfd(signal, &evt).unwrap();
+
+        // Pretend that a handler loaded the eventfd on another thread.
+        let writers = &SIGNAL_EVENTFD_WRITERS[signal as usize];
+        writers.fetch_add(1, Ordering::SeqCst);
+        let dropper = thread::spawn(move || drop(guard));
+        thread::sleep(Duration::from_millis(20));
+        assert!(!dropper.is_finished());
+
+        writers.fetch_sub(1, Ordering::SeqCst);
+        dropper.join().unwrap();
+        assert_eq!(SIGNAL_EVENTFDS[signal as usize].load(Ordering::SeqCst), -1);
+    }
+
+    #[test]
+    fn test_signal_flag() {
+        static FLAG: SignalFlag = SignalFlag::new();
+
+        extern "C" fn handle_flag_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            FLAG.set();
+        }
+
+        let signal = SIGRTMIN() + 5;
+        register_signal_handler(signal, handle_flag_signal).unwrap();
+        assert!(!FLAG.is_set());
+        assert!(!FLAG.check_and_clear());
+
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+        assert!(FLAG.is_set());
+        assert!(FLAG.check_and_clear());
+        assert!(!FLAG.is_set());
+        assert!(!FLAG.check_and_clear());
+    }
+
+    // Interrupt a thread blocked reading from a pipe with `signal`, registered with `flags`,
+    // and return the result of the read.
+    fn interrupt_slow_read(signal: c_int, flags: c_int) -> io::Result<usize> {
+        extern "C" fn handle_interrupt(_: c_int, _: *mut siginfo_t, _: *mut c_void) {}
+        register_signal_handler_with_flags(signal, handle_interrupt, flags).unwrap();
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let [read_fd, write_fd] = fds;
+        let done = Arc::new(AtomicBool::new(false));
+        let reader_done = done.clone();
+        let reader = thread::spawn(move || {
+            let mut buf = [0u8; 1];
+            // Safe because we give a valid buffer along with its size.
+            let ret = unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+            let res = if ret < 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(ret as usize)
+            };
+            reader_done.store(true, Ordering::SeqCst);
+            res
+        });
+
+        // Keep signaling the reader, so that at least one signal interrupts the read.
+        for _ in 0..10 {
+            thread::sleep(Duration::from_millis(20));
+            if done.load(Ordering::SeqCst) {
+                break;
+            }
+            reader.kill(signal).unwrap();
+        }
+        // Unblock the reader in case the read was restarted.
+        assert_eq!(
+            unsafe { libc::write(write_fd, [1u8].as_ptr() as *const c_void, 1) },
+            1
+        );
+        let res = reader.join().unwrap();
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+        }
+        res
+    }
+
+    #[test]
+    fn test_register_signal_handler_with_flags() {
+        assert!(register_signal_handler_with_flags(SIGRTMAX() + 1, handle_signal, 0).is_err());
+
+        let err = interrupt_slow_read(SIGRTMIN() + 6, SA_SIGINFO).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        // The read resumes after the handler returns and gets the data written later.
+        assert_eq!(
+            interrupt_slow_read(SIGRTMIN() + 7, SA_SIGINFO | SA_RESTART).unwrap(),
+            1
+        );
+        // The handler doesn't need `SA_SIGINFO` when it doesn't use the extra arguments.
+        let err = interrupt_slow_read(SIGRTMIN() + 8, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    }
+
+    fn current_alt_stack() -> stack_t {
+        let mut stack: stack_t = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { sigaltstack(null(), &mut stack) }, 0);
+        stack
+    }
+
+    #[test]
+    fn test_set_alt_stack() {
+        static HANDLER_STACK_ADDR: AtomicUsize = AtomicUsize::new(0);
+        extern "C" fn handle_on_stack(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            let local = 0u8;
+            HANDLER_STACK_ADDR.store(&local as *const u8 as usize, Ordering::SeqCst);
+        }
+
+        let prev = current_alt_stack();
+        {
+            let _guard = set_alt_stack(libc::SIGSTKSZ).unwrap();
+            let stack = current_alt_stack();
+            assert_ne!(stack.ss_sp, prev.ss_sp);
+            assert_eq!(stack.ss_size, libc::SIGSTKSZ);
+            let range = stack.ss_sp as usize..stack.ss_sp as usize + stack.ss_size;
+
+            let signal = SIGRTMIN() + 11;
+            register_signal_handler_with_flags(signal, handle_on_stack, SA_SIGINFO | SA_ONSTACK)
+                .unwrap();
+            assert_eq!(unsafe { libc::raise(signal) }, 0);
+            assert!(range.contains(&HANDLER_STACK_ADDR.load(Ordering::SeqCst)));
+        }
+
+        // Dropping the guard restored the previous stack.
+        let stack = current_alt_stack();
+        assert_eq!(stack.ss_sp, prev.ss_sp);
+        assert_eq!(stack.ss_size, prev.ss_size);
+        assert_eq!(stack.ss_flags, prev.ss_flags);
+
+        assert_eq!(set_alt_stack(16).err().unwrap().errno(), libc::ENOMEM);
+    }
+
     #[test]
     fn test_register_signal_handler() {
         // testing bad value
@@ -560,4 +1528,224 @@ mod tests {
         assert!(killable.kill(SIGRTMIN() + 1).is_ok());
         killable.join().unwrap();
     }
+
+    #[test]
+    fn test_drain_pending_signal() {
+        let signal = SIGRTMIN() + 9;
+        block_signal(signal).unwrap();
+        assert_eq!(drain_pending_signal(signal).unwrap(), 0);
+
+        // Instances of a real-time signal are queued while it is blocked.
+        for _ in 0..5 {
+            assert_eq!(unsafe { libc::raise(signal) }, 0);
+        }
+        assert!(is_pending(signal));
+        assert_eq!(drain_pending_signal(signal).unwrap(), 5);
+        assert!(!is_pending(signal));
+        assert_eq!(drain_pending_signal(signal).unwrap(), 0);
+
+        assert!(drain_pending_signal(-1).is_err());
+    }
+
+    #[test]
+    fn test_sigset() {
+        let mut set = SigSet::new();
+        assert!(set.signals().is_empty());
+        assert!(!set.contains(libc::SIGINT));
+
+        set.add(libc::SIGINT).unwrap();
+        set.add(SIGRTMAX()).unwrap();
+        set.add(libc::SIGHUP).unwrap();
+        assert!(set.contains(libc::SIGINT));
+        assert_eq!(set.signals(), vec![libc::SIGHUP, libc::SIGINT, SIGRTMAX()]);
+        assert_eq!(
+            format!("{:?}", SigSet::from_signals(&[libc::SIGHUP]).unwrap()),
+            format!("{{{}}}", libc::SIGHUP)
+        );
+
+        set.remove(libc::SIGINT).unwrap();
+        assert!(!set.contains(libc::SIGINT));
+        // Removing a signal that isn't in the set is not an error.
+        set.remove(libc::SIGINT).unwrap();
+
+        // Round trip through the list of signals and the raw sigset_t.
+        let set2 = SigSet::from_signals(&set.signals()).unwrap();
+        assert_eq!(set2.signals(), set.signals());
+        let set3 = SigSet::from(*set2.as_sigset());
+        assert_eq!(set3.signals(), vec![libc::SIGHUP, SIGRTMAX()]);
+
+        assert_eq!(set.add(0).unwrap_err().errno(), EINVAL);
+        assert_eq!(set.remove(SIGRTMAX() + 1).unwrap_err().errno(), EINVAL);
+        assert!(SigSet::from_signals(&[libc::SIGINT, -1]).is_err());
+        assert!(!set.contains(-1));
+    }
+
+    #[test]
+    fn test_with_signals_unblocked() {
+        static DELIVERED: SignalFlag = SignalFlag::new();
+        extern "C" fn handle_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            DELIVERED.set();
+        }
+
+        let signal = SIGRTMIN() + 13;
+        register_signal_handler(signal, handle_signal).unwrap();
+        block_signal(signal).unwrap();
+
+        // The signal raised while blocked is delivered once unblocked.
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+        assert!(!DELIVERED.is_set());
+        let blocked_inside = with_signals_unblocked(&[signal], || {
+            assert!(DELIVERED.is_set());
+            get_blocked_signals().unwrap().contains(&signal)
+        })
+        .unwrap();
+        assert!(!blocked_inside);
+        assert!(get_blocked_signals().unwrap().contains(&signal));
+
+        // The mask is restored when the closure panics too.
+        let res = std::panic::catch_unwind(|| {
+            with_signals_unblocked(&[signal], || panic!("unblocked section panicked"))
+        });
+        assert!(res.is_err());
+        assert!(get_blocked_signals().unwrap().contains(&signal));
+
+        assert!(with_signals_unblocked(&[0], || ()).is_err());
+        unblock_signal(signal).unwrap();
+    }
+
+    #[test]
+    fn test_set_signal_mask() {
+        let signal = SIGRTMIN() + 12;
+        let mask = SigSet::from_signals(&[signal, libc::SIGUSR2]).unwrap();
+        let prev = set_signal_mask(&mask).unwrap();
+
+        let mut current: sigset_t = unsafe { mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::sigprocmask(SIG_BLOCK, null(), &mut current) },
+            0
+        );
+        assert_eq!(SigSet::from(current).signals(), mask.signals());
+
+        let replaced = set_signal_mask(&prev).unwrap();
+        assert_eq!(replaced.signals(), mask.signals());
+        assert_eq!(get_blocked_signals().unwrap(), prev.signals());
+    }
+
+    #[test]
+    fn test_block_all_restore_signals() {
+        static RESTORED_SIGNAL_DELIVERED: AtomicBool = AtomicBool::new(false);
+        extern "C" fn handle_restored_signal(_: c_int, _: *mut siginfo_t, _: *mut c_void) {
+            RESTORED_SIGNAL_DELIVERED.store(true, Ordering::SeqCst);
+        }
+
+        let signal = SIGRTMIN() + 3;
+        register_signal_handler(signal, handle_restored_signal).unwrap();
+
+        let prev = block_all_signals().unwrap();
+        assert!(!prev.contains(signal));
+        assert!(get_blocked_signals().unwrap().contains(&signal));
+        assert!(get_blocked_signals().unwrap().contains(&libc::SIGTERM));
+
+        // `raise` directs the signal to the calling thread, where it stays pending.
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+        assert!(is_pending(signal));
+        assert!(!RESTORED_SIGNAL_DELIVERED.load(Ordering::SeqCst));
+
+        restore_signals(&prev).unwrap();
+        assert!(!get_blocked_signals().unwrap().contains(&signal));
+        assert!(RESTORED_SIGNAL_DELIVERED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_tracer_pid() {
+        assert_eq!(tracer_pid().unwrap(), None);
+
+        let status = "Name:\tvmm\nState:\tS (sleeping)\nTracerPid:\t0\nUid:\t0\t0\t0\t0\n";
+        assert_eq!(parse_tracer_pid(status).unwrap(), None);
+        let status = "Name:\tvmm\nTracerPid:\t4242\n";
+        assert_eq!(parse_tracer_pid(status).unwrap(), Some(4242));
+        // The last line may not be terminated, and the spacing may vary.
+        assert_eq!(parse_tracer_pid("TracerPid:  17 ").unwrap(), Some(17));
+
+        for status in &[
+            "",
+            "Name:\tvmm\n",
+            "TracerPid:\n",
+            "TracerPid:\tabc\n",
+            "TracerPid:\t-1\n",
+            "XTracerPid:\t1\n",
+        ] {
+            assert_eq!(
+                parse_tracer_pid(status).unwrap_err(),
+                errno::Error::new(EINVAL),
+                "{:?}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn test_signal_name() {
+        for signum in &[libc::SIGHUP, libc::SIGSEGV, libc::SIGCHLD, libc::SIGSYS] {
+            assert_eq!(
+                signal_from_name(signal_name(*signum).unwrap()),
+                Some(*signum)
+            );
+        }
+        assert_eq!(signal_name(libc::SIGKILL), Some("SIGKILL"));
+        assert_eq!(signal_from_name("SIGTERM"), Some(libc::SIGTERM));
+
+        for signum in SIGRTMIN()..=SIGRTMAX() {
+            assert_eq!(signal_from_name(signal_name(signum).unwrap()), Some(signum));
+        }
+        assert_eq!(signal_name(SIGRTMIN()), Some("SIGRTMIN"));
+        assert_eq!(signal_name(SIGRTMIN() + 3), Some("SIGRTMIN+3"));
+
+        assert_eq!(signal_name(0), None);
+        assert_eq!(signal_name(SIGRTMAX() + 1), None);
+        assert_eq!(signal_name(-1), None);
+        assert_eq!(signal_from_name("SIGFOO"), None);
+        assert_eq!(signal_from_name("sigterm"), None);
+        assert_eq!(
+            signal_from_name(&format!("SIGRTMIN+{}", SIGRTMAX() - SIGRTMIN() + 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reset_all_signal_handlers() {
+        // Resetting the handlers of the test process would break the other tests, so do it
+        // in a child process, which only makes async-signal-safe calls before exiting.
+        let signal = SIGRTMIN() + 14;
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0);
+        if pid == 0 {
+            let code = (|| {
+                if register_signal_handler(signal, handle_signal).is_err()
+                    || register_signal_handler(libc::SIGUSR1, handle_signal).is_err()
+                    || unsafe { libc::signal(libc::SIGPIPE, libc::SIG_IGN) } == libc::SIG_ERR
+                {
+                    return 1;
+                }
+                if reset_all_signal_handlers().is_err() {
+                    return 2;
+                }
+                for num in &[signal, libc::SIGUSR1, libc::SIGPIPE] {
+                    let mut act: sigaction = unsafe { mem::zeroed() };
+                    if unsafe { sigaction(*num, null(), &mut act) } < 0
+                        || act.sa_sigaction != libc::SIG_DFL
+                    {
+                        return 3;
+                    }
+                }
+                0
+            })();
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 0);
+    }
 }
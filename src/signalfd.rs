@@ -0,0 +1,172 @@
+// Copyright 2026 rust-vmm Authors or its affiliates. All Rights Reserved.
+//
+// SPDX-License-Identifier: Apache-2.0 OR BSD-3-Clause
+
+//! Structure and wrapper functions for working with
+//! [`signalfd`](http://man7.org/linux/man-pages/man2/signalfd.2.html).
+
+use std::fs::File;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::{io, mem, result};
+
+use libc::{c_int, c_void, read, signalfd, SFD_CLOEXEC};
+
+use crate::signal::create_sigset;
+
+// Reexport the structure returned by `read` from libc.
+pub use libc::signalfd_siginfo;
+
+/// A safe wrapper around Linux
+/// [`signalfd`](http://man7.org/linux/man-pages/man2/signalfd.2.html).
+///
+/// A `SignalFd` makes the signals of its mask readable as events on a file
+/// descriptor, so they can be handled in an event loop (i.e. by registering
+/// the `SignalFd` in an [`EpollContext`](../poll/struct.EpollContext.html))
+/// instead of by an asynchronous signal handler.
+pub struct SignalFd {
+    signalfd: File,
+}
+
+impl SignalFd {
+    /// Create a new SignalFd accepting the signals in `mask`.
+    ///
+    /// The signals in `mask` must be blocked by the caller (e.g. with
+    /// [`block_signal`](../signal/fn.block_signal.html)) in every thread before
+    /// they are raised, otherwise they are delivered according to their current
+    /// disposition and never become readable on the `SignalFd`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask`: the signal numbers to be accepted via this `SignalFd`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::signal::{block_signal, SIGRTMIN};
+    /// use vmm_sys_util::signalfd::SignalFd;
+    ///
+    /// block_signal(SIGRTMIN()).unwrap();
+    /// let sigfd = SignalFd::new(&[SIGRTMIN()]).unwrap();
+    /// ```
+    pub fn new(mask: &[c_int]) -> result::Result<SignalFd, io::Error> {
+        let sigset = create_sigset(mask).map_err(|e| io::Error::from_raw_os_error(e.errno()))?;
+
+        // This is safe because `sigset` is a valid signal set and we check
+        // the return value.
+        let ret = unsafe { signalfd(-1, &sigset, SFD_CLOEXEC) };
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            // This is safe because we checked ret for success and know
+            // the kernel gave us an fd that we own.
+            Ok(SignalFd {
+                signalfd: unsafe { File::from_raw_fd(ret) },
+            })
+        }
+    }
+
+    /// Read the information of a pending signal from the SignalFd.
+    ///
+    /// If none of the signals in the mask is pending, this blocks until one
+    /// of them is raised.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libc;
+    /// extern crate vmm_sys_util;
+    /// use vmm_sys_util::signal::{block_signal, SIGRTMIN};
+    /// use vmm_sys_util::signalfd::SignalFd;
+    ///
+    /// block_signal(SIGRTMIN()).unwrap();
+    /// let sigfd = SignalFd::new(&[SIGRTMIN()]).unwrap();
+    ///
+    /// unsafe { libc::raise(SIGRTMIN()) };
+    /// assert_eq!(sigfd.read().unwrap().ssi_signo as i32, SIGRTMIN());
+    /// ```
+    pub fn read(&self) -> result::Result<signalfd_siginfo, io::Error> {
+        // This is safe because `signalfd_siginfo` only contains primitive fields.
+        let mut siginfo: signalfd_siginfo = unsafe { mem::zeroed() };
+        let siginfo_size = mem::size_of::<signalfd_siginfo>();
+
+        // This is safe because we made this fd and the pointer we pass
+        // can not overflow because we give the syscall's size parameter properly.
+        let ret = unsafe {
+            read(
+                self.as_raw_fd(),
+                &mut siginfo as *mut signalfd_siginfo as *mut c_void,
+                siginfo_size,
+            )
+        };
+        if ret < 0 {
+            Err(io::Error::last_os_error())
+        } else if ret as usize != siginfo_size {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "short read from signalfd",
+            ))
+        } else {
+            Ok(siginfo)
+        }
+    }
+}
+
+impl AsRawFd for SignalFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.signalfd.as_raw_fd()
+    }
+}
+
+impl AsFd for SignalFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.signalfd.as_fd()
+    }
+}
+
+impl FromRawFd for SignalFd {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        SignalFd {
+            signalfd: File::from_raw_fd(fd),
+        }
+    }
+}
+
+impl IntoRawFd for SignalFd {
+    fn into_raw_fd(self) -> RawFd {
+        self.signalfd.into_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{block_signal, SIGRTMIN};
+
+    #[test]
+    fn test_new() {
+        assert!(SignalFd::new(&[]).is_ok());
+        assert!(SignalFd::new(&[SIGRTMIN()]).is_ok());
+    }
+
+    #[test]
+    fn test_as_fd() {
+        let sigfd = SignalFd::new(&[]).unwrap();
+        assert_eq!(sigfd.as_fd().as_raw_fd(), sigfd.as_raw_fd());
+    }
+
+    #[test]
+    fn test_read() {
+        let signal = SIGRTMIN() + 2;
+
+        // The signal must be blocked so that it stays pending instead of being delivered.
+        block_signal(signal).unwrap();
+        let sigfd = SignalFd::new(&[signal]).unwrap();
+
+        // `raise` directs the signal to the calling thread, which has it blocked.
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+
+        let siginfo = sigfd.read().unwrap();
+        assert_eq!(siginfo.ssi_signo as c_int, signal);
+    }
+}
@@ -8,16 +8,27 @@
 //! control messages (e.g. Unix domain sockets).
 
 use std::fs::File;
-use std::mem::size_of;
+use std::mem::{size_of, zeroed, MaybeUninit};
+use std::net::Shutdown;
 use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
-use std::os::unix::net::{UnixDatagram, UnixStream};
+use std::os::unix::net::{UnixDatagram, UnixListener, UnixStream};
 use std::ptr::{copy_nonoverlapping, null_mut, write_unaligned};
+use std::time::Duration;
 
 use crate::errno::{Error, Result};
 use libc::{
-    c_long, c_void, cmsghdr, iovec, msghdr, recvmsg, sendmsg, MSG_NOSIGNAL, SCM_RIGHTS, SOL_SOCKET,
+    bind, c_char, c_int, c_long, c_void, close, cmsghdr, connect, iovec, listen, msghdr, recv,
+    recvmsg, sa_family_t, sendmsg, setsockopt, shutdown, sockaddr, sockaddr_un, socket, socklen_t,
+    suseconds_t, time_t, timeval, AF_UNIX, EBADMSG, ECONNRESET, EINVAL, MSG_NOSIGNAL, MSG_PEEK,
+    MSG_TRUNC, SCM_RIGHTS, SHUT_RD, SHUT_RDWR, SHUT_WR, SOCK_CLOEXEC, SOCK_STREAM, SOL_SOCKET,
+    SO_RCVTIMEO,
 };
 
+/// The maximum number of file descriptors that can be sent in a single message.
+///
+/// This matches the `SCM_MAX_FD` limit of the kernel.
+pub const MAX_FDS_PER_MESSAGE: usize = 253;
+
 // Each of the following macros performs the same function as their C counterparts. They are each
 // macros because they are used to size statically allocated arrays.
 
@@ -149,6 +160,10 @@ impl CmsgBuffer {
 }
 
 fn raw_sendmsg<D: IntoIovec>(fd: RawFd, out_data: &[D], out_fds: &[RawFd]) -> Result<usize> {
+    if out_fds.len() > MAX_FDS_PER_MESSAGE {
+        return Err(Error::new(EINVAL));
+    }
+
     let cmsg_capacity = CMSG_SPACE!(size_of::<RawFd>() * out_fds.len());
     let mut cmsg_buffer = CmsgBuffer::with_capacity(cmsg_capacity);
 
@@ -197,7 +212,12 @@ fn raw_sendmsg<D: IntoIovec>(fd: RawFd, out_data: &[D], out_fds: &[RawFd]) -> Re
     }
 }
 
-fn raw_recvmsg(fd: RawFd, iovecs: &mut [iovec], in_fds: &mut [RawFd]) -> Result<(usize, usize)> {
+fn raw_recvmsg(
+    fd: RawFd,
+    iovecs: &mut [iovec],
+    in_fds: &mut [RawFd],
+    flags: c_int,
+) -> Result<(usize, usize)> {
     let cmsg_capacity = CMSG_SPACE!(size_of::<RawFd>() * in_fds.len());
     let mut cmsg_buffer = CmsgBuffer::with_capacity(cmsg_capacity);
     let mut msg = new_msghdr(iovecs);
@@ -209,7 +229,7 @@ fn raw_recvmsg(fd: RawFd, iovecs: &mut [iovec], in_fds: &mut [RawFd]) -> Result<
 
     // Safe because the msghdr was properly constructed from valid (or null) pointers of the
     // indicated length and we check the return value.
-    let total_read = unsafe { recvmsg(fd, &mut msg, 0) };
+    let total_read = unsafe { recvmsg(fd, &mut msg, flags) };
 
     if total_read == -1 {
         return Err(Error::last());
@@ -244,6 +264,30 @@ fn raw_recvmsg(fd: RawFd, iovecs: &mut [iovec], in_fds: &mut [RawFd]) -> Result<
     Ok((total_read as usize, in_fds_count))
 }
 
+// Receives exactly `buf.len()` bytes, adding the file descriptors received along the way to
+// `in_fds` after the `in_fds_count` ones already received.
+fn recv_exact_with_fds(
+    fd: RawFd,
+    buf: &mut [u8],
+    in_fds: &mut [RawFd],
+    in_fds_count: &mut usize,
+) -> Result<()> {
+    let mut read_count = 0;
+    while read_count < buf.len() {
+        let mut iovecs = [iovec {
+            iov_base: buf[read_count..].as_mut_ptr() as *mut c_void,
+            iov_len: buf.len() - read_count,
+        }];
+        let (count, fd_count) = raw_recvmsg(fd, &mut iovecs, &mut in_fds[*in_fds_count..], 0)?;
+        *in_fds_count += fd_count;
+        if count == 0 {
+            return Err(Error::new(ECONNRESET));
+        }
+        read_count += count;
+    }
+    Ok(())
+}
+
 /// Trait for file descriptors can send and receive socket control messages via `sendmsg` and
 /// `recvmsg`.
 ///
@@ -304,6 +348,10 @@ pub trait ScmSocket {
     ///
     /// On success, returns the number of bytes sent.
     ///
+    /// Fails with `EINVAL` if more than [`MAX_FDS_PER_MESSAGE`](constant.MAX_FDS_PER_MESSAGE.html)
+    /// file descriptors are given. Use [`send_fds_chunked`](#method.send_fds_chunked) for
+    /// sending larger lists.
+    ///
     /// # Arguments
     ///
     /// * `bufs` - A list of data buffer to send on the `socket`.
@@ -312,6 +360,160 @@ pub trait ScmSocket {
         raw_sendmsg(self.socket_fd(), bufs, fds)
     }
 
+    /// Sends the given file descriptors over the socket, splitting them across as many messages
+    /// as needed to stay within [`MAX_FDS_PER_MESSAGE`](constant.MAX_FDS_PER_MESSAGE.html).
+    ///
+    /// Each message carries the data in `bufs` along with its chunk of file descriptors. The
+    /// data should not be empty for stream sockets, which do not deliver file descriptors sent
+    /// without any data.
+    ///
+    /// On success, returns the number of messages sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `bufs` - A list of data buffer to send with each message.
+    /// * `fds` - A list of file descriptors to be sent.
+    fn send_fds_chunked<D: IntoIovec>(&self, bufs: &[D], fds: &[RawFd]) -> Result<usize> {
+        if fds.is_empty() {
+            self.send_with_fds(bufs, fds)?;
+            return Ok(1);
+        }
+
+        let mut count = 0;
+        for chunk in fds.chunks(MAX_FDS_PER_MESSAGE) {
+            self.send_with_fds(bufs, chunk)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Sends `payload` as a single message prefixed with its length, along with file
+    /// descriptors.
+    ///
+    /// The message is framed with its length as a 4 byte little endian integer, so that
+    /// [`recv_framed`](#method.recv_framed) receives it whole on the other end. This is meant
+    /// for stream sockets, and the data is sent over as many calls as needed. The file
+    /// descriptors are sent along with the first bytes of the message.
+    ///
+    /// Fails with `EINVAL` if `payload` is longer than `u32::MAX` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The message to send on the socket.
+    /// * `fds` - A list of file descriptors to be sent.
+    fn send_framed(&self, payload: &[u8], fds: &[RawFd]) -> Result<()> {
+        if payload.len() > u32::MAX as usize {
+            return Err(Error::new(EINVAL));
+        }
+        let header = (payload.len() as u32).to_le_bytes();
+
+        let mut write_count = self.send_with_fds(&[&header[..], payload], fds)?;
+        while write_count < header.len() {
+            write_count += self.send_with_fds(&[&header[write_count..], payload], &[])?;
+        }
+        let mut write_count = write_count - header.len();
+        while write_count < payload.len() {
+            write_count += self.send_with_fds(&[&payload[write_count..]], &[])?;
+        }
+        Ok(())
+    }
+
+    /// Receives a message sent with [`send_framed`](#method.send_framed), along with file
+    /// descriptors.
+    ///
+    /// This reads the length of the message and then exactly that many bytes, over as many
+    /// calls as needed. On success, returns the message and the number of file descriptors
+    /// received as a tuple `(message, files count)`. The buffer for the message is allocated
+    /// from the length sent by the peer, so the peer must be trusted with up to 4 GiB.
+    ///
+    /// Fails with `ECONNRESET` if the peer closes the socket before sending the whole message.
+    /// On failure, the file descriptors received are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `fds` - A slice of `RawFd`s to put the received file descriptors into, with the same
+    ///   ownership rules as for [`recv_with_fds`](trait.ScmSocket.html#method.recv_with_fds).
+    fn recv_framed(&self, fds: &mut [RawFd]) -> Result<(Vec<u8>, usize)> {
+        let mut fd_count = 0;
+        let mut header = [0u8; 4];
+        let mut payload = Vec::new();
+        let res =
+            recv_exact_with_fds(self.socket_fd(), &mut header, fds, &mut fd_count).and_then(|()| {
+                payload = vec![0u8; u32::from_le_bytes(header) as usize];
+                recv_exact_with_fds(self.socket_fd(), &mut payload, fds, &mut fd_count)
+            });
+
+        if let Err(e) = res {
+            for fd in &fds[..fd_count] {
+                // Safe because we own the received file descriptors and don't use them anymore.
+                unsafe { close(*fd) };
+            }
+            return Err(e);
+        }
+        Ok((payload, fd_count))
+    }
+
+    /// Sends the bytes of `header` along with the file descriptor `fd`, in a single message.
+    ///
+    /// This is meant for sending a file descriptor with a small description of it (e.g. the
+    /// offset and size of a memory region), which the peer receives together with
+    /// [`recv_fd_with_header`](#method.recv_fd_with_header). The header is sent as its raw
+    /// bytes in native endianness.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The header to send on the socket.
+    /// * `fd` - The file descriptor to be sent.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because all the bytes of `header` are read, so the caller needs to ensure
+    /// that `H` has no padding bytes, e.g. that it is a `#[repr(C)]` POD without padding.
+    unsafe fn send_fd_with_header<H: Copy>(&self, header: &H, fd: RawFd) -> Result<()> {
+        // Safe because `header` is a valid reference to `size_of::<H>()` bytes, which the caller
+        // ensures are all initialized.
+        let bytes = std::slice::from_raw_parts(header as *const H as *const u8, size_of::<H>());
+
+        // A stream socket may take the header in several calls; the file descriptor is sent
+        // along with its first bytes.
+        let mut write_count = self.send_with_fds(&[bytes], &[fd])?;
+        while write_count < bytes.len() {
+            write_count += self.send_with_fds(&[&bytes[write_count..]], &[])?;
+        }
+        Ok(())
+    }
+
+    /// Receives a header and a file descriptor sent with
+    /// [`send_fd_with_header`](#method.send_fd_with_header).
+    ///
+    /// Fails with `EBADMSG` if no file descriptor is received with the header, and with
+    /// `ECONNRESET` if the peer closes the socket before sending the whole header. On
+    /// failure, the file descriptor received is closed.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the header is read from the bytes sent by the peer, so the
+    /// caller needs to ensure that any bytes make a valid `H`, i.e. that `H` is a POD.
+    unsafe fn recv_fd_with_header<H: Copy>(&self) -> Result<(H, File)> {
+        let mut header = MaybeUninit::<H>::zeroed();
+        let bytes = std::slice::from_raw_parts_mut(header.as_mut_ptr() as *mut u8, size_of::<H>());
+        let mut fds = [0];
+        let mut fd_count = 0;
+        let res = recv_exact_with_fds(self.socket_fd(), bytes, &mut fds, &mut fd_count);
+        // The received file descriptor is owned by us, so it's closed on failure.
+        let file = if fd_count == 0 {
+            None
+        } else {
+            Some(File::from_raw_fd(fds[0]))
+        };
+        res?;
+
+        match file {
+            Some(file) => Ok((header.assume_init(), file)),
+            None => Err(Error::new(EBADMSG)),
+        }
+    }
+
     /// Receives data and potentially a file descriptor from the socket.
     ///
     /// On success, returns the number of bytes and an optional file descriptor.
@@ -351,7 +553,159 @@ pub trait ScmSocket {
     ///           closed on drop like a `File`-like type would be. It is recommended that each valid
     ///           file descriptor gets wrapped in a drop type that closes it after this returns.
     fn recv_with_fds(&self, iovecs: &mut [iovec], fds: &mut [RawFd]) -> Result<(usize, usize)> {
-        raw_recvmsg(self.socket_fd(), iovecs, fds)
+        raw_recvmsg(self.socket_fd(), iovecs, fds, 0)
+    }
+
+    /// Receives data and file descriptors from the socket, reporting whether the data was
+    /// truncated.
+    ///
+    /// When a datagram is larger than the buffers in `iovecs`, the excess data is discarded.
+    /// On success, returns a tuple `(message length, files count, truncated)`, where the
+    /// message length is the full length of the datagram even when it exceeds the capacity of
+    /// `iovecs`, in which case only as many bytes as fit are received and `truncated` is true.
+    ///
+    /// # Arguments
+    ///
+    /// * `iovecs` - A list of iovec to receive data from the socket.
+    /// * `fds` - A slice of `RawFd`s to put the received file descriptors into, with the same
+    ///   ownership rules as for [`recv_with_fds`](trait.ScmSocket.html#method.recv_with_fds).
+    fn recv_with_fds_trunc(
+        &self,
+        iovecs: &mut [iovec],
+        fds: &mut [RawFd],
+    ) -> Result<(usize, usize, bool)> {
+        let capacity: usize = iovecs.iter().map(|iovec| iovec.iov_len).sum();
+        let (msg_len, fd_count) = raw_recvmsg(self.socket_fd(), iovecs, fds, MSG_TRUNC)?;
+        Ok((msg_len, fd_count, msg_len > capacity))
+    }
+
+    /// Receives data and file descriptors from the socket into a possibly uninitialized buffer.
+    ///
+    /// This avoids having to zero `buf` before each call, which is measurable when receiving
+    /// large buffers at a high rate. On success, returns the number of bytes and file
+    /// descriptors received as a tuple `(bytes count, files count)`; only the first
+    /// `bytes count` bytes of `buf` are initialized by this call.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A buffer to receive data from the socket.
+    /// * `fds` - A slice of `RawFd`s to put the received file descriptors into, with the same
+    ///   ownership rules as for [`recv_with_fds`](trait.ScmSocket.html#method.recv_with_fds).
+    fn recv_with_fds_uninit(
+        &self,
+        buf: &mut [MaybeUninit<u8>],
+        fds: &mut [RawFd],
+    ) -> Result<(usize, usize)> {
+        let mut iovecs = [iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        }];
+        raw_recvmsg(self.socket_fd(), &mut iovecs[..], fds, 0)
+    }
+
+    /// Receives data from the socket without removing it from the receive queue.
+    ///
+    /// On success, returns the number of bytes copied into `buf`, which a subsequent
+    /// receive call returns again. Only data can be peeked: file descriptors sent along
+    /// with it are not received, since peeking them would duplicate them.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - A buffer to receive data from the socket.
+    fn recv_peek(&self, buf: &mut [u8]) -> Result<usize> {
+        // Safe because the kernel only writes up to `buf.len()` bytes to `buf` and we check the
+        // return value.
+        let ret = unsafe {
+            recv(
+                self.socket_fd(),
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                MSG_PEEK,
+            )
+        };
+        if ret == -1 {
+            Err(Error::last())
+        } else {
+            Ok(ret as usize)
+        }
+    }
+
+    /// Sets the timeout of the receive calls on the socket.
+    ///
+    /// Once set, a receive call blocked for longer than `timeout` fails with `EAGAIN` (also
+    /// known as `EWOULDBLOCK`), so that a peer that stops sending can't block the receiver
+    /// forever. A timed out receive call doesn't receive any data nor file descriptors, so
+    /// there is nothing to clean up. The timeout is rounded up to the granularity of the
+    /// kernel.
+    ///
+    /// Fails with `EINVAL` if `timeout` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - The receive timeout, or `None` for blocking indefinitely.
+    fn set_recv_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        let tv = match timeout {
+            Some(timeout) if timeout.as_nanos() == 0 => return Err(Error::new(EINVAL)),
+            Some(timeout) => {
+                let mut tv = timeval {
+                    tv_sec: timeout.as_secs().min(time_t::MAX as u64) as time_t,
+                    tv_usec: timeout.subsec_micros() as suseconds_t,
+                };
+                // Don't round a sub-microsecond timeout down to zero, which disables it.
+                if tv.tv_sec == 0 && tv.tv_usec == 0 {
+                    tv.tv_usec = 1;
+                }
+                tv
+            }
+            None => timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+        };
+
+        // Safe because we give a valid pointer to `tv` along with its size and we check the
+        // return value.
+        let ret = unsafe {
+            setsockopt(
+                self.socket_fd(),
+                SOL_SOCKET,
+                SO_RCVTIMEO,
+                &tv as *const timeval as *const c_void,
+                size_of::<timeval>() as socklen_t,
+            )
+        };
+        if ret == -1 {
+            Err(Error::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Shuts down the receiving side, the sending side, or both sides of the socket.
+    ///
+    /// After shutting down the sending side, the peer reads the end of the stream (i.e. a
+    /// zero length read) once it has received the data already sent, while this side can
+    /// still receive the remaining data sent by the peer. This is the moral equivalent of
+    /// [`shutdown`](http://man7.org/linux/man-pages/man2/shutdown.2.html). It isn't named
+    /// `shutdown`, so that it doesn't clash with the inherent methods of `UnixStream` and
+    /// `UnixDatagram`.
+    ///
+    /// # Arguments
+    ///
+    /// * `how` - The sides of the socket to shut down.
+    fn shutdown_socket(&self, how: Shutdown) -> Result<()> {
+        let how = match how {
+            Shutdown::Read => SHUT_RD,
+            Shutdown::Write => SHUT_WR,
+            Shutdown::Both => SHUT_RDWR,
+        };
+        // Safe because this doesn't modify any memory and we check the return value.
+        let ret = unsafe { shutdown(self.socket_fd(), how) };
+        if ret == -1 {
+            Err(Error::last())
+        } else {
+            Ok(())
+        }
     }
 }
 
@@ -367,6 +721,107 @@ impl ScmSocket for UnixStream {
     }
 }
 
+// Builds the address of `name` in the abstract namespace, along with its length.
+fn abstract_socket_address(name: &[u8]) -> Result<(sockaddr_un, socklen_t)> {
+    // Safe because sockaddr_un only contains primitive fields.
+    let mut addr: sockaddr_un = unsafe { zeroed() };
+    // The name goes after the leading NUL byte of `sun_path`, which selects the abstract
+    // namespace.
+    if name.len() >= addr.sun_path.len() {
+        return Err(Error::new(EINVAL));
+    }
+    addr.sun_family = AF_UNIX as sa_family_t;
+    for (dst, src) in addr.sun_path[1..].iter_mut().zip(name) {
+        *dst = *src as c_char;
+    }
+    // Abstract names aren't NUL terminated, so the length of the address delimits the name.
+    let len = size_of::<sa_family_t>() + 1 + name.len();
+    Ok((addr, len as socklen_t))
+}
+
+fn new_unix_stream_socket() -> Result<RawFd> {
+    // Safe because this doesn't modify any memory and we check the return value.
+    let fd = unsafe { socket(AF_UNIX, SOCK_STREAM | SOCK_CLOEXEC, 0) };
+    if fd == -1 {
+        Err(Error::last())
+    } else {
+        Ok(fd)
+    }
+}
+
+/// Creates a Unix stream socket listening on `name` in the abstract namespace.
+///
+/// Abstract socket addresses start with a NUL byte and don't exist in the file system, so
+/// they can't be given to `UnixListener::bind`. They go away when the socket is closed,
+/// with no file to clean up. The accepted `UnixStream`s implement
+/// [`ScmSocket`](trait.ScmSocket.html), so file descriptors can be passed over them.
+///
+/// Fails with `EINVAL` if `name` doesn't fit in a socket address, i.e. if it is longer than
+/// 107 bytes.
+///
+/// # Arguments
+///
+/// * `name` - The name of the socket, without the leading NUL byte.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::sock_ctrl_msg::{bind_abstract, connect_abstract};
+///
+/// let listener = bind_abstract(b"vmm-sys-util-example").unwrap();
+/// let client = connect_abstract(b"vmm-sys-util-example").unwrap();
+/// let (server, _) = listener.accept().unwrap();
+/// ```
+pub fn bind_abstract(name: &[u8]) -> Result<UnixListener> {
+    let (addr, len) = abstract_socket_address(name)?;
+    // Safe because we own the new socket.
+    let listener = unsafe { UnixListener::from_raw_fd(new_unix_stream_socket()?) };
+
+    // Safe because we give a valid address along with its length and we check the return value.
+    let ret = unsafe {
+        bind(
+            listener.as_raw_fd(),
+            &addr as *const sockaddr_un as *const sockaddr,
+            len,
+        )
+    };
+    if ret == -1 {
+        return Err(Error::last());
+    }
+    // Safe because this doesn't modify any memory and we check the return value.
+    if unsafe { listen(listener.as_raw_fd(), 128) } == -1 {
+        return Err(Error::last());
+    }
+    Ok(listener)
+}
+
+/// Connects a Unix stream socket to `name` in the abstract namespace.
+///
+/// This is the client side of [`bind_abstract`](fn.bind_abstract.html).
+///
+/// # Arguments
+///
+/// * `name` - The name of the socket, without the leading NUL byte.
+pub fn connect_abstract(name: &[u8]) -> Result<UnixStream> {
+    let (addr, len) = abstract_socket_address(name)?;
+    // Safe because we own the new socket.
+    let stream = unsafe { UnixStream::from_raw_fd(new_unix_stream_socket()?) };
+
+    // Safe because we give a valid address along with its length and we check the return value.
+    let ret = unsafe {
+        connect(
+            stream.as_raw_fd(),
+            &addr as *const sockaddr_un as *const sockaddr,
+            len,
+        )
+    };
+    if ret == -1 {
+        return Err(Error::last());
+    }
+    Ok(stream)
+}
+
 /// Trait for types that can be converted into an `iovec` that can be referenced by a syscall for
 /// the lifetime of this object.
 ///
@@ -398,12 +853,15 @@ unsafe impl<'a> IntoIovec for &'a [u8] {
 mod tests {
     use super::*;
     use crate::eventfd::EventFd;
+    use crate::rand::rand_alphanumerics;
 
     use std::io::Write;
     use std::mem::size_of;
     use std::os::raw::c_long;
+    use std::os::unix::ffi::OsStrExt;
     use std::os::unix::net::UnixDatagram;
     use std::slice::from_raw_parts;
+    use std::time::Instant;
 
     use libc::cmsghdr;
 
@@ -531,4 +989,319 @@ mod tests {
 
         assert_eq!(evt.read().expect("failed to read from eventfd"), 1203);
     }
+
+    #[test]
+    fn send_recv_framed() {
+        let (s1, s2) = UnixStream::pair().expect("failed to create socket pair");
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        s1.send_framed(b"framed message", &[evt.as_raw_fd()])
+            .expect("failed to send message");
+        s1.send_framed(b"", &[]).expect("failed to send message");
+
+        let mut files = [0; 2];
+        let (payload, file_count) = s2.recv_framed(&mut files).expect("failed to recv message");
+        assert_eq!(payload, b"framed message");
+        assert_eq!(file_count, 1);
+        let mut file = unsafe { File::from_raw_fd(files[0]) };
+        file.write_all(&1203u64.to_ne_bytes())
+            .expect("failed to write to sent fd");
+        assert_eq!(evt.read().expect("failed to read from eventfd"), 1203);
+
+        let (payload, file_count) = s2.recv_framed(&mut files).expect("failed to recv message");
+        assert!(payload.is_empty());
+        assert_eq!(file_count, 0);
+
+        // A message larger than the socket buffers is sent and received in several calls.
+        let big: Vec<u8> = (0..4 * 1024 * 1024).map(|i| i as u8).collect();
+        let big_clone = big.clone();
+        let sender = std::thread::spawn(move || s1.send_framed(&big_clone, &[]).unwrap());
+        let (payload, _) = s2.recv_framed(&mut files).expect("failed to recv message");
+        assert_eq!(payload, big);
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn send_recv_fd_with_header() {
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Region {
+            offset: u64,
+            size: u64,
+        }
+
+        let (s1, s2) = UnixStream::pair().expect("failed to create socket pair");
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        let region = Region {
+            offset: 0x1000,
+            size: 0x20_0000,
+        };
+        unsafe { s1.send_fd_with_header(&region, evt.as_raw_fd()) }.expect("failed to send header");
+
+        let (header, mut file) =
+            unsafe { s2.recv_fd_with_header::<Region>() }.expect("failed to recv header");
+        assert_eq!(header, region);
+        file.write_all(&1203u64.to_ne_bytes())
+            .expect("failed to write to sent fd");
+        assert_eq!(evt.read().expect("failed to read from eventfd"), 1203);
+
+        // A header without a file descriptor is rejected.
+        s1.send_with_fds(&[&[0u8; 16][..]], &[]).unwrap();
+        let err = unsafe { s2.recv_fd_with_header::<Region>() }.unwrap_err();
+        assert_eq!(err.errno(), libc::EBADMSG);
+    }
+
+    #[test]
+    fn recv_framed_truncated() {
+        let (mut s1, s2) = UnixStream::pair().expect("failed to create socket pair");
+
+        // The header announces 10 bytes, but only 3 are sent before closing the socket.
+        s1.write_all(&10u32.to_le_bytes()).unwrap();
+        s1.write_all(b"abc").unwrap();
+        drop(s1);
+
+        let mut files = [0; 1];
+        let err = s2.recv_framed(&mut files).unwrap_err();
+        assert_eq!(err.errno(), libc::ECONNRESET);
+    }
+
+    #[test]
+    fn send_too_many_fds() {
+        let (s1, s2) = UnixDatagram::pair().expect("failed to create socket pair");
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        let fds = vec![evt.as_raw_fd(); MAX_FDS_PER_MESSAGE + 47];
+        assert_eq!(
+            s1.send_with_fds(&[[1u8].as_ref()], &fds)
+                .unwrap_err()
+                .errno(),
+            EINVAL
+        );
+
+        assert_eq!(
+            s1.send_fds_chunked(&[[1u8].as_ref()], &fds)
+                .expect("failed to send fds"),
+            2
+        );
+
+        let mut files = vec![0; MAX_FDS_PER_MESSAGE];
+        let mut buf = [0u8];
+        for &expected in &[MAX_FDS_PER_MESSAGE, 47] {
+            let mut iovecs = [iovec {
+                iov_base: buf.as_mut_ptr() as *mut c_void,
+                iov_len: buf.len(),
+            }];
+            let (read_count, file_count) = s2
+                .recv_with_fds(&mut iovecs[..], &mut files)
+                .expect("failed to recv fds");
+            assert_eq!(read_count, 1);
+            assert_eq!(file_count, expected);
+            for &fd in &files[..file_count] {
+                // Safe because the received fds are owned by us and closed only once here.
+                drop(unsafe { File::from_raw_fd(fd) });
+            }
+        }
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (s1, s2) = UnixStream::pair().expect("failed to create socket pair");
+        assert_eq!(
+            s2.set_recv_timeout(Some(Duration::from_secs(0)))
+                .unwrap_err()
+                .errno(),
+            EINVAL
+        );
+        s2.set_recv_timeout(Some(Duration::from_millis(50)))
+            .expect("failed to set timeout");
+
+        // The peer never sends anything, so the receive times out.
+        let mut files = [-1; 1];
+        let mut buf = [0u8; 8];
+        let mut iovecs = [iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        }];
+        let start = Instant::now();
+        let err = s2
+            .recv_with_fds(&mut iovecs[..], &mut files)
+            .expect_err("recv didn't time out");
+        assert_eq!(err.errno(), libc::EAGAIN);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(files[0], -1);
+
+        // Clearing the timeout makes the socket block again until data arrives.
+        s2.set_recv_timeout(None).expect("failed to clear timeout");
+        s1.send_with_fds(&[[1u8, 2].as_ref()], &[])
+            .expect("failed to send data");
+        let (count, file_count) = s2
+            .recv_with_fds(&mut iovecs[..], &mut files)
+            .expect("failed to recv data");
+        assert_eq!(count, 2);
+        assert_eq!(file_count, 0);
+    }
+
+    #[test]
+    fn shutdown_write() {
+        let (s1, s2) = UnixStream::pair().expect("failed to create socket pair");
+        s1.send_with_fds(&[[1u8, 2].as_ref()], &[])
+            .expect("failed to send data");
+        s1.shutdown_socket(Shutdown::Write)
+            .expect("failed to shut down socket");
+        assert_eq!(
+            s1.send_with_fds(&[[3u8].as_ref()], &[])
+                .unwrap_err()
+                .errno(),
+            libc::EPIPE
+        );
+
+        // The peer receives the data sent before the shutdown, then the end of the stream.
+        let mut buf = [0u8; 8];
+        let (count, _) = s2.recv_with_fd(&mut buf).expect("failed to recv data");
+        assert_eq!(&buf[..count], &[1, 2]);
+        let (count, _) = s2.recv_with_fd(&mut buf).expect("failed to recv data");
+        assert_eq!(count, 0);
+
+        // Data still flows the other way.
+        s2.send_with_fds(&[[4u8, 5, 6].as_ref()], &[])
+            .expect("failed to send data");
+        let (count, _) = s1.recv_with_fd(&mut buf).expect("failed to recv data");
+        assert_eq!(&buf[..count], &[4, 5, 6]);
+
+        s2.shutdown_socket(Shutdown::Both)
+            .expect("failed to shut down socket");
+        let (count, _) = s1.recv_with_fd(&mut buf).expect("failed to recv data");
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn abstract_socket() {
+        let mut name = format!("vmm-sys-util-test-{}-", std::process::id()).into_bytes();
+        name.extend_from_slice(rand_alphanumerics(8).as_bytes());
+
+        let listener = bind_abstract(&name).expect("failed to bind abstract socket");
+        // The name is in use until the listener is closed.
+        assert_eq!(bind_abstract(&name).unwrap_err().errno(), libc::EADDRINUSE);
+        let client = connect_abstract(&name).expect("failed to connect abstract socket");
+        let (server, _) = listener.accept().expect("failed to accept");
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        client
+            .send_with_fd([1u8, 2, 3].as_ref(), evt.as_raw_fd())
+            .expect("failed to send fd");
+        let mut buf = [0u8; 3];
+        let (count, file) = server.recv_with_fd(&mut buf).expect("failed to recv fd");
+        assert_eq!(count, 3);
+        assert_eq!(buf, [1, 2, 3]);
+        let mut file = file.expect("no fd received");
+        file.write_all(&5u64.to_ne_bytes())
+            .expect("failed to write to received fd");
+        assert_eq!(evt.read().unwrap(), 5);
+
+        // Abstract sockets don't outlive the listener.
+        drop(listener);
+        assert_eq!(
+            connect_abstract(&name).unwrap_err().errno(),
+            libc::ECONNREFUSED
+        );
+        assert_eq!(
+            bind_abstract(&[b'a'; 108]).unwrap_err().errno(),
+            libc::EINVAL
+        );
+    }
+
+    #[test]
+    fn send_recv_trunc() {
+        let (s1, s2) = UnixDatagram::pair().expect("failed to create socket pair");
+        let data = [1u8, 1, 2, 3, 5, 8, 13, 21];
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        s1.send_with_fds(&[data.as_ref()], &[evt.as_raw_fd()])
+            .expect("failed to send fd");
+        s1.send_with_fds(&[data[..4].as_ref()], &[])
+            .expect("failed to send data");
+
+        let mut files = [0; 1];
+        let mut buf = [0u8; 4];
+        let mut iovecs = [iovec {
+            iov_base: buf.as_mut_ptr() as *mut c_void,
+            iov_len: buf.len(),
+        }];
+        let (msg_len, file_count, truncated) = s2
+            .recv_with_fds_trunc(&mut iovecs[..], &mut files)
+            .expect("failed to recv fd");
+        assert_eq!(msg_len, data.len());
+        assert_eq!(file_count, 1);
+        assert!(truncated);
+        assert_eq!(buf, data[..4]);
+        drop(unsafe { File::from_raw_fd(files[0]) });
+
+        // A datagram that fits exactly isn't truncated.
+        let (msg_len, file_count, truncated) = s2
+            .recv_with_fds_trunc(&mut iovecs[..], &mut files)
+            .expect("failed to recv data");
+        assert_eq!(msg_len, 4);
+        assert_eq!(file_count, 0);
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn send_recv_uninit() {
+        let (s1, s2) = UnixDatagram::pair().expect("failed to create socket pair");
+        let data = [1u8, 1, 2, 3, 5, 8, 13];
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        s1.send_with_fds(&[data.as_ref()], &[evt.as_raw_fd()])
+            .expect("failed to send fd");
+        s1.send_with_fds(&[data.as_ref()], &[])
+            .expect("failed to send data");
+
+        let mut files = [0; 1];
+        let mut buf = [MaybeUninit::<u8>::uninit(); 16];
+        let (read_count, file_count) = s2
+            .recv_with_fds_uninit(&mut buf, &mut files)
+            .expect("failed to recv fd");
+        assert_eq!(read_count, data.len());
+        assert_eq!(file_count, 1);
+        // Safe because the first `read_count` bytes were initialized by the call.
+        let received = unsafe { from_raw_parts(buf.as_ptr() as *const u8, read_count) };
+        assert_eq!(received, data);
+        let file = unsafe { File::from_raw_fd(files[0]) };
+        assert_ne!(file.as_raw_fd(), evt.as_raw_fd());
+
+        // The zeroing variant receives the same data.
+        let mut zeroed_buf = [0u8; 16];
+        let mut iovecs = [iovec {
+            iov_base: zeroed_buf.as_mut_ptr() as *mut c_void,
+            iov_len: zeroed_buf.len(),
+        }];
+        let (read_count, file_count) = s2
+            .recv_with_fds(&mut iovecs[..], &mut files)
+            .expect("failed to recv data");
+        assert_eq!(file_count, 0);
+        assert_eq!(&zeroed_buf[..read_count], received);
+    }
+
+    #[test]
+    fn send_recv_peek() {
+        let (s1, s2) = UnixDatagram::pair().expect("failed to create socket pair");
+
+        let evt = EventFd::new(0).expect("failed to create eventfd");
+        s1.send_with_fds(&[[1u8, 2, 3, 4].as_ref()], &[evt.as_raw_fd()])
+            .expect("failed to send fd");
+
+        let mut peek_buf = [0u8; 2];
+        assert_eq!(s2.recv_peek(&mut peek_buf).expect("failed to peek"), 2);
+        assert_eq!(peek_buf, [1, 2]);
+        let mut peek_buf = [0u8; 8];
+        assert_eq!(s2.recv_peek(&mut peek_buf).expect("failed to peek"), 4);
+        assert_eq!(peek_buf[..4], [1, 2, 3, 4]);
+
+        // The data and the fd are still there to be received.
+        let mut buf = [0u8; 8];
+        let (read_count, file) = s2.recv_with_fd(&mut buf).expect("failed to recv fd");
+        assert_eq!(read_count, 4);
+        assert_eq!(buf[..4], peek_buf[..4]);
+        assert!(file.is_some());
+    }
 }
@@ -5,11 +5,11 @@
 // SPDX-License-Identifier: (Apache-2.0 AND BSD-3-Clause)
 
 //! Structure for handling temporary directories.
-use std::env::temp_dir;
+use std::env::{self, temp_dir};
 use std::ffi::{CString, OsStr, OsString};
 use std::fs;
 use std::os::unix::ffi::OsStringExt;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use libc;
 
@@ -92,6 +92,46 @@ impl TempDir {
         Ok(temp_dir)
     }
 
+    /// Creates a new private scratch directory with `prefix`, for runtime files such as
+    /// Unix domain sockets or FIFOs.
+    ///
+    /// The directory is created inside `$XDG_RUNTIME_DIR` if it is set to an existing
+    /// directory, since that directory belongs to the user and is usually short, which
+    /// matters for the length limit of Unix socket paths. Otherwise it is created inside
+    /// `$TMPDIR` if set, or in `/tmp`. Either way, the new directory is only accessible by
+    /// its owner (mode 0700).
+    ///
+    /// Fails with `EINVAL` if `prefix` contains a `/`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix`: the beginning of the name of the directory, to which six random
+    ///   alphanumeric characters are appended.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use vmm_sys_util::tempdir::TempDir;
+    /// let t = TempDir::runtime_scratch("vmm").unwrap();
+    /// let socket_path = t.as_path().join("api.sock");
+    /// ```
+    pub fn runtime_scratch(prefix: &str) -> Result<TempDir> {
+        let runtime_dir = env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        TempDir::runtime_scratch_in(runtime_dir.as_deref(), prefix)
+    }
+
+    fn runtime_scratch_in(runtime_dir: Option<&Path>, prefix: &str) -> Result<TempDir> {
+        if prefix.contains('/') {
+            return Err(Error::new(libc::EINVAL));
+        }
+        let parent = match runtime_dir {
+            Some(dir) if dir.is_absolute() && dir.is_dir() => dir.to_path_buf(),
+            _ => temp_dir(),
+        };
+        // mkdtemp creates the directory with mode 0700.
+        TempDir::new_with_prefix(parent.join(prefix))
+    }
+
     /// Removes the temporary directory.
     ///
     /// Calling this is optional as when a `TempDir` object goes out of scope,
@@ -130,6 +170,96 @@ impl TempDir {
     pub fn as_path(&self) -> &Path {
         self.path.as_ref()
     }
+
+    /// Returns an iterator over the entries of the tempdir.
+    ///
+    /// The iterator doesn't prevent the directory from being removed, so it can
+    /// outlive the `TempDir` object, in which case it won't yield further entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use vmm_sys_util::tempdir::TempDir;
+    /// let temp_dir = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+    /// File::create(temp_dir.as_path().join("file")).unwrap();
+    /// assert_eq!(temp_dir.read_dir().unwrap().count(), 1);
+    /// ```
+    pub fn read_dir(&self) -> Result<fs::ReadDir> {
+        fs::read_dir(&self.path).map_err(Error::from)
+    }
+
+    /// Creates the subdirectory `relative` inside the tempdir, along with all its missing
+    /// parents, and returns its absolute path.
+    ///
+    /// The subdirectories are removed along with the tempdir. Fails with `EINVAL` if
+    /// `relative` is an absolute path or contains `..` components, since it could then
+    /// point outside of the tempdir.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative`: the path of the subdirectory, relative to the tempdir.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::Path;
+    /// # use vmm_sys_util::tempdir::TempDir;
+    /// let temp_dir = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+    /// let subdir = temp_dir.create_subdir(Path::new("a/b")).unwrap();
+    /// assert!(subdir.is_dir());
+    /// ```
+    pub fn create_subdir(&self, relative: &Path) -> Result<PathBuf> {
+        let escapes = relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if escapes {
+            return Err(Error::new(libc::EINVAL));
+        }
+
+        let path = self.path.join(relative);
+        fs::create_dir_all(&path).map_err(Error::from)?;
+        Ok(path)
+    }
+
+    /// Returns the path of a Unix domain socket named `name` inside the tempdir.
+    ///
+    /// The socket itself is not created. The path of a Unix domain socket must fit in the
+    /// `sun_path` field of `sockaddr_un` (108 bytes, including the terminating NUL), which
+    /// is easily overflowed by a deeply nested tempdir. Instead of the `EINVAL` returned
+    /// when binding to such a path, this fails early with `ENAMETOOLONG`; consider using
+    /// [`bind_abstract`](../sock_ctrl_msg/fn.bind_abstract.html) then, since abstract
+    /// socket names are not files. Fails with `EINVAL` if `name` is not a single path
+    /// component.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the file name of the socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::os::unix::net::UnixListener;
+    /// # use vmm_sys_util::tempdir::TempDir;
+    /// let temp_dir = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+    /// let path = temp_dir.socket_path("api.sock").unwrap();
+    /// let listener = UnixListener::bind(&path).unwrap();
+    /// ```
+    pub fn socket_path(&self, name: &str) -> Result<PathBuf> {
+        let mut components = Path::new(name).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(_)), None) => (),
+            _ => return Err(Error::new(libc::EINVAL)),
+        }
+
+        let path = self.path.join(name);
+        // Safe because sockaddr_un only contains primitive fields.
+        let addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        if path.as_os_str().len() >= addr.sun_path.len() {
+            return Err(Error::new(libc::ENAMETOOLONG));
+        }
+        Ok(path)
+    }
 }
 
 impl Drop for TempDir {
@@ -185,6 +315,41 @@ mod tests {
         assert!(path.starts_with("/tmp"));
     }
 
+    #[test]
+    fn test_runtime_scratch() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let runtime_dir = TempDir::new().unwrap();
+        let t = TempDir::runtime_scratch_in(Some(runtime_dir.as_path()), "scratch").unwrap();
+        let path = t.as_path();
+        assert!(path.is_dir());
+        assert_eq!(path.parent().unwrap(), runtime_dir.as_path());
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("scratch"));
+        let mode = fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // Fall back to the temporary directory when the runtime directory is missing.
+        let missing = runtime_dir.as_path().join("missing");
+        let t = TempDir::runtime_scratch_in(Some(&missing), "scratch").unwrap();
+        assert!(t.as_path().starts_with(temp_dir()));
+        let t = TempDir::runtime_scratch_in(None, "scratch").unwrap();
+        assert!(t.as_path().starts_with(temp_dir()));
+
+        assert_eq!(
+            TempDir::runtime_scratch_in(None, "a/b").err().unwrap(),
+            Error::new(libc::EINVAL)
+        );
+        assert!(TempDir::runtime_scratch("scratch")
+            .unwrap()
+            .as_path()
+            .is_dir());
+    }
+
     #[test]
     fn test_drop() {
         use std::mem::drop;
@@ -195,4 +360,72 @@ mod tests {
 
         assert!(!(path.exists()));
     }
+
+    #[test]
+    fn test_create_subdir() {
+        let t = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+        let root = t.as_path().to_owned();
+
+        let subdir = t.create_subdir(Path::new("a/b/c")).unwrap();
+        assert_eq!(subdir, root.join("a/b/c"));
+        assert!(subdir.is_dir());
+        fs::File::create(subdir.join("file")).unwrap();
+
+        // Creating an existing subdirectory is fine.
+        assert_eq!(
+            t.create_subdir(Path::new("./a/b")).unwrap(),
+            root.join("./a/b")
+        );
+
+        for escape in &["../a", "a/../../b", "/tmp/a"] {
+            assert_eq!(
+                t.create_subdir(Path::new(escape)).unwrap_err(),
+                Error::new(libc::EINVAL)
+            );
+        }
+
+        drop(t);
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn test_socket_path() {
+        let t = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+
+        let path = t.socket_path("test.sock").unwrap();
+        assert_eq!(path, t.as_path().join("test.sock"));
+        std::os::unix::net::UnixListener::bind(&path).unwrap();
+
+        let long_name = "s".repeat(108);
+        assert_eq!(
+            t.socket_path(&long_name).unwrap_err(),
+            Error::new(libc::ENAMETOOLONG)
+        );
+        for name in &["", "a/b", "..", "/tmp"] {
+            assert_eq!(t.socket_path(name).unwrap_err(), Error::new(libc::EINVAL));
+        }
+    }
+
+    #[test]
+    fn test_read_dir() {
+        let t = TempDir::new_with_prefix("/tmp/testdir").unwrap();
+        let path = t.as_path().to_owned();
+        fs::File::create(path.join("file1")).unwrap();
+        fs::File::create(path.join("file2")).unwrap();
+        fs::create_dir(path.join("dir")).unwrap();
+
+        let mut names: Vec<OsString> = t
+            .read_dir()
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["dir", "file1", "file2"]);
+
+        // An open iterator doesn't prevent the cleanup.
+        let entries = t.read_dir().unwrap();
+        drop(t);
+        assert!(!path.exists());
+        drop(entries);
+    }
 }
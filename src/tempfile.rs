@@ -26,14 +26,58 @@ use std::env::temp_dir;
 use std::ffi::{CString, OsStr};
 use std::fs;
 use std::fs::File;
+use std::io::{self, BufWriter};
 use std::os::unix::ffi::{OsStrExt, OsStringExt};
-use std::os::unix::io::FromRawFd;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::path::{Path, PathBuf};
+use std::ptr::null_mut;
 
-use libc;
+use libc::{self, c_int, c_uint};
 
 use crate::errno::{errno_result, Error, Result};
 
+// Reexport the seals that can be added to a memfd from libc.
+pub use libc::{F_SEAL_GROW, F_SEAL_SEAL, F_SEAL_SHRINK, F_SEAL_WRITE};
+
+/// The flags used for creating a memfd with [`TempFile::memfd`](struct.TempFile.html#method.memfd).
+#[derive(Clone, Copy, Debug)]
+pub struct MemfdFlags(c_uint);
+
+impl MemfdFlags {
+    /// Returns empty `MemfdFlags`.
+    pub fn empty() -> MemfdFlags {
+        MemfdFlags(0)
+    }
+
+    /// Closes the memfd on `exec` (`MFD_CLOEXEC`).
+    pub fn set_cloexec(self) -> MemfdFlags {
+        MemfdFlags(self.0 | libc::MFD_CLOEXEC)
+    }
+
+    /// Allows adding seals to the memfd (`MFD_ALLOW_SEALING`).
+    pub fn set_allow_sealing(self) -> MemfdFlags {
+        MemfdFlags(self.0 | libc::MFD_ALLOW_SEALING)
+    }
+
+    /// Returns the raw flags.
+    pub fn get_raw(&self) -> c_uint {
+        self.0
+    }
+}
+
+/// How the content of a file was cloned by
+/// [`TempFile::reflink_from`](struct.TempFile.html#method.reflink_from).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloneMethod {
+    /// The clone shares the extents of the source file (`FICLONE`), which is copied on write.
+    Reflink,
+    /// The content was copied by the kernel with `copy_file_range`.
+    CopyFileRange,
+    /// The content was copied by reading and writing it.
+    Copy,
+}
+
 /// Wrapper for working with temporary files.
 ///
 /// The file will be maintained for the lifetime of the `TempFile` object.
@@ -51,13 +95,45 @@ impl TempFile {
     /// random alphanumeric characters will be added to the end of this to form
     /// the filename.
     pub fn new_with_prefix<P: AsRef<OsStr>>(prefix: P) -> Result<TempFile> {
+        TempFile::new_with_prefix_and_flags(prefix, 0)
+    }
+
+    /// Creates the TempFile using a prefix, opening it with additional `flags`.
+    ///
+    /// The `flags` are OR-ed into the flags used for opening the file, e.g. `O_SYNC` for
+    /// making every write durable before it returns. With `O_DIRECT`, the buffers, offsets
+    /// and lengths of all the I/O operations on the file must be aligned to the logical
+    /// block size of the underlying device (usually 512 bytes or 4 KiB), otherwise they
+    /// fail with `EINVAL`; some filesystems (e.g. tmpfs) don't support `O_DIRECT` at all,
+    /// in which case creating the file fails.
+    ///
+    /// # Arguments
+    ///
+    /// `prefix`: The path and filename where to create the temporary file. Six
+    /// random alphanumeric characters will be added to the end of this to form
+    /// the filename.
+    /// `flags`: The additional flags, as accepted by
+    /// [`mkostemp`](http://man7.org/linux/man-pages/man3/mkostemp.3.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate libc;
+    /// extern crate vmm_sys_util;
+    /// use std::io::Write;
+    /// use vmm_sys_util::tempfile::TempFile;
+    ///
+    /// let t = TempFile::new_with_prefix_and_flags("/tmp/synced", libc::O_SYNC).unwrap();
+    /// t.as_file().write_all(b"hello world").unwrap();
+    /// ```
+    pub fn new_with_prefix_and_flags<P: AsRef<OsStr>>(prefix: P, flags: c_int) -> Result<TempFile> {
         let mut os_fname = prefix.as_ref().to_os_string();
         os_fname.push("XXXXXX");
 
         let raw_fname = CString::new(os_fname.into_vec()).unwrap().into_raw();
 
         // Safe because I'm sure `raw_fname` is a valid CString.
-        let fd = unsafe { libc::mkstemp(raw_fname) };
+        let fd = unsafe { libc::mkostemp(raw_fname, flags) };
 
         // The `raw_fname` is the same as `os_fname` (which is `prefix` with X's
         // appended). The X's are documented as "six characters" which seem to
@@ -95,6 +171,180 @@ impl TempFile {
         Ok(temp_file)
     }
 
+    /// Creates the TempFile next to `target`, so that it can be atomically renamed over it.
+    ///
+    /// The temporary file is created in the parent directory of `target`, and thus on the
+    /// same filesystem, so renaming it to `target` cannot fail with `EXDEV`.
+    ///
+    /// # Arguments
+    ///
+    /// `target`: The path the temporary file is meant to be renamed to. It does not need
+    /// to exist, but its parent directory does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::io::Write;
+    /// use vmm_sys_util::tempdir::TempDir;
+    /// use vmm_sys_util::tempfile::TempFile;
+    ///
+    /// let dir = TempDir::new().unwrap();
+    /// let target = dir.as_path().join("config");
+    /// let t = TempFile::new_in_same_fs_as(&target).unwrap();
+    /// t.as_file().write_all(b"hello world").unwrap();
+    /// t.as_file().sync_all().unwrap();
+    /// fs::rename(t.as_path(), &target).unwrap();
+    /// ```
+    pub fn new_in_same_fs_as(target: &Path) -> Result<Self> {
+        let parent = match target.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let parent = parent.canonicalize().map_err(Error::from)?;
+        TempFile::new_in(parent.as_path())
+    }
+
+    /// Creates an anonymous, memory backed TempFile with
+    /// [`memfd_create`](http://man7.org/linux/man-pages/man2/memfd_create.2.html).
+    ///
+    /// Unlike the other temporary files, a memfd doesn't live on a filesystem and is freed
+    /// once all the references to it are closed, so it can be shared with another process
+    /// (e.g. a vhost-user backend) by passing its file descriptor. Thus it has no path:
+    /// [`as_path`](struct.TempFile.html#method.as_path) returns an empty path and
+    /// [`remove`](struct.TempFile.html#method.remove) fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: the name of the memfd, which is only used for debugging purposes.
+    /// * `flags`: the flags used for creating the memfd.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmm_sys_util::tempfile::{MemfdFlags, TempFile, F_SEAL_GROW};
+    ///
+    /// let flags = MemfdFlags::empty().set_cloexec().set_allow_sealing();
+    /// let t = TempFile::memfd("guest_memory", flags).unwrap();
+    /// t.as_file().set_len(4096).unwrap();
+    /// t.add_seals(F_SEAL_GROW).unwrap();
+    /// assert!(t.as_file().set_len(8192).is_err());
+    /// ```
+    pub fn memfd(name: &str, flags: MemfdFlags) -> Result<Self> {
+        let name = CString::new(name).map_err(|_| Error::new(libc::EINVAL))?;
+
+        // Safe because `name` is a valid CString and we check the return value.
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), flags.get_raw()) };
+        if fd < 0 {
+            return errno_result();
+        }
+
+        // Safe because we checked `fd` and we uniquely own the file descriptor.
+        let file = unsafe { File::from_raw_fd(fd) };
+
+        Ok(TempFile {
+            path: PathBuf::new(),
+            file,
+        })
+    }
+
+    /// Creates a TempFile holding a copy of the content of `src`, as fast as the filesystem
+    /// allows.
+    ///
+    /// The temporary file is created next to `src`, as with
+    /// [`new_in_same_fs_as`](struct.TempFile.html#method.new_in_same_fs_as). On filesystems
+    /// supporting it (e.g. btrfs or XFS), the file is a reflink of `src`, so it is created
+    /// instantly and only takes up space when either file is modified, which suits
+    /// snapshotting disk images. Otherwise the content is copied with
+    /// [`copy_file_range`](http://man7.org/linux/man-pages/man2/copy_file_range.2.html), or
+    /// by reading and writing it if that is not supported either.
+    ///
+    /// On success, returns the TempFile along with the method used for cloning `src`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src`: the path of the file to be cloned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    /// use vmm_sys_util::tempdir::TempDir;
+    /// use vmm_sys_util::tempfile::TempFile;
+    ///
+    /// let dir = TempDir::new().unwrap();
+    /// let image = dir.as_path().join("disk.img");
+    /// fs::write(&image, b"hello world").unwrap();
+    ///
+    /// let (snapshot, _) = TempFile::reflink_from(&image).unwrap();
+    /// assert_eq!(fs::read(snapshot.as_path()).unwrap(), b"hello world");
+    /// ```
+    pub fn reflink_from(src: &Path) -> Result<(Self, CloneMethod)> {
+        let mut src_file = File::open(src).map_err(Error::from)?;
+        let temp_file = TempFile::new_in_same_fs_as(src)?;
+        let src_fd = src_file.as_raw_fd();
+        let dst_fd = temp_file.file.as_raw_fd();
+
+        // Safe because this doesn't modify any memory and we check the return value.
+        if unsafe { libc::ioctl(dst_fd, libc::FICLONE, src_fd) } == 0 {
+            return Ok((temp_file, CloneMethod::Reflink));
+        }
+
+        let mut method = CloneMethod::CopyFileRange;
+        loop {
+            // Safe because this doesn't modify any memory and we check the return value. Null
+            // offsets mean that the file offsets are used and updated.
+            let ret = unsafe {
+                libc::copy_file_range(src_fd, null_mut(), dst_fd, null_mut(), 1 << 30, 0)
+            };
+            if ret == 0 {
+                break;
+            }
+            if ret < 0 {
+                let err = Error::last();
+                match err.errno() {
+                    // Nothing was copied yet, since the first call is the one that fails when
+                    // copy_file_range is not supported.
+                    libc::ENOSYS | libc::EXDEV | libc::EINVAL | libc::EOPNOTSUPP => {
+                        method = CloneMethod::Copy;
+                        break;
+                    }
+                    _ => return Err(err),
+                }
+            }
+        }
+
+        if method == CloneMethod::Copy {
+            io::copy(&mut src_file, &mut &temp_file.file).map_err(Error::from)?;
+        }
+        Ok((temp_file, method))
+    }
+
+    /// Adds `seals` to the TempFile, which must be a memfd created with sealing allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `seals`: a combination of `F_SEAL_SEAL`, `F_SEAL_SHRINK`, `F_SEAL_GROW` and
+    ///   `F_SEAL_WRITE`.
+    pub fn add_seals(&self, seals: c_int) -> Result<()> {
+        // Safe because this doesn't modify any memory and we check the return value.
+        let ret = unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_ADD_SEALS, seals) };
+        if ret < 0 {
+            return errno_result();
+        }
+        Ok(())
+    }
+
+    /// Returns the seals of the TempFile.
+    pub fn get_seals(&self) -> Result<c_int> {
+        // Safe because this doesn't modify any memory and we check the return value.
+        let ret = unsafe { libc::fcntl(self.file.as_raw_fd(), libc::F_GET_SEALS) };
+        if ret < 0 {
+            return errno_result();
+        }
+        Ok(ret)
+    }
+
     /// Creates the TempFile.
     ///
     /// Creates a temporary file inside `$TMPDIR` if set, otherwise inside `/tmp`.
@@ -114,6 +364,45 @@ impl TempFile {
         fs::remove_file(&self.path).map_err(Error::from)
     }
 
+    /// Overwrites the content of the temporary file with zeroes before removing it.
+    ///
+    /// This is meant for temporary files holding secrets (e.g. keys or guest memory dumps),
+    /// whose content would otherwise stay on the disk after the file is removed. The zeroes
+    /// are synced to the disk before the file is truncated and removed. This is best-effort:
+    /// on copy-on-write or log-structured filesystems, with data journaling, or on flash
+    /// storage, overwriting the file doesn't overwrite the blocks that held its content.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use vmm_sys_util::tempfile::TempFile;
+    ///
+    /// let t = TempFile::new_with_prefix("/tmp/testfile").unwrap();
+    /// let path = t.as_path().to_owned();
+    /// t.as_file().write_all(b"secret").unwrap();
+    /// t.secure_delete().unwrap();
+    /// assert!(!path.exists());
+    /// ```
+    pub fn secure_delete(mut self) -> Result<()> {
+        const CHUNK_LEN: u64 = 64 * 1024;
+
+        let len = self.file.metadata().map_err(Error::from)?.len();
+        let zeroes = vec![0u8; CHUNK_LEN as usize];
+        let mut offset = 0;
+        while offset < len {
+            let chunk_len = std::cmp::min(CHUNK_LEN, len - offset) as usize;
+            self.file
+                .write_all_at(&zeroes[..chunk_len], offset)
+                .map_err(Error::from)?;
+            offset += chunk_len as u64;
+        }
+        self.file.sync_all().map_err(Error::from)?;
+        self.file.set_len(0).map_err(Error::from)?;
+        self.file.sync_all().map_err(Error::from)?;
+        self.remove()
+    }
+
     /// Returns the path to the tempfile if it is currently valid
     pub fn as_path(&self) -> &Path {
         &self.path
@@ -123,6 +412,29 @@ impl TempFile {
     pub fn as_file(&self) -> &File {
         &self.file
     }
+
+    /// Returns a buffered writer over a duplicate of the file descriptor.
+    ///
+    /// The writer and the `TempFile` share the file offset, but data written
+    /// through the writer only reaches the file when it is flushed, so flush
+    /// (or drop) it before syncing the file or reading it back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    /// use vmm_sys_util::tempfile::TempFile;
+    ///
+    /// let t = TempFile::new_with_prefix("/tmp/testfile").unwrap();
+    /// let mut writer = t.buffered_writer().unwrap();
+    /// writer.write_all(b"hello world").unwrap();
+    /// writer.flush().unwrap();
+    /// t.as_file().sync_all().unwrap();
+    /// ```
+    pub fn buffered_writer(&self) -> Result<BufWriter<File>> {
+        let file = self.file.try_clone().map_err(Error::from)?;
+        Ok(BufWriter::new(file))
+    }
 }
 
 impl Drop for TempFile {
@@ -134,7 +446,8 @@ impl Drop for TempFile {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
+    use crate::tempdir::TempDir;
+    use std::io::{Read, Seek, SeekFrom, Write};
 
     #[test]
     fn test_create_file_with_prefix() {
@@ -168,6 +481,24 @@ mod tests {
         assert_eq!(f.metadata().unwrap().len(), 11);
     }
 
+    #[test]
+    fn test_create_file_with_prefix_and_flags() {
+        let t = TempFile::new_with_prefix_and_flags("/tmp/sync", libc::O_SYNC).unwrap();
+        assert!(t.as_path().is_file());
+        // Safe because this doesn't modify any memory.
+        let flags = unsafe { libc::fcntl(t.as_file().as_raw_fd(), libc::F_GETFL) };
+        assert_ne!(flags & libc::O_SYNC, 0);
+
+        let mut f = t.as_file();
+        f.write_all(b"hello world").unwrap();
+        assert_eq!(f.metadata().unwrap().len(), 11);
+
+        let t = TempFile::new_with_prefix("/tmp/nosync").unwrap();
+        // Safe because this doesn't modify any memory.
+        let flags = unsafe { libc::fcntl(t.as_file().as_raw_fd(), libc::F_GETFL) };
+        assert_eq!(flags & libc::O_SYNC, 0);
+    }
+
     #[test]
     fn test_create_file_new() {
         let t = TempFile::new().unwrap();
@@ -195,6 +526,89 @@ mod tests {
         assert!(path.starts_with("/tmp/"));
     }
 
+    #[test]
+    fn test_create_file_new_in_same_fs_as() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.as_path().join("target");
+        let t = TempFile::new_in_same_fs_as(&target).unwrap();
+        assert_eq!(
+            t.as_path().parent().unwrap(),
+            dir.as_path().canonicalize().unwrap()
+        );
+
+        t.as_file().write_all(b"hello world").unwrap();
+        fs::rename(t.as_path(), &target).unwrap();
+        assert!(!t.as_path().exists());
+        assert_eq!(fs::read(&target).unwrap(), b"hello world");
+
+        // The parent directory must exist.
+        assert!(TempFile::new_in_same_fs_as(&target.join("missing").join("target")).is_err());
+    }
+
+    #[test]
+    fn test_reflink_from() {
+        let dir = TempDir::new().unwrap();
+        let src = dir.as_path().join("image");
+        let data: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+        fs::write(&src, &data).unwrap();
+
+        let (t, method) = TempFile::reflink_from(&src).unwrap();
+        assert_eq!(
+            t.as_path().parent().unwrap(),
+            dir.as_path().canonicalize().unwrap()
+        );
+        assert_eq!(fs::read(t.as_path()).unwrap(), data);
+
+        if method == CloneMethod::Reflink {
+            // Writing to the clone doesn't change the source.
+            let mut f = t.as_file();
+            f.write_all(b"changed").unwrap();
+            assert_eq!(fs::read(&src).unwrap(), data);
+        }
+
+        // Empty files are cloned too.
+        let empty = dir.as_path().join("empty");
+        fs::write(&empty, b"").unwrap();
+        let (t, _) = TempFile::reflink_from(&empty).unwrap();
+        assert_eq!(t.as_file().metadata().unwrap().len(), 0);
+
+        assert!(TempFile::reflink_from(&dir.as_path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_memfd() {
+        let flags = MemfdFlags::empty().set_cloexec().set_allow_sealing();
+        let t = TempFile::memfd("test_memfd", flags).unwrap();
+        assert_eq!(t.as_path(), Path::new(""));
+        assert_eq!(t.get_seals().unwrap(), 0);
+
+        let mut f = t.as_file();
+        f.set_len(4096).unwrap();
+        f.write_all(b"hello world").unwrap();
+        t.add_seals(F_SEAL_WRITE | F_SEAL_GROW | F_SEAL_SHRINK)
+            .unwrap();
+        assert_eq!(
+            t.get_seals().unwrap(),
+            F_SEAL_WRITE | F_SEAL_GROW | F_SEAL_SHRINK
+        );
+        assert!(f.write_all(b"hello world").is_err());
+        assert!(f.set_len(8192).is_err());
+
+        let mut contents = [0u8; 11];
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_exact(&mut contents).unwrap();
+        assert_eq!(&contents, b"hello world");
+        assert_eq!(f.metadata().unwrap().len(), 4096);
+
+        // Seals can't be added without `MFD_ALLOW_SEALING`.
+        let t = TempFile::memfd("test_memfd", MemfdFlags::empty()).unwrap();
+        assert_eq!(
+            t.add_seals(F_SEAL_WRITE).unwrap_err(),
+            Error::new(libc::EPERM)
+        );
+        assert!(TempFile::memfd("test\0memfd", MemfdFlags::empty()).is_err());
+    }
+
     #[test]
     fn test_remove_file() {
         let mut t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
@@ -209,6 +623,32 @@ mod tests {
         assert!(t.remove().is_err());
     }
 
+    #[test]
+    fn test_secure_delete() {
+        let t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
+        let path = t.as_path().to_owned();
+        // Keep a handle on the file, to check that it was truncated.
+        let mut f = t.as_file().try_clone().unwrap();
+        let secret = vec![0xa5u8; 200 * 1024];
+        f.write_all(&secret).unwrap();
+
+        t.secure_delete().unwrap();
+        assert!(!path.exists());
+        assert_eq!(f.metadata().unwrap().len(), 0);
+
+        // An already truncated file is removed too.
+        let t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
+        let path = t.as_path().to_owned();
+        t.secure_delete().unwrap();
+        assert!(!path.exists());
+
+        // The removal fails if the file was already removed.
+        let mut t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
+        t.as_file().write_all(b"secret").unwrap();
+        t.remove().unwrap();
+        assert!(t.secure_delete().is_err());
+    }
+
     #[test]
     fn test_drop_file() {
         let t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
@@ -217,4 +657,24 @@ mod tests {
         drop(t);
         assert!(!path.exists());
     }
+
+    #[test]
+    fn test_buffered_writer() {
+        let t = TempFile::new_with_prefix("/tmp/asdf").unwrap();
+        let mut writer = t.buffered_writer().unwrap();
+        writer.write_all(b"hello ").unwrap();
+        writer.write_all(b"world").unwrap();
+        // Nothing reached the file yet.
+        assert_eq!(t.as_file().metadata().unwrap().len(), 0);
+
+        writer.flush().unwrap();
+        drop(writer);
+        t.as_file().sync_all().unwrap();
+
+        let mut contents = String::new();
+        let mut f = t.as_file();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello world");
+    }
 }
@@ -8,34 +8,118 @@
 
 //! Trait for working with [`termios`](http://man7.org/linux/man-pages/man3/termios.3.html).
 
+use std::cmp::min;
 use std::io::StdinLock;
 use std::mem::zeroed;
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::str;
+use std::time::Duration;
 
 use libc::{
-    c_int, fcntl, isatty, read, tcgetattr, tcsetattr, termios, ECHO, F_GETFL, F_SETFL, ICANON,
-    ISIG, O_NONBLOCK, STDIN_FILENO, TCSANOW,
+    c_int, fcntl, isatty, read, ECHO, F_GETFL, F_SETFL, ICANON, ISIG, O_NONBLOCK, STDIN_FILENO,
+    STDOUT_FILENO,
 };
 
 use crate::errno::{errno_result, Result};
+use crate::poll::{wait_readable, Deadline};
+
+// Reexport the termios structure and the `tcsetattr` actions from libc.
+pub use libc::{termios, TCSADRAIN, TCSAFLUSH, TCSANOW};
+
+/// Check whether `fd` refers to a terminal.
+///
+/// This is a safe wrapper over
+/// [`isatty`](http://man7.org/linux/man-pages/man3/isatty.3.html). An invalid file
+/// descriptor is not a terminal.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor to check.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::fs::File;
+/// # use std::os::unix::io::AsRawFd;
+/// use vmm_sys_util::terminal::is_tty;
+///
+/// let file = File::open("/dev/zero").unwrap();
+/// assert!(!is_tty(file.as_raw_fd()));
+/// ```
+pub fn is_tty(fd: RawFd) -> bool {
+    // Safe because isatty doesn't access any memory and we check the return value.
+    unsafe { isatty(fd) == 1 }
+}
+
+/// Check whether the standard input is a terminal.
+///
+/// This lets a tool decide whether to enter raw mode at all, since the standard
+/// input may as well be a pipe or a file.
+pub fn stdin_is_tty() -> bool {
+    is_tty(STDIN_FILENO)
+}
+
+/// Check whether the standard output is a terminal.
+pub fn stdout_is_tty() -> bool {
+    is_tty(STDOUT_FILENO)
+}
+
+/// Get the terminal attributes of `fd`.
+///
+/// This is a safe wrapper over
+/// [`tcgetattr`](http://man7.org/linux/man-pages/man3/tcgetattr.3.html), which works
+/// on any terminal fd (e.g. a pseudoterminal) rather than only on the standard input.
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor of the terminal.
+pub fn tcgetattr(fd: &dyn AsRawFd) -> Result<termios> {
+    // Safe because termios gets totally overwritten by tcgetattr and we check the return result.
+    let mut termios: termios = unsafe { zeroed() };
+    let ret = unsafe { libc::tcgetattr(fd.as_raw_fd(), &mut termios) };
+    if ret < 0 {
+        return errno_result();
+    }
+    Ok(termios)
+}
+
+/// Set the terminal attributes of `fd`.
+///
+/// This is a safe wrapper over
+/// [`tcsetattr`](http://man7.org/linux/man-pages/man3/tcsetattr.3.html).
+///
+/// # Arguments
+///
+/// * `fd`: the file descriptor of the terminal.
+/// * `optional_actions`: when the change takes effect, one of `TCSANOW`, `TCSADRAIN` or
+///   `TCSAFLUSH`.
+/// * `termios`: the attributes to set.
+pub fn tcsetattr(fd: &dyn AsRawFd, optional_actions: c_int, termios: &termios) -> Result<()> {
+    // Safe because the syscall will only read the extent of termios and we check the return result.
+    let ret = unsafe { libc::tcsetattr(fd.as_raw_fd(), optional_actions, termios) };
+    if ret < 0 {
+        return errno_result();
+    }
+    Ok(())
+}
 
 fn modify_mode<F: FnOnce(&mut termios)>(fd: RawFd, f: F) -> Result<()> {
-    // Safe because we check the return value of isatty.
-    if unsafe { isatty(fd) } != 1 {
+    if !is_tty(fd) {
         return Ok(());
     }
 
     // The following pair are safe because termios gets totally overwritten by tcgetattr and we
     // check the return result.
     let mut termios: termios = unsafe { zeroed() };
-    let ret = unsafe { tcgetattr(fd, &mut termios as *mut _) };
+    let ret = unsafe { libc::tcgetattr(fd, &mut termios as *mut _) };
     if ret < 0 {
         return errno_result();
     }
     let mut new_termios = termios;
     f(&mut new_termios);
     // Safe because the syscall will only read the extent of termios and we check the return result.
-    let ret = unsafe { tcsetattr(fd, TCSANOW, &new_termios as *const _) };
+    let ret = unsafe { libc::tcsetattr(fd, TCSANOW, &new_termios as *const _) };
     if ret < 0 {
         return errno_result();
     }
@@ -43,6 +127,41 @@ fn modify_mode<F: FnOnce(&mut termios)>(fd: RawFd, f: F) -> Result<()> {
     Ok(())
 }
 
+// Reset `termios` to sane, cooked mode settings, like `stty sane` does.
+fn make_sane(termios: &mut termios) {
+    use libc::{
+        BRKINT, CREAD, ECHOCTL, ECHOE, ECHOK, ECHOKE, ECHONL, ECHOPRT, ICRNL, IEXTEN, IGNBRK,
+        IGNCR, IMAXBEL, INLCR, IXANY, IXOFF, IXON, NOFLSH, OCRNL, ONLCR, ONLRET, ONOCR, OPOST,
+        TOSTOP, VEOF, VEOL, VERASE, VINTR, VKILL, VMIN, VQUIT, VSTART, VSTOP, VSUSP, VTIME,
+    };
+
+    termios.c_iflag &= !(IGNBRK | INLCR | IGNCR | IXOFF | IXANY);
+    termios.c_iflag |= BRKINT | ICRNL | IXON | IMAXBEL;
+    termios.c_oflag &= !(OCRNL | ONOCR | ONLRET);
+    termios.c_oflag |= OPOST | ONLCR;
+    termios.c_cflag |= CREAD;
+    termios.c_lflag &= !(ECHONL | NOFLSH | TOSTOP | ECHOPRT);
+    termios.c_lflag |= ISIG | ICANON | IEXTEN | ECHO | ECHOE | ECHOK | ECHOCTL | ECHOKE;
+
+    // The default control characters: ^C, ^\, DEL, ^U, ^D, ^Q, ^S and ^Z.
+    let control_chars = [
+        (VINTR, 0x03),
+        (VQUIT, 0x1c),
+        (VERASE, 0x7f),
+        (VKILL, 0x15),
+        (VEOF, 0x04),
+        (VEOL, 0),
+        (VSTART, 0x11),
+        (VSTOP, 0x13),
+        (VSUSP, 0x1a),
+        (VMIN, 1),
+        (VTIME, 0),
+    ];
+    for &(index, value) in control_chars.iter() {
+        termios.c_cc[index] = value;
+    }
+}
+
 fn get_flags(fd: RawFd) -> Result<c_int> {
     // Safe because no third parameter is expected and we check the return result.
     let ret = unsafe { fcntl(fd, F_GETFL) };
@@ -71,6 +190,14 @@ pub unsafe trait Terminal {
     /// Get the file descriptor of the TTY.
     fn tty_fd(&self) -> RawFd;
 
+    /// Check whether the file descriptor of this terminal actually refers to a TTY.
+    ///
+    /// When it doesn't, e.g. because the standard input is a pipe, the mode changes
+    /// of this trait don't do anything.
+    fn is_tty(&self) -> bool {
+        is_tty(self.tty_fd())
+    }
+
     /// Set this terminal to canonical mode (`ICANON | ECHO | ISIG`).
     ///
     /// Enable canonical mode with `ISIG` that generates signal when receiving
@@ -81,6 +208,17 @@ pub unsafe trait Terminal {
         modify_mode(self.tty_fd(), |t| t.c_lflag |= ICANON | ECHO | ISIG)
     }
 
+    /// Reset this terminal to sane, cooked mode settings.
+    ///
+    /// Unlike [`set_canon_mode`](trait.Terminal.html#method.set_canon_mode), which only
+    /// sets the canonical mode flags, this also resets the other input, output and local
+    /// flags, as well as the control characters (e.g. INTR is ^C), to the usual defaults.
+    /// This is the equivalent of `stty sane`, for recovering a terminal left in an unknown
+    /// mode, e.g. when its original attributes weren't saved.
+    fn set_canonical(&self) -> Result<()> {
+        modify_mode(self.tty_fd(), make_sane)
+    }
+
     /// Set this terminal to raw mode.
     ///
     /// Unset the canonical mode with (`!(ICANON | ECHO | ISIG)`) which means
@@ -149,13 +287,238 @@ unsafe impl<'a> Terminal for StdinLock<'a> {
     }
 }
 
+/// A key read from a terminal by [`KeyReader`](struct.KeyReader.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// A printable character.
+    Char(char),
+    /// A character typed while holding Ctrl, e.g. `Ctrl('c')` for ^C.
+    Ctrl(char),
+    /// The Enter key, sent as either CR or LF.
+    Enter,
+    /// The Tab key.
+    Tab,
+    /// The Backspace key, sent as either DEL or BS.
+    Backspace,
+    /// The Escape key.
+    Esc,
+    /// The up arrow key.
+    Up,
+    /// The down arrow key.
+    Down,
+    /// The right arrow key.
+    Right,
+    /// The left arrow key.
+    Left,
+    /// The Home key.
+    Home,
+    /// The End key.
+    End,
+    /// The Page Up key.
+    PageUp,
+    /// The Page Down key.
+    PageDown,
+    /// The Insert key.
+    Insert,
+    /// The Delete key.
+    Delete,
+    /// A function key, from `F(1)` to `F(12)`.
+    F(u8),
+    /// A sequence of bytes that isn't recognized as any key.
+    Unknown(Vec<u8>),
+}
+
+const ESC: u8 = 0x1b;
+
+// How long to wait for the rest of an escape sequence after an ESC byte, before deciding
+// that it was the Escape key.
+const ESC_DELAY: Duration = Duration::from_millis(25);
+
+// Returns the key at the start of `buf` along with its length in bytes, or `None` if `buf`
+// only holds the start of a key.
+fn decode_key(buf: &[u8]) -> Option<(Key, usize)> {
+    let first = *buf.first()?;
+    let key = match first {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        ESC => return decode_escape(buf),
+        0x00 => Key::Ctrl('@'),
+        0x01..=0x1a => Key::Ctrl((b'a' + first - 1) as char),
+        0x1c..=0x1f => Key::Ctrl((first + 0x40) as char),
+        0x20..=0x7e => Key::Char(first as char),
+        _ => return decode_utf8(buf),
+    };
+    Some((key, 1))
+}
+
+fn decode_utf8(buf: &[u8]) -> Option<(Key, usize)> {
+    let len = match buf[0] {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return Some((Key::Unknown(vec![buf[0]]), 1)),
+    };
+    if buf.len() < len {
+        return None;
+    }
+    match str::from_utf8(&buf[..len]) {
+        Ok(s) => s.chars().next().map(|c| (Key::Char(c), len)),
+        Err(_) => Some((Key::Unknown(vec![buf[0]]), 1)),
+    }
+}
+
+fn decode_escape(buf: &[u8]) -> Option<(Key, usize)> {
+    match buf.get(1) {
+        // A lone ESC is handled by the reader, which knows whether more bytes are coming.
+        None => None,
+        Some(b'[') => decode_csi(buf),
+        Some(b'O') => {
+            let key = match *buf.get(2)? {
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                b @ b'P'..=b'S' => Key::F(b - b'P' + 1),
+                _ => Key::Unknown(buf[..3].to_vec()),
+            };
+            Some((key, 3))
+        }
+        // ESC isn't the start of a sequence here, so it is a key of its own.
+        Some(_) => Some((Key::Esc, 1)),
+    }
+}
+
+// Decodes a control sequence, i.e. ESC [ followed by parameter bytes and a final byte.
+fn decode_csi(buf: &[u8]) -> Option<(Key, usize)> {
+    let end = 2 + buf[2..].iter().position(|b| (0x40..=0x7e).contains(b))?;
+    let len = end + 1;
+    let params = &buf[2..end];
+    // Modifiers (e.g. "1;5" for Ctrl) come after the first parameter and aren't reported.
+    let first_param = params
+        .split(|b| *b == b';')
+        .next()
+        .and_then(|p| str::from_utf8(p).ok())
+        .and_then(|p| p.parse::<u8>().ok());
+
+    let key = match (buf[end], first_param) {
+        (b'A', _) => Key::Up,
+        (b'B', _) => Key::Down,
+        (b'C', _) => Key::Right,
+        (b'D', _) => Key::Left,
+        (b'H', _) => Key::Home,
+        (b'F', _) => Key::End,
+        (b'~', Some(1)) | (b'~', Some(7)) => Key::Home,
+        (b'~', Some(2)) => Key::Insert,
+        (b'~', Some(3)) => Key::Delete,
+        (b'~', Some(4)) | (b'~', Some(8)) => Key::End,
+        (b'~', Some(5)) => Key::PageUp,
+        (b'~', Some(6)) => Key::PageDown,
+        (b'~', Some(n @ 11..=15)) => Key::F(n - 10),
+        (b'~', Some(n @ 17..=21)) => Key::F(n - 11),
+        (b'~', Some(n @ 23..=24)) => Key::F(n - 12),
+        _ => Key::Unknown(buf[..len].to_vec()),
+    };
+    Some((key, len))
+}
+
+/// Reads single keys from a [`Terminal`](trait.Terminal.html), decoding the escape
+/// sequences sent for special keys (e.g. arrows or function keys).
+///
+/// The terminal is expected to be in raw mode (see
+/// [`set_raw_mode`](trait.Terminal.html#method.set_raw_mode)), so that keys are available
+/// as soon as they are typed. The reader buffers the bytes read past the current key, as
+/// well as incomplete escape sequences, for the next calls, so the same reader must be used
+/// for all the reads from a terminal.
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// # use std::io;
+/// # use std::time::Duration;
+/// use vmm_sys_util::terminal::{KeyReader, Terminal};
+///
+/// let stdin_handle = io::stdin();
+/// let stdin = stdin_handle.lock();
+/// let mut reader = KeyReader::new();
+/// if stdin.is_tty() {
+///     stdin.set_raw_mode().unwrap();
+///     let _key = reader.read_key(&stdin, Some(Duration::from_millis(1))).unwrap();
+///     stdin.set_canon_mode().unwrap();
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct KeyReader {
+    pending: Vec<u8>,
+}
+
+impl KeyReader {
+    /// Create a new `KeyReader` with nothing buffered.
+    pub fn new() -> Self {
+        KeyReader::default()
+    }
+
+    /// Read a key from `terminal`, waiting at most `timeout` for it if it is not `None`.
+    ///
+    /// Returns `None` if no whole key was read before the timeout, or if the terminal reports
+    /// the end of file. An ESC byte that isn't followed by the rest of an escape sequence
+    /// shortly after is returned as [`Key::Esc`](enum.Key.html#variant.Esc).
+    ///
+    /// # Arguments
+    ///
+    /// * `terminal`: the terminal to read from.
+    /// * `timeout`: the maximum time to wait, rounded down to milliseconds. If `None`, wait
+    ///   for as long as it takes.
+    pub fn read_key<T: Terminal + ?Sized>(
+        &mut self,
+        terminal: &T,
+        timeout: Option<Duration>,
+    ) -> Result<Option<Key>> {
+        let deadline = timeout.map(Deadline::new);
+        loop {
+            if let Some((key, len)) = decode_key(&self.pending) {
+                self.pending.drain(..len);
+                return Ok(Some(key));
+            }
+
+            let remaining = deadline.map(|d| d.remaining().unwrap_or_default());
+            let lone_esc = self.pending == [ESC];
+            let wait = if lone_esc {
+                Some(remaining.map_or(ESC_DELAY, |r| min(r, ESC_DELAY)))
+            } else {
+                remaining
+            };
+            if !wait_readable(&terminal.tty_fd(), wait)? {
+                if lone_esc {
+                    self.pending.clear();
+                    return Ok(Some(Key::Esc));
+                }
+                return Ok(None);
+            }
+
+            let mut buf = [0u8; 32];
+            let count = terminal.read_raw(&mut buf)?;
+            if count == 0 {
+                return Ok(None);
+            }
+            self.pending.extend_from_slice(&buf[..count]);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::File;
-    use std::io;
-    use std::os::unix::io::AsRawFd;
+    use std::io::{self, Write};
+    use std::os::unix::io::FromRawFd;
     use std::path::Path;
+    use std::ptr::null_mut;
+
+    use crate::errno::Error;
 
     unsafe impl Terminal for File {
         fn tty_fd(&self) -> RawFd {
@@ -177,9 +540,210 @@ mod tests {
         assert!(stdin.read_raw(&mut out[..]).is_ok());
     }
 
+    #[test]
+    fn test_tcgetattr_tcsetattr() {
+        let mut master = 0;
+        let mut slave = 0;
+        // Safe because openpty only writes to the two fds and we check the return value.
+        let ret =
+            unsafe { libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), null_mut()) };
+        assert_eq!(ret, 0);
+        // Safe because we uniquely own the new fds.
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+
+        let mut attr = tcgetattr(&slave).unwrap();
+        assert_ne!(attr.c_lflag & ICANON, 0);
+        attr.c_lflag &= !(ICANON | ECHO | ISIG);
+        tcsetattr(&slave, TCSANOW, &attr).unwrap();
+        let attr = tcgetattr(&master).unwrap();
+        assert_eq!(attr.c_lflag & (ICANON | ECHO | ISIG), 0);
+
+        assert_eq!(
+            tcsetattr(&slave, -1, &attr).unwrap_err(),
+            Error::new(libc::EINVAL)
+        );
+        let file = File::open(Path::new("/dev/zero")).unwrap();
+        assert_eq!(tcgetattr(&file).unwrap_err(), Error::new(libc::ENOTTY));
+    }
+
+    #[test]
+    fn test_set_canonical() {
+        let mut master = 0;
+        let mut slave = 0;
+        // Safe because openpty only writes to the two fds and we check the return value.
+        let ret =
+            unsafe { libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), null_mut()) };
+        assert_eq!(ret, 0);
+        // Safe because we uniquely own the new fds.
+        let _master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+
+        // Mess up the terminal beyond what raw mode does.
+        slave.set_raw_mode().unwrap();
+        let mut attr = tcgetattr(&slave).unwrap();
+        attr.c_iflag &= !libc::ICRNL;
+        attr.c_oflag &= !libc::OPOST;
+        attr.c_cc[libc::VINTR] = b'x';
+        tcsetattr(&slave, TCSANOW, &attr).unwrap();
+
+        slave.set_canonical().unwrap();
+        let attr = tcgetattr(&slave).unwrap();
+        assert_eq!(attr.c_lflag & (ICANON | ECHO | ISIG), ICANON | ECHO | ISIG);
+        assert_ne!(attr.c_iflag & libc::ICRNL, 0);
+        assert_ne!(attr.c_oflag & libc::OPOST, 0);
+        assert_eq!(attr.c_cc[libc::VINTR], 0x03);
+        assert_eq!(attr.c_cc[libc::VERASE], 0x7f);
+
+        // This doesn't do anything for a non tty.
+        let file = File::open(Path::new("/dev/zero")).unwrap();
+        assert!(file.set_canonical().is_ok());
+    }
+
+    #[test]
+    fn test_is_tty() {
+        let mut master = 0;
+        let mut slave = 0;
+        // Safe because openpty only writes to the two fds and we check the return value.
+        let ret =
+            unsafe { libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), null_mut()) };
+        assert_eq!(ret, 0);
+        // Safe because we uniquely own the new fds.
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        assert!(is_tty(master.as_raw_fd()));
+        assert!(is_tty(slave.as_raw_fd()));
+
+        let mut fds = [0; 2];
+        // Safe because pipe only writes to the two fds and we check the return value.
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        // Safe because we uniquely own the new fds.
+        let read_end = unsafe { File::from_raw_fd(fds[0]) };
+        let write_end = unsafe { File::from_raw_fd(fds[1]) };
+        assert!(!is_tty(read_end.as_raw_fd()));
+        assert!(!is_tty(write_end.as_raw_fd()));
+
+        assert!(!is_tty(-1));
+        assert_eq!(stdin_is_tty(), is_tty(STDIN_FILENO));
+        assert_eq!(stdout_is_tty(), is_tty(STDOUT_FILENO));
+    }
+
+    #[test]
+    fn test_decode_key() {
+        let cases: &[(&[u8], Key)] = &[
+            (b"a", Key::Char('a')),
+            (b"~", Key::Char('~')),
+            ("é".as_bytes(), Key::Char('é')),
+            ("€".as_bytes(), Key::Char('€')),
+            (b"\r", Key::Enter),
+            (b"\n", Key::Enter),
+            (b"\t", Key::Tab),
+            (b"\x7f", Key::Backspace),
+            (b"\x03", Key::Ctrl('c')),
+            (b"\x1a", Key::Ctrl('z')),
+            (b"\x1c", Key::Ctrl('\\')),
+            (b"\x1b[A", Key::Up),
+            (b"\x1b[B", Key::Down),
+            (b"\x1b[C", Key::Right),
+            (b"\x1b[D", Key::Left),
+            (b"\x1b[1;5C", Key::Right),
+            (b"\x1bOH", Key::Home),
+            (b"\x1b[F", Key::End),
+            (b"\x1b[2~", Key::Insert),
+            (b"\x1b[3~", Key::Delete),
+            (b"\x1b[5~", Key::PageUp),
+            (b"\x1b[6~", Key::PageDown),
+            (b"\x1bOP", Key::F(1)),
+            (b"\x1bOS", Key::F(4)),
+            (b"\x1b[15~", Key::F(5)),
+            (b"\x1b[21~", Key::F(10)),
+            (b"\x1b[24~", Key::F(12)),
+            (b"\x1b[99~", Key::Unknown(b"\x1b[99~".to_vec())),
+            (b"\xff", Key::Unknown(vec![0xff])),
+        ];
+        for (bytes, key) in cases {
+            assert_eq!(decode_key(bytes), Some((key.clone(), bytes.len())));
+        }
+
+        // Only the first key is decoded.
+        assert_eq!(decode_key(b"\x1b[Ab"), Some((Key::Up, 3)));
+        assert_eq!(decode_key(b"\x1b\x1b[A"), Some((Key::Esc, 1)));
+        // Incomplete keys.
+        for bytes in &[
+            &b""[..],
+            b"\x1b",
+            b"\x1b[",
+            b"\x1b[1;5",
+            b"\x1bO",
+            &"é".as_bytes()[..1],
+        ] {
+            assert_eq!(decode_key(bytes), None);
+        }
+    }
+
+    #[test]
+    fn test_read_key() {
+        let mut master = 0;
+        let mut slave = 0;
+        // Safe because openpty only writes to the two fds and we check the return value.
+        let ret =
+            unsafe { libc::openpty(&mut master, &mut slave, null_mut(), null_mut(), null_mut()) };
+        assert_eq!(ret, 0);
+        // Safe because we uniquely own the new fds.
+        let mut master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        slave.set_raw_mode().unwrap();
+
+        let timeout = Some(Duration::from_millis(100));
+        let mut reader = KeyReader::new();
+        assert_eq!(
+            reader
+                .read_key(&slave, Some(Duration::from_millis(10)))
+                .unwrap(),
+            None
+        );
+
+        master.write_all("a\x03\x1b[Aé\r".as_bytes()).unwrap();
+        let expected = [
+            Key::Char('a'),
+            Key::Ctrl('c'),
+            Key::Up,
+            Key::Char('é'),
+            Key::Enter,
+        ];
+        for key in expected.iter() {
+            assert_eq!(
+                reader.read_key(&slave, timeout).unwrap().as_ref(),
+                Some(key)
+            );
+        }
+
+        // A partial escape sequence is kept until the rest of it is read.
+        master.write_all(b"\x1b[2").unwrap();
+        assert_eq!(
+            reader
+                .read_key(&slave, Some(Duration::from_millis(50)))
+                .unwrap(),
+            None
+        );
+        master.write_all(b"4~").unwrap();
+        assert_eq!(reader.read_key(&slave, timeout).unwrap(), Some(Key::F(12)));
+
+        // An ESC that isn't followed by anything is the Escape key.
+        master.write_all(b"\x1b").unwrap();
+        assert_eq!(reader.read_key(&slave, None).unwrap(), Some(Key::Esc));
+        assert_eq!(
+            reader
+                .read_key(&slave, Some(Duration::from_millis(0)))
+                .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn test_a_non_tty() {
         let file = File::open(Path::new("/dev/zero")).unwrap();
+        assert!(!file.is_tty());
         assert!(file.set_canon_mode().is_ok());
     }
 }
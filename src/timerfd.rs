@@ -7,19 +7,24 @@
 //! Structure and functions for working with
 //! [`timerfd`](http://man7.org/linux/man-pages/man2/timerfd_create.2.html).
 
+use std::cmp::max;
 use std::fs::File;
 use std::mem;
-use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
 use std::ptr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use libc::{self, timerfd_create, timerfd_gettime, timerfd_settime, CLOCK_MONOTONIC, TFD_CLOEXEC};
+use libc::{
+    self, clock_gettime, clockid_t, timerfd_create, timerfd_gettime, timerfd_settime,
+    CLOCK_BOOTTIME, CLOCK_MONOTONIC, TFD_CLOEXEC, TFD_TIMER_ABSTIME,
+};
 
 use crate::errno::{errno_result, Result};
+use crate::rand::Xorshift;
 
 /// A safe wrapper around a Linux
 /// [`timerfd`](http://man7.org/linux/man-pages/man2/timerfd_create.2.html).
-pub struct TimerFd(File);
+pub struct TimerFd(File, clockid_t);
 
 impl TimerFd {
     /// Create a new [`TimerFd`](struct.TimerFd.html).
@@ -28,14 +33,39 @@ impl TimerFd {
     /// change after system startup. The timer is initally disarmed and must be
     /// armed by calling [`reset`](fn.reset.html).
     pub fn new() -> Result<TimerFd> {
+        TimerFd::with_clock(CLOCK_MONOTONIC)
+    }
+
+    /// Create a new [`TimerFd`](struct.TimerFd.html) based on `CLOCK_BOOTTIME`.
+    ///
+    /// Unlike the clock used by [`new`](struct.TimerFd.html#method.new), this clock
+    /// keeps advancing while the system is suspended, so the timer also accounts for
+    /// the time spent asleep. The timer is initially disarmed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new_boottime().unwrap();
+    /// timer.reset(Duration::from_millis(100), None).unwrap();
+    /// assert_eq!(timer.wait().unwrap(), 1);
+    /// ```
+    pub fn new_boottime() -> Result<TimerFd> {
+        TimerFd::with_clock(CLOCK_BOOTTIME)
+    }
+
+    fn with_clock(clock: clockid_t) -> Result<TimerFd> {
         // Safe because this doesn't modify any memory and we check the return value.
-        let ret = unsafe { timerfd_create(CLOCK_MONOTONIC, TFD_CLOEXEC) };
+        let ret = unsafe { timerfd_create(clock, TFD_CLOEXEC) };
         if ret < 0 {
             return errno_result();
         }
 
         // Safe because we uniquely own the file descriptor.
-        Ok(TimerFd(unsafe { File::from_raw_fd(ret) }))
+        Ok(TimerFd(unsafe { File::from_raw_fd(ret) }, clock))
     }
 
     /// Arm the [`TimerFd`](struct.TimerFd.html).
@@ -87,6 +117,154 @@ impl TimerFd {
         Ok(())
     }
 
+    /// Arm the [`TimerFd`](struct.TimerFd.html) to expire once at `deadline`.
+    ///
+    /// The timer is armed with the time remaining until `deadline`, as with
+    /// [`reset`](struct.TimerFd.html#method.reset), so this suits code tracking its
+    /// deadlines as `Instant`s. A `deadline` that already passed makes the timer expire
+    /// immediately. Cancels any existing duration and repeating interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline`: the time of the expiration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::{Duration, Instant};
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new().unwrap();
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    ///
+    /// timer.arm_at(deadline).unwrap();
+    /// assert_eq!(timer.wait().unwrap(), 1);
+    /// assert!(Instant::now() >= deadline);
+    /// ```
+    pub fn arm_at(&mut self, deadline: Instant) -> Result<()> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // A zero expiration time would disarm the timer.
+        self.reset(max(remaining, Duration::new(0, 1)), None)
+    }
+
+    /// Arm the [`TimerFd`](struct.TimerFd.html) to expire at `first` and then
+    /// every `interval`.
+    ///
+    /// Unlike [`reset`](struct.TimerFd.html#method.reset), which counts the initial
+    /// expiration from the time of the call, this schedules every expiration at
+    /// `first + n * interval`, so the ticks of several timers (or of a timer
+    /// re-armed over and over) stay aligned to the same base. `first` is an
+    /// `Instant` because the timer uses the monotonic clock.
+    ///
+    /// If the reader falls behind, or `first` is already in the past, the missed
+    /// expirations are accumulated, so [`wait`](struct.TimerFd.html#method.wait)
+    /// returns a count greater than one.
+    ///
+    /// # Arguments
+    ///
+    /// * `first`: the time of the first expiration.
+    /// * `interval`: the period of the following expirations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::{Duration, Instant};
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new().unwrap();
+    /// let interval = Duration::from_millis(100);
+    ///
+    /// timer
+    ///     .set_periodic_absolute(Instant::now() + interval, interval)
+    ///     .unwrap();
+    /// assert_eq!(timer.wait().unwrap(), 1);
+    /// ```
+    pub fn set_periodic_absolute(&mut self, first: Instant, interval: Duration) -> Result<()> {
+        // Safe because we are zero-initializing a struct with only primitive member fields.
+        let mut now: libc::timespec = unsafe { mem::zeroed() };
+        // Safe because clock_gettime only modifies `now` and we check the return value.
+        let ret = unsafe { clock_gettime(self.1, &mut now) };
+        if ret < 0 {
+            return errno_result();
+        }
+
+        // `Instant` doesn't expose its value, so convert `first` using its distance
+        // from the current time of the timer's clock.
+        let now_instant = Instant::now();
+        let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+        let first = if first >= now_instant {
+            now + (first - now_instant)
+        } else {
+            now.checked_sub(now_instant - first).unwrap_or_default()
+        };
+        // A zero expiration time would disarm the timer.
+        let first = max(first, Duration::new(0, 1));
+
+        // Safe because we are zero-initializing a struct with only primitive member fields.
+        let mut spec: libc::itimerspec = unsafe { mem::zeroed() };
+        spec.it_value.tv_sec = first.as_secs() as libc::time_t;
+        spec.it_value.tv_nsec = libc::c_long::from(first.subsec_nanos() as i32);
+        spec.it_interval.tv_sec = interval.as_secs() as libc::time_t;
+        spec.it_interval.tv_nsec = libc::c_long::from(interval.subsec_nanos() as i32);
+
+        // Safe because this doesn't modify any memory and we check the return value.
+        let ret =
+            unsafe { timerfd_settime(self.as_raw_fd(), TFD_TIMER_ABSTIME, &spec, ptr::null_mut()) };
+        if ret < 0 {
+            return errno_result();
+        }
+
+        Ok(())
+    }
+
+    /// Arm the [`TimerFd`](struct.TimerFd.html) to expire every `interval`, starting
+    /// after `interval` plus a random offset in `[0, jitter)`.
+    ///
+    /// Timers armed at the same time with the same interval (e.g. by many VMs started
+    /// together) keep expiring together; the random offset spreads their expirations.
+    /// The jitter only delays the first expiration: the following ones are still exactly
+    /// `interval` apart. Cancels any existing duration and repeating interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval`: the period of the expirations.
+    /// * `jitter`: the upper bound, exclusive, of the random offset added to the first
+    ///   expiration. A zero `jitter` makes this the same as `reset(interval, Some(interval))`.
+    /// * `rng`: the generator of the random offset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::rand::Xorshift;
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new().unwrap();
+    /// let mut rng = Xorshift::new(42);
+    ///
+    /// timer
+    ///     .set_periodic_jittered(Duration::from_millis(10), Duration::from_millis(5), &mut rng)
+    ///     .unwrap();
+    /// assert_eq!(timer.wait().unwrap(), 1);
+    /// ```
+    pub fn set_periodic_jittered(
+        &mut self,
+        interval: Duration,
+        jitter: Duration,
+        rng: &mut Xorshift,
+    ) -> Result<()> {
+        let jitter_nanos = jitter.as_nanos().min(u128::from(u64::MAX)) as u64;
+        let offset = if jitter_nanos == 0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_nanos(rng.rand_range(0, jitter_nanos))
+        };
+        self.reset(interval + offset, Some(interval))
+    }
+
     /// Wait until the timer expires.
     ///
     /// The return value represents the number of times the timer has expired since
@@ -149,6 +327,44 @@ impl TimerFd {
     /// assert!(timer.is_armed().unwrap());
     /// ```
     pub fn is_armed(&self) -> Result<bool> {
+        let spec = self.get_time()?;
+        Ok(spec.it_value.tv_sec != 0 || spec.it_value.tv_nsec != 0)
+    }
+
+    /// Get the time remaining until the next expiration of the timer.
+    ///
+    /// Returns `Ok(None)` if the timer is disarmed. Note that a one-shot timer is
+    /// disarmed once it expires, even if the expiration was not consumed with
+    /// [`wait`](struct.TimerFd.html#method.wait), while for a repeating timer the
+    /// returned value is the time until the next period ends, which may be close
+    /// to zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new().unwrap();
+    /// assert!(timer.next_expiry().unwrap().is_none());
+    ///
+    /// let dur = Duration::from_secs(10);
+    /// timer.reset(dur, None).unwrap();
+    /// assert!(timer.next_expiry().unwrap().unwrap() <= dur);
+    /// ```
+    pub fn next_expiry(&self) -> Result<Option<Duration>> {
+        let spec = self.get_time()?;
+        if spec.it_value.tv_sec == 0 && spec.it_value.tv_nsec == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Duration::new(
+            spec.it_value.tv_sec as u64,
+            spec.it_value.tv_nsec as u32,
+        )))
+    }
+
+    fn get_time(&self) -> Result<libc::itimerspec> {
         // Safe because we are zero-initializing a struct with only primitive member fields.
         let mut spec: libc::itimerspec = unsafe { mem::zeroed() };
 
@@ -158,7 +374,7 @@ impl TimerFd {
             return errno_result();
         }
 
-        Ok(spec.it_value.tv_sec != 0 || spec.it_value.tv_nsec != 0)
+        Ok(spec)
     }
 
     /// Disarm the timer.
@@ -191,6 +407,57 @@ impl TimerFd {
 
         Ok(())
     }
+
+    /// Disarm the timer and return its previous setting.
+    ///
+    /// On success, returns the time that was remaining until the next expiration
+    /// and the repeating interval, if any, so that the timer can be paused and
+    /// later re-armed with [`reset`](struct.TimerFd.html#method.reset).
+    ///
+    /// The remaining time is zero if the timer was already disarmed, which is
+    /// also the case of a one-shot timer that already expired. Since `reset`
+    /// with a zero duration disarms the timer, such a setting must not be used
+    /// for re-arming it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate vmm_sys_util;
+    /// # use std::time::Duration;
+    /// use vmm_sys_util::timerfd::TimerFd;
+    ///
+    /// let mut timer = TimerFd::new().unwrap();
+    /// let interval = Duration::from_millis(100);
+    ///
+    /// timer.reset(interval, Some(interval)).unwrap();
+    /// let (remaining, interval) = timer.disarm().unwrap();
+    /// assert!(!timer.is_armed().unwrap());
+    ///
+    /// timer.reset(remaining, interval).unwrap();
+    /// assert!(timer.is_armed().unwrap());
+    /// ```
+    pub fn disarm(&mut self) -> Result<(Duration, Option<Duration>)> {
+        // Safe because we are zero-initializing structs with only primitive member fields.
+        let spec: libc::itimerspec = unsafe { mem::zeroed() };
+        let mut old_spec: libc::itimerspec = unsafe { mem::zeroed() };
+
+        // Safe because timerfd_settime is trusted to only modify `old_spec` and we check the
+        // return value.
+        let ret = unsafe { timerfd_settime(self.as_raw_fd(), 0, &spec, &mut old_spec) };
+        if ret < 0 {
+            return errno_result();
+        }
+
+        let remaining = Duration::new(
+            old_spec.it_value.tv_sec as u64,
+            old_spec.it_value.tv_nsec as u32,
+        );
+        let interval = Duration::new(
+            old_spec.it_interval.tv_sec as u64,
+            old_spec.it_interval.tv_nsec as u32,
+        );
+        Ok((remaining, Some(interval).filter(|i| !i.is_zero())))
+    }
 }
 
 impl AsRawFd for TimerFd {
@@ -199,9 +466,17 @@ impl AsRawFd for TimerFd {
     }
 }
 
+impl AsFd for TimerFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+// The timer is assumed to be based on `CLOCK_MONOTONIC`, which only matters for
+// `set_periodic_absolute`.
 impl FromRawFd for TimerFd {
     unsafe fn from_raw_fd(fd: RawFd) -> Self {
-        TimerFd(File::from_raw_fd(fd))
+        TimerFd(File::from_raw_fd(fd), CLOCK_MONOTONIC)
     }
 }
 
@@ -224,6 +499,12 @@ mod tests {
         assert_eq!(tfd.is_armed().unwrap(), false);
     }
 
+    #[test]
+    fn test_as_fd() {
+        let tfd = TimerFd::new().expect("failed to create timerfd");
+        assert_eq!(tfd.as_fd().as_raw_fd(), tfd.as_raw_fd());
+    }
+
     #[test]
     fn test_into_raw_fd() {
         let tfd = TimerFd::new().expect("failed to create timerfd");
@@ -249,6 +530,45 @@ mod tests {
         assert_eq!(tfd.is_armed().unwrap(), false);
     }
 
+    #[test]
+    fn test_arm_at() {
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        let deadline = Instant::now() + Duration::from_millis(50);
+        tfd.arm_at(deadline).expect("failed to arm timer");
+        assert!(tfd.is_armed().unwrap());
+        assert!(tfd.next_expiry().unwrap().unwrap() <= Duration::from_millis(50));
+
+        assert_eq!(tfd.wait().unwrap(), 1);
+        assert!(Instant::now() >= deadline);
+        assert!(!tfd.is_armed().unwrap());
+
+        // A deadline in the past fires immediately, instead of disarming the timer.
+        tfd.arm_at(deadline).expect("failed to arm timer");
+        assert_eq!(tfd.wait().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_boottime() {
+        let mut tfd = TimerFd::new_boottime().expect("failed to create timerfd");
+        assert!(!tfd.is_armed().unwrap());
+
+        let dur = Duration::from_millis(50);
+        let now = Instant::now();
+        tfd.reset(dur, None).expect("failed to arm timer");
+        assert!(tfd.is_armed().unwrap());
+        assert_eq!(tfd.wait().unwrap(), 1);
+        assert!(now.elapsed() >= dur);
+
+        // Absolute expirations are converted to the boottime clock.
+        let start = Instant::now();
+        tfd.set_periodic_absolute(start + dur, dur)
+            .expect("failed to arm timer");
+        let next = tfd.next_expiry().unwrap().unwrap();
+        assert!(next <= dur, "next = {:?}", next);
+        assert_eq!(tfd.wait().unwrap(), 1);
+        assert!(start.elapsed() >= dur);
+    }
+
     #[test]
     fn test_repeating() {
         let mut tfd = TimerFd::new().expect("failed to create timerfd");
@@ -264,4 +584,153 @@ mod tests {
         tfd.clear().expect("unable to clear the timer");
         assert_eq!(tfd.is_armed().unwrap(), false);
     }
+
+    #[test]
+    fn test_next_expiry() {
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        assert_eq!(tfd.next_expiry().unwrap(), None);
+
+        let dur = Duration::from_millis(500);
+        let interval = Duration::from_millis(100);
+        tfd.reset(dur, Some(interval)).expect("failed to arm timer");
+        let remaining = tfd.next_expiry().unwrap().unwrap();
+        assert!(remaining <= dur && remaining > Duration::from_millis(300));
+
+        // After the first expiration, the remaining time is bounded by the interval.
+        sleep(dur + interval / 2);
+        let remaining = tfd.next_expiry().unwrap().unwrap();
+        assert!(remaining <= interval);
+
+        tfd.clear().expect("unable to clear the timer");
+        assert_eq!(tfd.next_expiry().unwrap(), None);
+    }
+
+    #[test]
+    fn test_disarm() {
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        assert_eq!(tfd.disarm().unwrap(), (Duration::from_secs(0), None));
+
+        let dur = Duration::from_millis(100);
+        let interval = Duration::from_millis(50);
+        tfd.reset(dur, Some(interval)).expect("failed to arm timer");
+        let (remaining, old_interval) = tfd.disarm().unwrap();
+        assert!(!tfd.is_armed().unwrap());
+        assert!(remaining <= dur && remaining > Duration::from_millis(50));
+        assert_eq!(old_interval, Some(interval));
+
+        // Re-arming with the previous setting resumes the timer.
+        let now = Instant::now();
+        tfd.reset(remaining, old_interval)
+            .expect("failed to re-arm timer");
+        assert_eq!(tfd.wait().unwrap(), 1);
+        assert!(now.elapsed() >= remaining);
+        assert!(tfd.is_armed().unwrap());
+
+        // A one-shot timer that already expired is reported as disarmed.
+        tfd.reset(Duration::from_millis(10), None)
+            .expect("failed to arm timer");
+        sleep(Duration::from_millis(20));
+        assert_eq!(tfd.disarm().unwrap(), (Duration::from_secs(0), None));
+    }
+
+    #[test]
+    fn test_periodic_jittered() {
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        let interval = Duration::from_millis(50);
+        let jitter = Duration::from_millis(30);
+        let mut rng = Xorshift::new(7);
+
+        for _ in 0..5 {
+            let start = Instant::now();
+            tfd.set_periodic_jittered(interval, jitter, &mut rng)
+                .expect("failed to arm timer");
+            let remaining = tfd.next_expiry().unwrap().unwrap();
+            assert!(remaining < interval + jitter, "remaining = {:?}", remaining);
+            assert!(remaining + start.elapsed() >= interval);
+        }
+
+        // The following expirations are `interval` apart.
+        assert_eq!(tfd.wait().unwrap(), 1);
+        let remaining = tfd.next_expiry().unwrap().unwrap();
+        assert!(remaining <= interval && remaining > interval / 2);
+
+        // Without jitter the first expiration is after exactly `interval`.
+        tfd.set_periodic_jittered(interval, Duration::from_secs(0), &mut rng)
+            .expect("failed to arm timer");
+        let remaining = tfd.next_expiry().unwrap().unwrap();
+        assert!(remaining <= interval && remaining > interval / 2);
+    }
+
+    #[test]
+    fn test_periodic_absolute() {
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        let interval = Duration::from_millis(50);
+
+        let start = Instant::now();
+        tfd.set_periodic_absolute(start + interval, interval)
+            .expect("failed to arm timer");
+        assert!(tfd.is_armed().unwrap());
+        assert_eq!(tfd.wait().unwrap(), 1);
+        assert!(start.elapsed() >= interval);
+
+        // Reading late doesn't shift the schedule: the missed expirations are counted
+        // and the next one is still aligned to `start`.
+        sleep(interval * 3 + interval / 2);
+        let count = tfd.wait().unwrap();
+        let elapsed = start.elapsed();
+        assert!(count >= 3, "count = {}", count);
+        let next = tfd.next_expiry().unwrap().unwrap();
+        let ticks = (elapsed + next).as_millis() as f64 / interval.as_millis() as f64;
+        assert!((ticks - ticks.round()).abs() < 0.2, "ticks = {}", ticks);
+
+        // A first expiration in the past fires immediately, counting the missed ticks.
+        let start = Instant::now();
+        sleep(interval * 3);
+        tfd.set_periodic_absolute(start, interval)
+            .expect("failed to arm timer");
+        let count = tfd.wait().unwrap();
+        assert!(count >= 3, "count = {}", count);
+    }
+
+    #[test]
+    fn test_periodic_absolute_drift() {
+        let interval = Duration::from_millis(50);
+        // The time taken by the consumer to handle each tick.
+        let delay = Duration::from_millis(15);
+        let ticks = 4;
+
+        // Re-arming a relative timer after handling each tick accumulates the delays.
+        let mut tfd = TimerFd::new().expect("failed to create timerfd");
+        let start = Instant::now();
+        for _ in 0..ticks {
+            tfd.reset(interval, None).expect("failed to arm timer");
+            assert_eq!(tfd.wait().unwrap(), 1);
+            sleep(delay);
+        }
+        let relative_drift = start.elapsed() - interval * ticks;
+        assert!(
+            relative_drift >= delay * ticks,
+            "relative drift = {:?}",
+            relative_drift
+        );
+
+        // The absolute schedule doesn't depend on when the ticks are handled.
+        let start = Instant::now();
+        tfd.set_periodic_absolute(start + interval, interval)
+            .expect("failed to arm timer");
+        let mut count = 0;
+        while count < ticks {
+            count += tfd.wait().unwrap() as u32;
+            sleep(delay);
+        }
+        let absolute_drift = (start.elapsed() - delay)
+            .checked_sub(interval * ticks)
+            .unwrap_or_default();
+        assert!(
+            absolute_drift < interval / 2,
+            "absolute drift = {:?}",
+            absolute_drift
+        );
+        assert!(absolute_drift < relative_drift);
+    }
 }
@@ -9,10 +9,49 @@
 use std::cmp::min;
 use std::fs::File;
 use std::io::{Error, Result, Seek, SeekFrom, Write};
+use std::os::raw::c_ulong;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::AsRawFd;
 
 use crate::fallocate::{fallocate, FallocateMode};
+use crate::file_traits::FileSetLen;
+use crate::ioctl::ioctl_with_ref;
+
+// Block device ioctls taking a `[start, length]` range of bytes, from <linux/fs.h>.
+mod blk {
+    ioctl_io_nr!(BLKDISCARD, 0x12, 119);
+    ioctl_io_nr!(BLKZEROOUT, 0x12, 127);
+}
+
+fn is_block_device(file: &File) -> bool {
+    file.metadata()
+        .map(|m| m.file_type().is_block_device())
+        .unwrap_or(false)
+}
+
+// Issue `req` (BLKDISCARD or BLKZEROOUT) for a range of a block device, in chunks of at most
+// `PUNCH_HOLE_MAX_CHUNK_LEN` bytes for the same reason as `punch_hole_chunked`.
+fn blkdev_range_ioctl(file: &File, req: c_ulong, offset: u64, length: u64) -> Result<()> {
+    let mut done = 0;
+    while done < length {
+        let chunk_len = min(length - done, PUNCH_HOLE_MAX_CHUNK_LEN);
+        let range = [offset + done, chunk_len];
+        // Safe because these ioctls only read the range we pass, and we check the return value.
+        let ret = unsafe { ioctl_with_ref(file, req, &range) };
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+        done += chunk_len;
+    }
+    Ok(())
+}
 
 /// A trait for deallocating space in a file.
+///
+/// Together with [`WriteZeroes`](trait.WriteZeroes.html) and
+/// [`FileSetLen`](../file_traits/trait.FileSetLen.html), this allows disk backends to be
+/// generic over the type backing the disk, so that they can also run on top of in-memory
+/// implementations.
 pub trait PunchHole {
     /// Replace a range of bytes with a hole.
     ///
@@ -21,15 +60,286 @@ pub trait PunchHole {
     /// * `offset`: offset of the file where to replace with a hole.
     /// * `length`: the bytes of a hole to replace with.
     fn punch_hole(&mut self, offset: u64, length: u64) -> Result<()>;
+
+    /// Replace a range of bytes with a hole that reads back as zeroes.
+    ///
+    /// The default implementation calls [`punch_hole`](#tymethod.punch_hole), which is
+    /// enough when holes read back as zeroes. Implementations for which that is not the case
+    /// zero the range some other way, or fail.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: offset of the file where to replace with a hole.
+    /// * `length`: the bytes of a hole to replace with.
+    fn punch_zeroed_hole(&mut self, offset: u64, length: u64) -> Result<()> {
+        self.punch_hole(offset, length)
+    }
 }
 
+/// The maximum length of a hole punched by a single syscall in [`File::punch_hole`].
+///
+/// [`File::punch_hole`]: trait.PunchHole.html#tymethod.punch_hole
+pub const PUNCH_HOLE_MAX_CHUNK_LEN: u64 = 1 << 30;
+
+/// Replace a range of bytes of `file` with a hole, using chunks of at most `max_chunk_len`
+/// bytes.
+///
+/// Punching a very large hole with a single syscall can block for a long time. Splitting it
+/// keeps the operation interruptible: if a chunk fails, e.g. with `EINTR` because a signal was
+/// received, the error is returned and the remaining chunks are not processed.
+///
+/// # Arguments
+///
+/// * `file`: the file in which to punch the hole.
+/// * `offset`: offset of the file where to replace with a hole.
+/// * `length`: the bytes of a hole to replace with.
+/// * `max_chunk_len`: the maximum length of the hole punched by a single syscall.
+pub fn punch_hole_chunked(
+    file: &dyn AsRawFd,
+    offset: u64,
+    length: u64,
+    max_chunk_len: u64,
+) -> Result<()> {
+    if max_chunk_len == 0 {
+        return Err(Error::from_raw_os_error(libc::EINVAL));
+    }
+
+    let mut done = 0;
+    while done < length {
+        let chunk_len = min(length - done, max_chunk_len);
+        fallocate(
+            file,
+            FallocateMode::PunchHole,
+            true,
+            offset + done,
+            chunk_len,
+        )
+        .map_err(|e| Error::from_raw_os_error(e.errno()))?;
+        done += chunk_len;
+    }
+    Ok(())
+}
+
+/// On a block device, punching a hole discards the range with the `BLKDISCARD` ioctl, since
+/// block devices don't have holes. The range must then be aligned to the logical block size of
+/// the device, and whether it reads back as zeroes afterwards depends on the device. For this
+/// reason, `punch_zeroed_hole` zeroes the range with the `BLKZEROOUT` ioctl instead.
 impl PunchHole for File {
     fn punch_hole(&mut self, offset: u64, length: u64) -> Result<()> {
-        fallocate(self, FallocateMode::PunchHole, true, offset, length as u64)
+        if is_block_device(self) {
+            return blkdev_range_ioctl(self, blk::BLKDISCARD(), offset, length);
+        }
+        punch_hole_chunked(self, offset, length, PUNCH_HOLE_MAX_CHUNK_LEN)
+    }
+
+    fn punch_zeroed_hole(&mut self, offset: u64, length: u64) -> Result<()> {
+        if is_block_device(self) {
+            return blkdev_range_ioctl(self, blk::BLKZEROOUT(), offset, length);
+        }
+        punch_hole_chunked(self, offset, length, PUNCH_HOLE_MAX_CHUNK_LEN)
+    }
+}
+
+impl<T: PunchHole + ?Sized> PunchHole for &mut T {
+    fn punch_hole(&mut self, offset: u64, length: u64) -> Result<()> {
+        (**self).punch_hole(offset, length)
+    }
+
+    fn punch_zeroed_hole(&mut self, offset: u64, length: u64) -> Result<()> {
+        (**self).punch_zeroed_hole(offset, length)
+    }
+}
+
+/// A trait for zeroing a range of a file while keeping its space allocated.
+pub trait ZeroRange {
+    /// Replace a range of bytes with zeroes, without deallocating it.
+    ///
+    /// The file is extended if the range ends past its end.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: offset of the file where the range starts.
+    /// * `length`: the length of the range.
+    fn zero_range(&mut self, offset: u64, length: u64) -> Result<()>;
+}
+
+/// On a block device, the range is zeroed with the `BLKZEROOUT` ioctl, and must be aligned to
+/// the logical block size of the device.
+impl ZeroRange for File {
+    fn zero_range(&mut self, offset: u64, length: u64) -> Result<()> {
+        if is_block_device(self) {
+            return blkdev_range_ioctl(self, blk::BLKZEROOUT(), offset, length);
+        }
+        fallocate(self, FallocateMode::ZeroRange, false, offset, length)
             .map_err(|e| Error::from_raw_os_error(e.errno()))
     }
 }
 
+impl<T: ZeroRange + ?Sized> ZeroRange for &mut T {
+    fn zero_range(&mut self, offset: u64, length: u64) -> Result<()> {
+        (**self).zero_range(offset, length)
+    }
+}
+
+/// The way [`ZeroesWriter::write_zeroes_strategy`] zeroes a range.
+///
+/// [`ZeroesWriter::write_zeroes_strategy`]: struct.ZeroesWriter.html#method.write_zeroes_strategy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZeroStrategy {
+    /// Punch a hole, deallocating the range, and fall back to writing zeroes. The hole is
+    /// punched with [`PunchHole::punch_zeroed_hole`], so that it reads back as zeroes.
+    ///
+    /// [`PunchHole::punch_zeroed_hole`]: trait.PunchHole.html#method.punch_zeroed_hole
+    PreferHole,
+    /// Zero the range while keeping it allocated, and fall back to writing zeroes.
+    PreferZeroRange,
+    /// Always write buffers of zeroes.
+    ForceWrite,
+    /// Zero the range while keeping it allocated, then fall back to punching a hole and
+    /// finally to writing zeroes.
+    Auto,
+}
+
+fn is_unsupported(e: &Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS)
+    )
+}
+
+/// A wrapper for writing zeroes to ranges of a file with a choice of [`ZeroStrategy`].
+///
+/// The operations found to be unsupported by the wrapped file (i.e. failing with
+/// `EOPNOTSUPP` or `ENOSYS`) are remembered, so that they are not attempted again.
+///
+/// [`ZeroStrategy`]: enum.ZeroStrategy.html
+///
+/// # Examples
+///
+/// ```
+/// extern crate vmm_sys_util;
+/// use vmm_sys_util::tempfile::TempFile;
+/// use vmm_sys_util::write_zeroes::{ZeroStrategy, ZeroesWriter};
+///
+/// let tempfile = TempFile::new().unwrap();
+/// let mut writer = ZeroesWriter::new(tempfile.as_file().try_clone().unwrap());
+/// writer
+///     .write_zeroes_strategy(0, 4096, ZeroStrategy::Auto)
+///     .unwrap();
+/// assert_eq!(tempfile.as_file().metadata().unwrap().len(), 4096);
+/// ```
+pub struct ZeroesWriter<T> {
+    inner: T,
+    zero_range_supported: bool,
+    punch_hole_supported: bool,
+}
+
+impl<T: PunchHole + ZeroRange + FileSetLen + Seek + Write> ZeroesWriter<T> {
+    /// Create a new `ZeroesWriter` over `inner`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner`: the file to write zeroes to.
+    pub fn new(inner: T) -> Self {
+        ZeroesWriter {
+            inner,
+            zero_range_supported: true,
+            punch_hole_supported: true,
+        }
+    }
+
+    /// Returns a reference to the wrapped file.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped file.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the file.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Replace a range of bytes with zeroes, using `strategy`.
+    ///
+    /// The file is extended if the range ends past its end, and its seek cursor is not
+    /// changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset`: offset of the file where the range starts.
+    /// * `length`: the length of the range.
+    /// * `strategy`: the way the range is zeroed.
+    pub fn write_zeroes_strategy(
+        &mut self,
+        offset: u64,
+        length: u64,
+        strategy: ZeroStrategy,
+    ) -> Result<()> {
+        let end = offset
+            .checked_add(length)
+            .ok_or_else(|| Error::from_raw_os_error(libc::EINVAL))?;
+        if length == 0 {
+            return Ok(());
+        }
+
+        let (zero_range, punch_hole) = match strategy {
+            ZeroStrategy::PreferHole => (false, true),
+            ZeroStrategy::PreferZeroRange => (true, false),
+            ZeroStrategy::ForceWrite => (false, false),
+            ZeroStrategy::Auto => (true, true),
+        };
+
+        if zero_range && self.zero_range_supported {
+            match self.inner.zero_range(offset, length) {
+                Ok(()) => return Ok(()),
+                Err(ref e) if is_unsupported(e) => self.zero_range_supported = false,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if punch_hole && self.punch_hole_supported {
+            match self.inner.punch_zeroed_hole(offset, length) {
+                // Punching a hole doesn't extend the file.
+                Ok(()) => return self.extend_to(end),
+                Err(ref e) if is_unsupported(e) => self.punch_hole_supported = false,
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.write_zeroes_at(offset, length)
+    }
+
+    fn extend_to(&mut self, end: u64) -> Result<()> {
+        let pos = self.inner.stream_position()?;
+        let size = self.inner.seek(SeekFrom::End(0))?;
+        self.inner.seek(SeekFrom::Start(pos))?;
+        if end > size {
+            self.inner.set_len(end)?;
+        }
+        Ok(())
+    }
+
+    fn write_zeroes_at(&mut self, offset: u64, length: u64) -> Result<()> {
+        let pos = self.inner.stream_position()?;
+        self.inner.seek(SeekFrom::Start(offset))?;
+
+        let buf = vec![0u8; min(length, 0x10000) as usize];
+        let mut remaining = length;
+        while remaining > 0 {
+            let write_size = min(remaining, buf.len() as u64) as usize;
+            self.inner.write_all(&buf[..write_size])?;
+            remaining -= write_size as u64;
+        }
+
+        self.inner.seek(SeekFrom::Start(pos))?;
+        Ok(())
+    }
+}
+
 /// A trait for writing zeroes to a stream.
 pub trait WriteZeroes {
     /// Write zeroes to a stream.
@@ -43,7 +353,7 @@ impl<T: PunchHole + Seek + Write> WriteZeroes for T {
     fn write_zeroes(&mut self, length: usize) -> Result<usize> {
         // Try to punch a hole first.
         let offset = self.seek(SeekFrom::Current(0))?;
-        if let Ok(()) = self.punch_hole(offset, length as u64) {
+        if let Ok(()) = self.punch_zeroed_hole(offset, length as u64) {
             // Advance the seek cursor as if we had done a real write().
             self.seek(SeekFrom::Current(length as i64))?;
             return Ok(length);
@@ -69,23 +379,147 @@ impl<T: PunchHole + Seek + Write> WriteZeroes for T {
 #[allow(clippy::unused_io_amount)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
     use std::fs::OpenOptions;
-    use std::io::{Read, Seek, SeekFrom};
+    use std::io::{ErrorKind, Read, Seek, SeekFrom};
     use std::path::PathBuf;
 
     use crate::tempdir::TempDir;
 
-    #[test]
-    fn simple_test() {
-        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+    // An in-memory file, which optionally doesn't support punching holes or zeroing ranges.
+    struct MemFile {
+        data: RefCell<Vec<u8>>,
+        pos: u64,
+        punch_hole_supported: bool,
+        zero_range_supported: bool,
+        punch_hole_calls: usize,
+        zero_range_calls: usize,
+    }
+
+    impl MemFile {
+        fn new(punch_hole_supported: bool) -> Self {
+            MemFile {
+                data: RefCell::new(Vec::new()),
+                pos: 0,
+                punch_hole_supported,
+                zero_range_supported: false,
+                punch_hole_calls: 0,
+                zero_range_calls: 0,
+            }
+        }
+    }
+
+    impl Write for MemFile {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let mut data = self.data.borrow_mut();
+            let start = self.pos as usize;
+            let end = start + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[start..end].copy_from_slice(buf);
+            self.pos = end as u64;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for MemFile {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            let new_pos = match pos {
+                SeekFrom::Start(offset) => offset as i64,
+                SeekFrom::Current(offset) => self.pos as i64 + offset,
+                SeekFrom::End(offset) => self.data.borrow().len() as i64 + offset,
+            };
+            if new_pos < 0 {
+                return Err(Error::from(ErrorKind::InvalidInput));
+            }
+            self.pos = new_pos as u64;
+            Ok(self.pos)
+        }
+    }
+
+    impl PunchHole for MemFile {
+        fn punch_hole(&mut self, offset: u64, length: u64) -> Result<()> {
+            self.punch_hole_calls += 1;
+            if !self.punch_hole_supported {
+                return Err(Error::from_raw_os_error(libc::EOPNOTSUPP));
+            }
+            let mut data = self.data.borrow_mut();
+            let start = min(offset as usize, data.len());
+            let end = min((offset + length) as usize, data.len());
+            for b in data[start..end].iter_mut() {
+                *b = 0;
+            }
+            Ok(())
+        }
+    }
+
+    impl ZeroRange for MemFile {
+        fn zero_range(&mut self, offset: u64, length: u64) -> Result<()> {
+            self.zero_range_calls += 1;
+            if !self.zero_range_supported {
+                return Err(Error::from_raw_os_error(libc::EOPNOTSUPP));
+            }
+            let mut data = self.data.borrow_mut();
+            let end = (offset + length) as usize;
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            for b in data[offset as usize..end].iter_mut() {
+                *b = 0;
+            }
+            Ok(())
+        }
+    }
+
+    impl FileSetLen for MemFile {
+        fn set_len(&self, len: u64) -> Result<()> {
+            self.data.borrow_mut().resize(len as usize, 0);
+            Ok(())
+        }
+    }
+
+    // Generic backend logic: fill the disk with 0x55, zero out a range, then shrink it.
+    fn discard_and_shrink<T: PunchHole + WriteZeroes + FileSetLen + Seek + Write>(disk: &mut T) {
+        disk.set_len(0x4000).unwrap();
+        disk.seek(SeekFrom::Start(0)).unwrap();
+        disk.write_all(&[0x55u8; 0x4000]).unwrap();
+
+        disk.seek(SeekFrom::Start(0x1000)).unwrap();
+        assert_eq!(disk.write_zeroes(0x1000).unwrap(), 0x1000);
+        assert_eq!(disk.stream_position().unwrap(), 0x2000);
+
+        disk.set_len(0x3000).unwrap();
+        assert_eq!(disk.seek(SeekFrom::End(0)).unwrap(), 0x3000);
+    }
+
+    fn check_discarded(data: &[u8]) {
+        assert_eq!(data.len(), 0x3000);
+        assert!(data[..0x1000].iter().all(|b| *b == 0x55));
+        assert!(data[0x1000..0x2000].iter().all(|b| *b == 0));
+        assert!(data[0x2000..].iter().all(|b| *b == 0x55));
+    }
+
+    fn create_file(tempdir: &TempDir) -> File {
         let mut path = PathBuf::from(tempdir.as_path());
         path.push("file");
-        let mut f = OpenOptions::new()
+        OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(true)
             .open(&path)
-            .unwrap();
+            .unwrap()
+    }
+
+    #[test]
+    fn simple_test() {
+        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+        let mut f = create_file(&tempdir);
         f.set_len(16384).unwrap();
 
         // Write buffer of non-zero bytes to offset 1234
@@ -144,14 +578,7 @@ mod tests {
     #[test]
     fn large_write_zeroes() {
         let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
-        let mut path = PathBuf::from(tempdir.as_path());
-        path.push("file");
-        let mut f = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&path)
-            .unwrap();
+        let mut f = create_file(&tempdir);
         f.set_len(16384).unwrap();
 
         // Write buffer of non-zero bytes
@@ -178,4 +605,228 @@ mod tests {
             assert_eq!(*read, 0x55);
         }
     }
+
+    #[test]
+    fn generic_backend() {
+        for punch_hole_supported in &[true, false] {
+            let mut mem_file = MemFile::new(*punch_hole_supported);
+            discard_and_shrink(&mut mem_file);
+            check_discarded(&mem_file.data.borrow());
+        }
+
+        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+        let mut f = create_file(&tempdir);
+        // Run the backend through a mutable reference, as a caller borrowing the file would.
+        discard_and_shrink(&mut &mut f);
+
+        let mut data = Vec::new();
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_to_end(&mut data).unwrap();
+        check_discarded(&data);
+    }
+
+    // Fill a 0x3000 bytes file with 0x55, then zero a range extending it to 0x4000 bytes.
+    fn zero_with_strategy<T: PunchHole + ZeroRange + FileSetLen + Seek + Write>(
+        writer: &mut ZeroesWriter<T>,
+        strategy: ZeroStrategy,
+    ) {
+        let file = writer.get_mut();
+        file.set_len(0).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(&[0x55u8; 0x3000]).unwrap();
+        file.seek(SeekFrom::Start(0x100)).unwrap();
+
+        writer
+            .write_zeroes_strategy(0x1000, 0x3000, strategy)
+            .unwrap();
+        let file = writer.get_mut();
+        assert_eq!(file.stream_position().unwrap(), 0x100);
+        assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 0x4000);
+    }
+
+    fn check_zeroed(data: &[u8]) {
+        assert_eq!(data.len(), 0x4000);
+        assert!(data[..0x1000].iter().all(|b| *b == 0x55));
+        assert!(data[0x1000..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn write_zeroes_strategy() {
+        let strategies = [
+            ZeroStrategy::PreferHole,
+            ZeroStrategy::PreferZeroRange,
+            ZeroStrategy::ForceWrite,
+            ZeroStrategy::Auto,
+        ];
+        for strategy in &strategies {
+            for punch_hole_supported in &[true, false] {
+                for zero_range_supported in &[true, false] {
+                    let mut mem_file = MemFile::new(*punch_hole_supported);
+                    mem_file.zero_range_supported = *zero_range_supported;
+                    let mut writer = ZeroesWriter::new(mem_file);
+                    zero_with_strategy(&mut writer, *strategy);
+                    check_zeroed(&writer.get_ref().data.borrow());
+                }
+            }
+        }
+
+        // Only the operations allowed by the strategy are attempted.
+        let mut writer = ZeroesWriter::new(MemFile::new(true));
+        zero_with_strategy(&mut writer, ZeroStrategy::ForceWrite);
+        zero_with_strategy(&mut writer, ZeroStrategy::PreferHole);
+        assert_eq!(writer.get_ref().punch_hole_calls, 1);
+        assert_eq!(writer.get_ref().zero_range_calls, 0);
+
+        // The unsupported operations are only attempted once.
+        let mut writer = ZeroesWriter::new(MemFile::new(false));
+        zero_with_strategy(&mut writer, ZeroStrategy::Auto);
+        zero_with_strategy(&mut writer, ZeroStrategy::Auto);
+        zero_with_strategy(&mut writer, ZeroStrategy::PreferZeroRange);
+        let mem_file = writer.into_inner();
+        assert_eq!(mem_file.punch_hole_calls, 1);
+        assert_eq!(mem_file.zero_range_calls, 1);
+        check_zeroed(&mem_file.data.borrow());
+
+        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+        let f = create_file(&tempdir);
+        let mut writer = ZeroesWriter::new(f);
+        for strategy in &strategies {
+            zero_with_strategy(&mut writer, *strategy);
+            let mut data = Vec::new();
+            let f = writer.get_mut();
+            f.seek(SeekFrom::Start(0)).unwrap();
+            f.read_to_end(&mut data).unwrap();
+            check_zeroed(&data);
+        }
+
+        assert!(writer
+            .write_zeroes_strategy(u64::MAX, 1, ZeroStrategy::Auto)
+            .is_err());
+    }
+
+    // Loop device control ioctls, from <linux/loop.h>.
+    const LOOP_SET_FD: c_ulong = 0x4C00;
+    const LOOP_CLR_FD: c_ulong = 0x4C01;
+    const LOOP_CTL_GET_FREE: c_ulong = 0x4C82;
+
+    // A loop device backed by a file, detached when dropped.
+    struct LoopDevice(File);
+
+    impl LoopDevice {
+        // Returns `None` if loop devices can't be set up, e.g. without the privileges for it.
+        fn attach(backing: &File) -> Option<Self> {
+            let control = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open("/dev/loop-control")
+                .ok()?;
+            let num = unsafe { crate::ioctl::ioctl(&control, LOOP_CTL_GET_FREE) };
+            if num < 0 {
+                return None;
+            }
+            let device = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(format!("/dev/loop{}", num))
+                .ok()?;
+            let ret = unsafe {
+                crate::ioctl::ioctl_with_val(&device, LOOP_SET_FD, backing.as_raw_fd() as c_ulong)
+            };
+            if ret < 0 {
+                return None;
+            }
+            Some(LoopDevice(device))
+        }
+    }
+
+    impl Drop for LoopDevice {
+        fn drop(&mut self) {
+            unsafe { crate::ioctl::ioctl(&self.0, LOOP_CLR_FD) };
+        }
+    }
+
+    #[test]
+    fn block_device_ioctls() {
+        assert_eq!(blk::BLKDISCARD(), 0x1277);
+        assert_eq!(blk::BLKZEROOUT(), 0x127f);
+
+        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+        let mut backing = create_file(&tempdir);
+        backing.write_all(&[0x55u8; 0x10_0000]).unwrap();
+        backing.sync_all().unwrap();
+        assert!(!is_block_device(&backing));
+
+        let mut dev = match LoopDevice::attach(&backing) {
+            Some(dev) => dev,
+            // Loop devices are unavailable.
+            None => return,
+        };
+        let f = &mut dev.0;
+        assert!(is_block_device(f));
+
+        // The loop driver punches discarded ranges in the backing file.
+        let allocated = crate::file_traits::allocated_size(&backing).unwrap();
+        f.punch_hole(0x1000, 0x4_0000).unwrap();
+        assert!(crate::file_traits::allocated_size(&backing).unwrap() < allocated);
+
+        f.zero_range(0x8_0000, 0x1000).unwrap();
+        let mut readback = vec![0x11u8; 0x1000];
+        f.seek(SeekFrom::Start(0x8_0000)).unwrap();
+        f.read_exact(&mut readback).unwrap();
+        assert!(readback.iter().all(|b| *b == 0));
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_exact(&mut readback).unwrap();
+        assert!(readback.iter().all(|b| *b == 0x55));
+
+        // Ranges that aren't aligned to the block size are rejected.
+        let err = f.zero_range(1, 0x1000).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+
+        // `write_zeroes` zeroes the range instead of discarding it.
+        f.seek(SeekFrom::Start(0x2_0000)).unwrap();
+        assert_eq!(f.write_zeroes(0x1000).unwrap(), 0x1000);
+        assert_eq!(f.stream_position().unwrap(), 0x2_1000);
+        f.seek(SeekFrom::Start(0x2_0000)).unwrap();
+        f.read_exact(&mut readback).unwrap();
+        assert!(readback.iter().all(|b| *b == 0));
+
+        // So does `write_zeroes_strategy` when preferring holes.
+        let mut writer = ZeroesWriter::new(&mut *f);
+        writer
+            .write_zeroes_strategy(0x9_0000, 0x1000, ZeroStrategy::PreferHole)
+            .unwrap();
+        f.seek(SeekFrom::Start(0x9_0000)).unwrap();
+        f.read_exact(&mut readback).unwrap();
+        assert!(readback.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn chunked_punch_hole() {
+        let tempdir = TempDir::new_with_prefix("/tmp/write_zeroes_test").unwrap();
+        let mut f = create_file(&tempdir);
+
+        // A large logical range, of which only the part around the hole is backed by data.
+        const LEN: u64 = 0x1_0000_0000;
+        f.set_len(LEN).unwrap();
+        f.seek(SeekFrom::Start(0x10_0000)).unwrap();
+        f.write_all(&[0x55u8; 0x80_0000]).unwrap();
+
+        assert!(punch_hole_chunked(&f, 0, LEN, 0).is_err());
+        // Punch with chunks not aligned with the data, the last chunk being shorter.
+        punch_hole_chunked(&f, 0x1000, LEN - 0x2000, 0x10_1000).unwrap();
+        assert_eq!(f.metadata().unwrap().len(), LEN);
+
+        let mut readback = vec![0x11u8; 0xA0_0000];
+        f.seek(SeekFrom::Start(0)).unwrap();
+        f.read_exact(&mut readback).unwrap();
+        assert!(readback.iter().all(|b| *b == 0));
+
+        // The default chunk size is used when punching through the trait.
+        f.seek(SeekFrom::Start(0x10_0000)).unwrap();
+        f.write_all(&[0x55u8; 0x1000]).unwrap();
+        f.punch_hole(0, LEN).unwrap();
+        f.seek(SeekFrom::Start(0x10_0000)).unwrap();
+        f.read_exact(&mut readback[..0x1000]).unwrap();
+        assert!(readback[..0x1000].iter().all(|b| *b == 0));
+    }
 }